@@ -1,21 +1,43 @@
+use crate::console::{Console, HISTORY_LENGTH};
 use crate::event::AppEvent;
+use crate::input::completion::{AUTO_COMPLETE_DELAY, Completion, char_to_byte};
 use crate::input::handler;
 use crate::input::vim::CellVim;
-use crate::kernel::client::{KernelClient, KernelMessage};
+use crate::json_tree::{self, JsonView};
+use crate::kernel::backend::{KernelBackend, KernelConnection, KernelSource, KernelspecInfo};
+use crate::kernel::client::KernelMessage;
 use crate::kernel::manager::KernelManager;
-use crate::notebook::model::{CellOutput, CellType, ExecutionState, Notebook};
+use crate::kernel::monitor;
+use crate::kernel::server::ServerKernel;
+use crate::notebook::mime::MimeBundle;
+use crate::notebook::model::{
+    Cell, CellOutput, CellType, ExecutionState, Notebook, timing_timestamp,
+};
 use crate::ui;
 use crate::ui::highlight::Highlighter;
+use crate::ui::notify;
+use crate::ui::render_cache::RenderCache;
+use crate::variables::{self, Variable, VariablePanel};
 use anyhow::{Context, Result};
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
-use jupyter_protocol::JupyterMessageContent;
+use jupyter_protocol::{
+    HistoryEntry, IsCompleteReplyStatus, JupyterMessage, JupyterMessageContent, Payload,
+    ReplyStatus, Transient,
+};
 use ratatui::DefaultTerminal;
 use ratatui_image::picker::Picker;
-use ratatui_image::protocol::StatefulProtocol;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
 use tui_textarea::TextArea;
 
+/// Minimum time between automatic restarts after the kernel dies.
+const AUTO_RESTART_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Runs longer than this trigger a notification when they finish.
+pub const DEFAULT_NOTIFY_AFTER: Duration = Duration::from_secs(30);
+
 /// Direction for search (/ = forward, ? = backward).
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SearchDirection {
@@ -38,6 +60,18 @@ pub enum Mode {
     Command,
     /// Search input (/ or ?)
     Search,
+    /// Answering a kernel input_request (`input()`, `getpass`, `pdb`)
+    Input,
+    /// Choosing a kernelspec from the picker popup (`:kernel`)
+    KernelPicker,
+    /// Reading the pager popup (K / Shift-Tab documentation, kernel `page` output)
+    Pager,
+    /// Browsing the variable explorer panel
+    Variables,
+    /// Typing at the scratch console prompt
+    Console,
+    /// Browsing a JSON output as a collapsible tree
+    JsonTree,
 }
 
 impl Mode {
@@ -56,10 +90,94 @@ impl std::fmt::Display for Mode {
             Mode::CellVisual => write!(f, "CELL:VISUAL"),
             Mode::Command => write!(f, "COMMAND"),
             Mode::Search => write!(f, "SEARCH"),
+            Mode::Input => write!(f, "INPUT"),
+            Mode::KernelPicker => write!(f, "KERNEL"),
+            Mode::Pager => write!(f, "PAGER"),
+            Mode::Console => write!(f, "CONSOLE"),
+            Mode::Variables => write!(f, "VARIABLES"),
+            Mode::JsonTree => write!(f, "JSON"),
         }
     }
 }
 
+/// A pending input_request from the kernel, answered from the prompt line.
+pub struct InputPrompt {
+    /// The input_request message (the reply is sent as its child)
+    pub request: JupyterMessage,
+    pub prompt: String,
+    /// Mask the typed text (getpass)
+    pub password: bool,
+    /// Text typed so far
+    pub buffer: String,
+    /// Mode to return to once the prompt is answered
+    pub return_mode: Mode,
+}
+
+impl InputPrompt {
+    /// msg_id of the execute_request that asked for input.
+    fn execute_msg_id(&self) -> Option<&str> {
+        self.request
+            .parent_header
+            .as_ref()
+            .map(|h| h.msg_id.as_str())
+    }
+}
+
+/// The kernelspec picker popup opened by `:kernel`.
+pub struct KernelPicker {
+    pub kernels: Vec<KernelspecInfo>,
+    pub selected: usize,
+    /// Mode to return to when the picker closes
+    pub return_mode: Mode,
+}
+
+/// The scrollable popup showing documentation from an inspect_request
+/// (K / Shift-Tab) or `page` output from an execute_reply (`obj?`, `%magic`).
+pub struct Pager {
+    /// text/plain data (may contain ANSI escapes)
+    pub text: String,
+    /// First line shown
+    pub scroll: u16,
+    /// The inspect_request shown, if any, kept to re-ask at another detail level
+    pub inspect: Option<InspectTarget>,
+    /// Mode to return to when the pager closes
+    pub return_mode: Mode,
+}
+
+/// What an inspect_request asked about.
+pub struct InspectTarget {
+    code: String,
+    cursor_pos: usize,
+    /// 0 for `obj?`, 1 for `obj??` (source)
+    pub detail_level: usize,
+}
+
+/// An in-flight complete_request.
+struct CompletionRequest {
+    generation: u64,
+    /// Cursor offset when the request was sent
+    offset: usize,
+    /// Asked for with Tab (report empty results) rather than after `.`
+    manual: bool,
+}
+
+/// An in-flight inspect_request.
+struct PendingInspect {
+    generation: u64,
+    code: String,
+    cursor_pos: usize,
+    detail_level: usize,
+    /// Mode when the request was sent; the reply is dropped if it changed
+    mode: Mode,
+}
+
+/// An in-flight is_complete_request for the console's input.
+struct ConsoleCheck {
+    generation: u64,
+    /// The input when Enter was pressed; the reply is dropped if it changed
+    code: String,
+}
+
 /// The main application state.
 pub struct App {
     pub mode: Mode,
@@ -71,6 +189,9 @@ pub struct App {
     pub kernel_status: String,
     pub should_quit: bool,
 
+    /// Something on screen changed since the last frame
+    pub needs_redraw: bool,
+
     /// Active text editor for the selected cell (only Some when in a cell mode)
     pub editor: Option<TextArea<'static>>,
 
@@ -96,39 +217,149 @@ pub struct App {
     /// Used to highlight matches across cells when searching from Normal mode.
     pub search_matches: Vec<(usize, usize, usize, usize)>,
 
-    /// Tab completion state
-    pub completions: Vec<String>,
-    pub completion_selected: usize,
-    pub completion_cursor_start: usize,
-    pub completion_cursor_end: usize,
+    /// Open completion panel
+    pub completion: Option<Completion>,
+
+    /// Completion request awaiting its reply
+    completion_request: Option<CompletionRequest>,
+
+    /// Bumped on every completion request and dismissal, so stale replies are dropped
+    completion_generation: u64,
+
+    /// When to request completions automatically (set after typing `.`)
+    completion_due: Option<Instant>,
+
+    /// Request completions automatically after `.` (`:set autocomplete`)
+    pub auto_complete: bool,
 
     /// Syntax highlighter (syntect-based) for code cells
     pub highlighter: Highlighter,
 
-    /// Image picker for ratatui-image (Kitty graphics protocol)
+    /// Image picker for ratatui-image (protocol detected from the terminal
+    /// or chosen with `--image-protocol`)
     pub picker: Picker,
 
-    /// Cached image render states, keyed by (cell_index, output_index)
-    pub image_states: HashMap<(usize, usize), StatefulProtocol>,
+    /// Draw images as `[image 640x480 png]` lines (`--image-protocol none`)
+    pub image_placeholders: bool,
+
+    /// Output layouts (decoded image sizes, wrapped text, image protocol
+    /// states) and rendered markdown, reused between frames
+    pub render_cache: RenderCache,
+
+    /// Columns the HTML tables in a cell's outputs are scrolled right by,
+    /// keyed by cell id
+    pub table_scroll: HashMap<String, usize>,
+
+    /// Expansion, cursor and search state of JSON output trees, keyed by
+    /// (cell id, output index) so they follow cells when they are moved
+    pub json_views: HashMap<(String, usize), JsonView>,
+
+    /// The JSON output browsed in `Mode::JsonTree`
+    pub json_focus: Option<(String, usize)>,
+
+    /// Search being typed after `/` in the JSON tree
+    pub json_search: Option<String>,
+
+    /// Maps kernel execute_request msg_id -> cell id for correlating IOPub
+    /// responses. Output for a cell deleted mid-run is discarded.
+    executing_cells: HashMap<String, String>,
+
+    /// Ids of code cells waiting to run, in order. Cells are sent one at a
+    /// time and the rest are skipped if one fails.
+    execution_queue: VecDeque<String>,
+
+    /// msg_id of the queued cell currently running
+    queue_running: Option<String>,
 
-    /// Maps kernel execute_request msg_id -> cell index for correlating IOPub responses
-    executing_cells: HashMap<String, usize>,
+    /// When the current run (one cell or a whole queue) started
+    queue_started: Option<Instant>,
+
+    /// Ring the bell and send a desktop notification when a run that took
+    /// at least this long finishes (`--notify-after`, `:set notifyafter=N`)
+    pub notify_after: Option<Duration>,
+
+    /// Recently finished executions (msg_id, cell id), for execute_replies
+    /// that arrive on the shell channel after IOPub went idle
+    finished_runs: VecDeque<(String, String)>,
+
+    /// Maps transient display_id -> every (cell id, output index) showing it,
+    /// so update_display_data can replace outputs in place (even across cells)
+    display_outputs: HashMap<String, Vec<(String, usize)>>,
+
+    /// Ids of cells to clear when their next output arrives (clear_output with wait=True)
+    pending_clear: HashSet<String>,
 
     /// Yanked cell buffer for yy/p cell operations
     pub yanked_cell: Option<crate::notebook::model::Cell>,
 
+    /// Pending kernel input_request, shown as a prompt line above the status bar
+    pub input_prompt: Option<InputPrompt>,
+
+    /// Open kernelspec picker popup
+    pub kernel_picker: Option<KernelPicker>,
+
+    /// Open pager popup
+    pub pager: Option<Pager>,
+
+    /// The last `page` output from the kernel, reopened with `:pager`
+    last_page: Option<String>,
+
+    /// Inspect request awaiting its reply
+    inspect_request: Option<PendingInspect>,
+
+    /// Bumped on every inspect request, so stale replies are dropped
+    inspect_generation: u64,
+
+    /// Scratch console pane (`` ` ``, `:console`)
+    pub console: Console,
+
+    /// is_complete_request awaiting its reply
+    console_check: Option<ConsoleCheck>,
+
+    /// Bumped on every is_complete_request, so stale replies are dropped
+    console_generation: u64,
+
+    /// Variable explorer side panel (`V`, `:vars`)
+    pub variables: VariablePanel,
+
+    /// An execution finished since the variable list was last requested
+    variables_stale: bool,
+
+    /// Bumped on every namespace listing request, so stale replies are dropped
+    variables_generation: u64,
+
+    /// Bumped on every variable detail request, so stale replies are dropped
+    variable_detail_generation: u64,
+
+    /// Restart the kernel automatically when it dies (`--auto-restart`, `:set autorestart`)
+    pub auto_restart: bool,
+
+    /// Save execution timestamps in each cell's metadata.execution (`:set recordtiming`)
+    pub record_timing: bool,
+
+    /// When the kernel was last restarted automatically, to avoid restart loops
+    last_auto_restart: Option<Instant>,
+
+    /// Incremented on every restart so stale death reports can be ignored
+    kernel_session: u64,
+
+    /// Heartbeat monitor for the current kernel (ZMQ kernels only)
+    heartbeat_monitor: Option<JoinHandle<()>>,
+
     /// Event sender for forwarding kernel messages on restart
     event_tx: mpsc::UnboundedSender<crate::event::AppEvent>,
 
     // Kernel communication
-    kernel_manager: KernelManager,
-    pub kernel_client: KernelClient,
+    kernel_manager: Box<dyn KernelBackend>,
+    pub kernel_client: Box<dyn KernelConnection>,
 }
 
 impl App {
     /// Initialize the application: start kernel, connect, load/create notebook.
     pub async fn new(
         file_path: Option<&str>,
+        source: &KernelSource,
+        kernel: Option<&str>,
         event_tx: mpsc::UnboundedSender<crate::event::AppEvent>,
         picker: Picker,
     ) -> Result<(Self, mpsc::UnboundedReceiver<KernelMessage>)> {
@@ -146,24 +377,69 @@ impl App {
             Notebook::new()
         };
 
-        // Determine kernel name from notebook metadata
-        let kernel_name = notebook
-            .metadata
-            .kernel_name
-            .as_deref()
-            .unwrap_or("python3");
-
-        // Start kernel
-        let kernel_manager = KernelManager::start(Some(kernel_name))
-            .await
-            .context("Failed to start kernel")?;
+        // Determine kernel name from --kernel or the notebook metadata
+        let kernel_name = kernel
+            .or(notebook.metadata.kernel_name())
+            .unwrap_or("python3")
+            .to_string();
+        let kernel_name = kernel_name.as_str();
+
+        // Start a kernel, attach to one started elsewhere, or start one on a server
+        let kernel_manager: Box<dyn KernelBackend> = match source {
+            KernelSource::Local => Box::new(
+                KernelManager::start(Some(kernel_name))
+                    .await
+                    .context("Failed to start kernel")?,
+            ),
+            KernelSource::Existing(connection_file) => Box::new(
+                KernelManager::attach(connection_file)
+                    .await
+                    .context("Failed to attach to existing kernel")?,
+            ),
+            KernelSource::Server { url, token } => Box::new(
+                ServerKernel::start(url, token.clone(), Some(kernel_name))
+                    .await
+                    .context("Failed to start server kernel")?,
+            ),
+        };
 
         // Connect to kernel
-        let (kernel_client, kernel_rx) = KernelClient::connect(kernel_manager.connection_info())
+        let (kernel_client, kernel_rx) = kernel_manager
+            .connect()
             .await
             .context("Failed to connect to kernel")?;
 
-        let mut app = Self {
+        let mut app = Self::with_kernel(notebook, kernel_manager, kernel_client, event_tx, picker);
+
+        // Send kernel_info_request to trigger a status: idle message on IOPub,
+        // so the status bar updates once the kernel is actually ready.
+        let _ = app.kernel_client.request_kernel_info().await;
+
+        // --kernel overrides the notebook's kernelspec; record the choice
+        if let Some(name) = kernel
+            && app.notebook.metadata.kernel_name() != Some(name)
+            && let Ok(specs) = app.kernel_manager.kernelspecs().await
+            && let Some(spec) = specs.iter().find(|s| s.name == name)
+        {
+            app.record_kernelspec(spec);
+        }
+
+        Ok((app, kernel_rx))
+    }
+
+    /// Build the app around a kernel that is already connected.
+    pub fn with_kernel(
+        notebook: Notebook,
+        kernel_manager: Box<dyn KernelBackend>,
+        kernel_client: Box<dyn KernelConnection>,
+        event_tx: mpsc::UnboundedSender<crate::event::AppEvent>,
+        picker: Picker,
+    ) -> Self {
+        let heartbeat_monitor = kernel_manager
+            .connection_info()
+            .map(|info| monitor::spawn_heartbeat_monitor(info.clone(), 0, event_tx.clone()));
+
+        Self {
             mode: Mode::Normal,
             notebook,
             selected_cell: 0,
@@ -172,6 +448,7 @@ impl App {
             status_message: String::from("Kernel starting..."),
             kernel_status: String::from("starting"),
             should_quit: false,
+            needs_redraw: true,
             editor: None,
             cell_vim: CellVim::new(),
             normal_count: None,
@@ -180,34 +457,186 @@ impl App {
             last_search: None,
             search_from_cell: false,
             search_matches: Vec::new(),
-            completions: Vec::new(),
-            completion_selected: 0,
-            completion_cursor_start: 0,
-            completion_cursor_end: 0,
+            completion: None,
+            completion_request: None,
+            completion_generation: 0,
+            completion_due: None,
+            auto_complete: true,
             highlighter: Highlighter::new(),
             picker,
-            image_states: HashMap::new(),
+            image_placeholders: false,
+            render_cache: RenderCache::default(),
+            table_scroll: HashMap::new(),
+            json_views: HashMap::new(),
+            json_focus: None,
+            json_search: None,
             executing_cells: HashMap::new(),
+            execution_queue: VecDeque::new(),
+            queue_running: None,
+            queue_started: None,
+            notify_after: Some(DEFAULT_NOTIFY_AFTER),
+            finished_runs: VecDeque::new(),
+            display_outputs: HashMap::new(),
+            pending_clear: HashSet::new(),
             yanked_cell: None,
+            input_prompt: None,
+            kernel_picker: None,
+            pager: None,
+            last_page: None,
+            inspect_request: None,
+            inspect_generation: 0,
+            console: Console::new(),
+            console_check: None,
+            console_generation: 0,
+            variables: VariablePanel::new(),
+            variables_stale: false,
+            variables_generation: 0,
+            variable_detail_generation: 0,
+            auto_restart: false,
+            record_timing: false,
+            last_auto_restart: None,
+            kernel_session: 0,
+            heartbeat_monitor,
             event_tx,
             kernel_manager,
             kernel_client,
-        };
-
-        // Send kernel_info_request to trigger a status: idle message on IOPub,
-        // so the status bar updates once the kernel is actually ready.
-        let _ = app.kernel_client.request_kernel_info().await;
-
-        Ok((app, kernel_rx))
+        }
     }
 
     /// Handle an incoming application event.
+    /// Every event but `Tick` marks the screen for redrawing.
     pub async fn handle_event(&mut self, event: AppEvent) -> Result<()> {
+        if !matches!(event, AppEvent::Tick) {
+            self.needs_redraw = true;
+        }
         match event {
             AppEvent::Key(key) => self.handle_key(key).await?,
-            AppEvent::Kernel(msg) => self.handle_kernel_message(msg),
+            AppEvent::Kernel(msg) => {
+                self.handle_kernel_message(msg);
+                if self.variables_stale {
+                    self.refresh_variables().await;
+                }
+                self.run_next_queued().await;
+            }
+            AppEvent::KernelDied { session, reason } => {
+                if session == self.kernel_session {
+                    self.handle_kernel_died(&reason).await?;
+                }
+            }
+            AppEvent::CompletionReply { generation, reply } => {
+                self.show_completions(generation, reply);
+            }
+            AppEvent::InspectReply { generation, reply } => {
+                self.show_inspect(generation, reply);
+            }
+            AppEvent::HistoryReply(reply) => match reply {
+                Ok(reply) => self
+                    .console
+                    .history
+                    .load(reply.history.into_iter().map(|entry| match entry {
+                        HistoryEntry::Input(_, _, source)
+                        | HistoryEntry::InputOutput(_, _, (source, _)) => source,
+                    })),
+                Err(e) => self.status_message = format!("History error: {}", e),
+            },
+            AppEvent::IsCompleteReply { generation, reply } => {
+                self.handle_is_complete(generation, reply).await;
+            }
+            AppEvent::VariablesReply { generation, reply } => {
+                self.show_variables(generation, reply);
+            }
+            AppEvent::VariableDetail { generation, reply } => {
+                self.show_variable_detail(generation, reply);
+            }
             AppEvent::Resize(_, _) => {} // ratatui handles this
-            AppEvent::Tick => {}
+            AppEvent::Tick => {
+                // Running cells show a live timer
+                if self.is_animating() {
+                    self.needs_redraw = true;
+                }
+
+                if self.completion_due.is_some_and(|due| Instant::now() >= due) {
+                    self.completion_due = None;
+                    if self.mode == Mode::CellInsert && self.completion.is_none() {
+                        self.request_completion(false).await;
+                        self.needs_redraw = true;
+                    }
+                }
+
+                // Catch crashes (OOM kill, segfault) as soon as the process exits,
+                // without waiting for the heartbeat to time out.
+                if self.kernel_status != "dead"
+                    && let Some(status) = self.kernel_manager.exit_status()
+                {
+                    self.handle_kernel_died(&format!("process exited, {}", status))
+                        .await?;
+                    self.needs_redraw = true;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the screen changes on its own: a running cell's timer ticks.
+    fn is_animating(&self) -> bool {
+        self.notebook
+            .cells
+            .iter()
+            .any(|cell| cell.run_started.is_some())
+    }
+
+    /// The kernel is gone: fail in-flight cells, show it in the status bar,
+    /// and restart if configured to.
+    async fn handle_kernel_died(&mut self, reason: &str) -> Result<()> {
+        if self.kernel_status == "dead" {
+            return Ok(());
+        }
+        self.kernel_status = "dead".to_string();
+        if let Some(monitor) = self.heartbeat_monitor.take() {
+            monitor.abort();
+        }
+        self.dismiss_input_prompt();
+        self.pending_clear.clear();
+        self.console.running = None;
+        self.console.finished = None;
+        self.skip_queued_cells();
+        if let Some(started) = self.queue_started.take() {
+            self.notify_if_long(started.elapsed(), "Kernel died");
+        }
+
+        for (_, cell_id) in self.executing_cells.drain() {
+            if let Some(cell) = self.notebook.cell_mut(&cell_id) {
+                cell.outputs.push(CellOutput::Error {
+                    ename: "DeadKernelError".to_string(),
+                    evalue: "Kernel died".to_string(),
+                    traceback: vec![format!(
+                        "\x1b[31mDeadKernelError\x1b[0m: Kernel died ({})",
+                        reason
+                    )],
+                });
+                cell.execution_state = ExecutionState::Error;
+                cell.run_started = None;
+            }
+        }
+
+        // Don't keep restarting a kernel that dies right after it comes back up
+        let recently_restarted = self
+            .last_auto_restart
+            .is_some_and(|t| t.elapsed() < AUTO_RESTART_COOLDOWN);
+        if self.auto_restart && !recently_restarted && !self.kernel_manager.is_attached() {
+            self.last_auto_restart = Some(Instant::now());
+            // A failed restart must not take the app (and unsaved edits) down
+            match self.restart_kernel().await {
+                Ok(()) => self.status_message = format!("Kernel died ({}); restarted", reason),
+                Err(e) => {
+                    self.kernel_status = "dead".to_string();
+                    self.status_message =
+                        format!("Kernel died ({}); restart failed: {:#}", reason, e);
+                }
+            }
+        } else {
+            self.status_message =
+                format!("Kernel died ({}). Use :restart to start a new one", reason);
         }
         Ok(())
     }
@@ -235,7 +664,10 @@ impl App {
                     self.exit_cell();
                 }
                 handler::CellInsertAction::RequestCompletion => {
-                    self.request_completion().await;
+                    self.request_completion(true).await;
+                }
+                handler::CellInsertAction::Inspect => {
+                    self.inspect_at_cursor().await;
                 }
                 handler::CellInsertAction::None => {}
             },
@@ -249,6 +681,12 @@ impl App {
             }
             Mode::Command => handler::handle_command_mode(self, key).await?,
             Mode::Search => handler::handle_search_mode(self, key),
+            Mode::Input => handler::handle_input_mode(self, key),
+            Mode::KernelPicker => handler::handle_kernel_picker_mode(self, key).await?,
+            Mode::Pager => handler::handle_pager_mode(self, key).await,
+            Mode::Console => handler::handle_console_mode(self, key).await,
+            Mode::Variables => handler::handle_variables_mode(self, key).await,
+            Mode::JsonTree => handler::handle_json_tree_mode(self, key),
         }
 
         Ok(())
@@ -264,219 +702,1054 @@ impl App {
                     .as_ref()
                     .map(|h| h.msg_id.as_str());
 
+                // A late status from the dead kernel must not hide that it died
+                if self.kernel_status == "dead"
+                    && matches!(jupyter_msg.content, JupyterMessageContent::Status(_))
+                {
+                    return;
+                }
+
+                if parent_msg_id.is_some() && parent_msg_id == self.console.running.as_deref() {
+                    self.handle_console_output(&jupyter_msg);
+                    return;
+                }
+
                 match &jupyter_msg.content {
+                    JupyterMessageContent::Status(status)
+                        if status.execution_state == jupyter_protocol::ExecutionState::Dead =>
+                    {
+                        // Server-hosted kernels report death over IOPub
+                        let _ = self.event_tx.send(AppEvent::KernelDied {
+                            session: self.kernel_session,
+                            reason: "reported dead by the server".to_string(),
+                        });
+                    }
                     JupyterMessageContent::Status(status) => {
                         self.kernel_status = format!("{:?}", status.execution_state).to_lowercase();
                         if self.status_message == "Kernel starting..." {
                             self.status_message = String::new();
                         }
 
-                        // When idle arrives with a matching parent_header, mark cell as Done
-                        if self.kernel_status == "idle" {
-                            if let Some(msg_id) = parent_msg_id {
-                                if let Some(cell_idx) = self.executing_cells.remove(msg_id) {
-                                    if cell_idx < self.notebook.cells.len() {
-                                        let cell = &mut self.notebook.cells[cell_idx];
-                                        // Only set Done if not already Error
-                                        if cell.execution_state == ExecutionState::Running {
-                                            cell.execution_state = ExecutionState::Done;
-                                        }
+                        // Record timing for the cell this status belongs to
+                        if let Some(cell_idx) = self.executing_cell(parent_msg_id) {
+                            let cell = &mut self.notebook.cells[cell_idx];
+                            let timestamp = timing_timestamp(&jupyter_msg.header.date);
+                            match status.execution_state {
+                                jupyter_protocol::ExecutionState::Busy => {
+                                    if self.record_timing {
+                                        cell.execution_timing().iopub_status_busy = Some(timestamp);
+                                    }
+                                    cell.run_started.get_or_insert_with(Instant::now);
+                                }
+                                jupyter_protocol::ExecutionState::Idle => {
+                                    if self.record_timing {
+                                        cell.execution_timing().iopub_status_idle = Some(timestamp);
+                                    }
+                                    if let Some(started) = cell.run_started.take() {
+                                        cell.last_run = Some(started.elapsed());
                                     }
                                 }
+                                _ => {}
                             }
                         }
-                    }
-                    JupyterMessageContent::StreamContent(stream) => {
-                        if let Some(cell_idx) =
-                            parent_msg_id.and_then(|id| self.executing_cells.get(id).copied())
+
+                        // When idle arrives with a matching parent_header, mark cell as Done
+                        if self.kernel_status == "idle"
+                            && let Some(msg_id) = parent_msg_id
                         {
-                            if cell_idx < self.notebook.cells.len() {
-                                let cell = &mut self.notebook.cells[cell_idx];
-                                let stream_name = format!("{:?}", stream.name).to_lowercase();
-                                // Append to existing stream output if same name, else new entry
-                                let appended = cell.outputs.iter_mut().any(|o| {
-                                    if let CellOutput::Stream { name, text } = o {
-                                        if *name == stream_name {
-                                            text.push_str(&stream.text);
-                                            return true;
-                                        }
-                                    }
-                                    false
-                                });
-                                if !appended {
-                                    cell.outputs.push(CellOutput::Stream {
-                                        name: stream_name,
-                                        text: stream.text.clone(),
-                                    });
+                            // A prompt left open by an interrupted execution is stale now
+                            if self
+                                .input_prompt
+                                .as_ref()
+                                .is_some_and(|p| p.execute_msg_id() == Some(msg_id))
+                            {
+                                self.dismiss_input_prompt();
+                            }
+
+                            if let Some(cell_id) = self.executing_cells.remove(msg_id) {
+                                self.remember_finished_run(msg_id, &cell_id);
+                                self.variables_stale = true;
+                                // The cell may have been deleted while it ran.
+                                // The queue moves on at the execute_reply.
+                                if let Some(cell) = self.notebook.cell_mut(&cell_id)
+                                    && cell.execution_state == ExecutionState::Running
+                                {
+                                    cell.execution_state = ExecutionState::Done;
                                 }
                             }
                         }
                     }
+                    JupyterMessageContent::StreamContent(stream) => {
+                        if let Some(cell_idx) = self.output_cell(parent_msg_id) {
+                            let stream_name = format!("{:?}", stream.name).to_lowercase();
+                            self.notebook.cells[cell_idx].append_stream(&stream_name, &stream.text);
+                        }
+                    }
                     JupyterMessageContent::ExecuteResult(result) => {
-                        if let Some(cell_idx) =
-                            parent_msg_id.and_then(|id| self.executing_cells.get(id).copied())
-                        {
-                            if cell_idx < self.notebook.cells.len() {
-                                let cell = &mut self.notebook.cells[cell_idx];
-                                let mut data = std::collections::HashMap::new();
-                                for mt in &result.data.content {
-                                    let (mime, val) =
-                                        crate::notebook::model::media_type_to_pair_pub(mt);
-                                    data.insert(mime, val);
-                                }
-                                cell.outputs.push(CellOutput::ExecuteResult {
-                                    execution_count: result.execution_count.value(),
-                                    data,
-                                });
-                                cell.execution_count = Some(result.execution_count.value());
-                            }
+                        if let Some(cell_idx) = self.output_cell(parent_msg_id) {
+                            let cell = &mut self.notebook.cells[cell_idx];
+                            cell.outputs.push(CellOutput::ExecuteResult {
+                                execution_count: result.execution_count.value(),
+                                data: MimeBundle::from_media(&result.data),
+                                metadata: result.metadata.clone(),
+                            });
+                            cell.execution_count = Some(result.execution_count.value());
+                            let output_idx = cell.outputs.len() - 1;
+                            self.register_display(result.transient.as_ref(), cell_idx, output_idx);
                         }
                     }
                     JupyterMessageContent::ErrorOutput(error) => {
-                        if let Some(cell_idx) =
-                            parent_msg_id.and_then(|id| self.executing_cells.get(id).copied())
-                        {
-                            if cell_idx < self.notebook.cells.len() {
-                                let cell = &mut self.notebook.cells[cell_idx];
-                                cell.outputs.push(CellOutput::Error {
-                                    ename: error.ename.clone(),
-                                    evalue: error.evalue.clone(),
-                                    traceback: error.traceback.clone(),
-                                });
-                                cell.execution_state = ExecutionState::Error;
-                            }
+                        if let Some(cell_idx) = self.output_cell(parent_msg_id) {
+                            let cell = &mut self.notebook.cells[cell_idx];
+                            cell.outputs.push(CellOutput::Error {
+                                ename: error.ename.clone(),
+                                evalue: error.evalue.clone(),
+                                traceback: error.traceback.clone(),
+                            });
+                            cell.execution_state = ExecutionState::Error;
                         }
                     }
                     JupyterMessageContent::DisplayData(display) => {
-                        if let Some(cell_idx) =
-                            parent_msg_id.and_then(|id| self.executing_cells.get(id).copied())
-                        {
-                            if cell_idx < self.notebook.cells.len() {
-                                let cell = &mut self.notebook.cells[cell_idx];
-                                let mut data = std::collections::HashMap::new();
-                                for mt in &display.data.content {
-                                    let (mime, val) =
-                                        crate::notebook::model::media_type_to_pair_pub(mt);
-                                    data.insert(mime, val);
-                                }
-                                cell.outputs.push(CellOutput::DisplayData { data });
+                        if let Some(cell_idx) = self.output_cell(parent_msg_id) {
+                            let cell = &mut self.notebook.cells[cell_idx];
+                            cell.outputs.push(CellOutput::DisplayData {
+                                data: MimeBundle::from_media(&display.data),
+                                metadata: display.metadata.clone(),
+                            });
+                            let output_idx = cell.outputs.len() - 1;
+                            self.register_display(display.transient.as_ref(), cell_idx, output_idx);
+                        }
+                    }
+                    JupyterMessageContent::UpdateDisplayData(update) => {
+                        if let Some(display_id) = &update.transient.display_id {
+                            self.update_display(display_id, &update.data, &update.metadata);
+                        }
+                    }
+                    JupyterMessageContent::ClearOutput(clear) => {
+                        if let Some(cell_idx) = self.executing_cell(parent_msg_id) {
+                            if clear.wait {
+                                // Defer until the next output to avoid flicker
+                                let cell_id = self.notebook.cells[cell_idx].id.clone();
+                                self.pending_clear.insert(cell_id);
+                            } else {
+                                self.clear_cell_outputs(cell_idx);
                             }
                         }
                     }
                     JupyterMessageContent::ExecuteInput(_) => {
-                        // Kernel echoes the input -- we can ignore this
+                        // The kernel started on the code: start the clock
+                        if let Some(cell_idx) = self.executing_cell(parent_msg_id) {
+                            let cell = &mut self.notebook.cells[cell_idx];
+                            if self.record_timing {
+                                cell.execution_timing().iopub_execute_input =
+                                    Some(timing_timestamp(&jupyter_msg.header.date));
+                            }
+                            cell.run_started = Some(Instant::now());
+                        }
                     }
                     _ => {}
                 }
             }
-            KernelMessage::ShellReply(_) => {}
+            KernelMessage::StdinRequest(jupyter_msg) => {
+                if let JupyterMessageContent::InputRequest(request) = &jupyter_msg.content {
+                    let return_mode = match self.input_prompt.take() {
+                        Some(previous) => previous.return_mode,
+                        None => self.mode.clone(),
+                    };
+                    self.input_prompt = Some(InputPrompt {
+                        prompt: request.prompt.clone(),
+                        password: request.password,
+                        buffer: String::new(),
+                        return_mode,
+                        request: jupyter_msg,
+                    });
+                    self.clear_completions();
+                    self.mode = Mode::Input;
+                }
+            }
+            KernelMessage::ShellReply(reply) => {
+                if let JupyterMessageContent::ExecuteReply(_) = &reply.content {
+                    self.handle_execute_reply(&reply);
+                }
+            }
             KernelMessage::IoPubError(e) => {
                 self.status_message = format!("IOPub error: {}", e);
             }
         }
     }
 
-    /// Execute the currently selected cell.
-    pub async fn execute_selected_cell(&mut self) -> Result<()> {
-        // If we're in insert mode, sync the editor content first
-        self.sync_editor_to_cell();
+    /// Put IOPub messages from the console's execution into its scrollback.
+    fn handle_console_output(&mut self, msg: &JupyterMessage) {
+        match &msg.content {
+            JupyterMessageContent::Status(status) => {
+                self.kernel_status = format!("{:?}", status.execution_state).to_lowercase();
+                if status.execution_state == jupyter_protocol::ExecutionState::Idle {
+                    self.variables_stale = true;
+                    let msg_id = self.console.running.take();
+                    self.console.finished = msg_id.clone();
+                    if self
+                        .input_prompt
+                        .as_ref()
+                        .is_some_and(|p| p.execute_msg_id() == msg_id.as_deref())
+                    {
+                        self.dismiss_input_prompt();
+                    }
+                }
+            }
+            JupyterMessageContent::ExecuteInput(input) => {
+                self.console
+                    .set_execution_count(input.execution_count.value());
+            }
+            JupyterMessageContent::StreamContent(stream) => {
+                let stream_name = format!("{:?}", stream.name).to_lowercase();
+                self.console.append_stream(&stream_name, &stream.text);
+            }
+            JupyterMessageContent::ExecuteResult(result) => {
+                self.console.push_output(CellOutput::ExecuteResult {
+                    execution_count: result.execution_count.value(),
+                    data: MimeBundle::from_media(&result.data),
+                    metadata: result.metadata.clone(),
+                });
+            }
+            JupyterMessageContent::ErrorOutput(error) => {
+                self.console.push_output(CellOutput::Error {
+                    ename: error.ename.clone(),
+                    evalue: error.evalue.clone(),
+                    traceback: error.traceback.clone(),
+                });
+            }
+            JupyterMessageContent::DisplayData(display) => {
+                self.console.push_output(CellOutput::DisplayData {
+                    data: MimeBundle::from_media(&display.data),
+                    metadata: display.metadata.clone(),
+                });
+            }
+            JupyterMessageContent::ClearOutput(clear) => self.console.clear_output(clear.wait),
+            _ => {}
+        }
+    }
 
-        let cell = &mut self.notebook.cells[self.selected_cell];
+    /// Bell + desktop notification for a run that outlasted `notify_after`.
+    fn notify_if_long(&self, elapsed: Duration, outcome: &str) {
+        if self
+            .notify_after
+            .is_none_or(|threshold| elapsed < threshold)
+        {
+            return;
+        }
+        let notebook = self
+            .notebook
+            .file_path
+            .as_ref()
+            .and_then(|p| p.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "[No Name]".to_string());
+        notify::send(
+            &format!("jupyter_tui: {}", notebook),
+            &format!("{} after {}", outcome, ui::cell::format_elapsed(elapsed)),
+        );
+    }
 
-        // Markdown cells: "executing" just renders them
-        if cell.cell_type == CellType::Markdown {
-            cell.rendered = true;
-            return Ok(());
+    /// Keep a bounded record of finished executions for late execute_replies.
+    fn remember_finished_run(&mut self, msg_id: &str, cell_id: &str) {
+        const MAX_FINISHED_RUNS: usize = 32;
+        if self.finished_runs.len() == MAX_FINISHED_RUNS {
+            self.finished_runs.pop_front();
         }
+        self.finished_runs
+            .push_back((msg_id.to_string(), cell_id.to_string()));
+    }
 
-        if cell.cell_type != CellType::Code {
-            self.status_message = "Can only execute code cells".to_string();
-            return Ok(());
+    /// Apply an execute_reply to its cell: the final execution count, the
+    /// reply status, and (with `:set recordtiming`) the timestamps in
+    /// metadata.execution. A reply to the queue's running cell moves the
+    /// queue on.
+    fn handle_execute_reply(&mut self, reply: &JupyterMessage) {
+        let JupyterMessageContent::ExecuteReply(content) = &reply.content else {
+            return;
+        };
+        let Some(msg_id) = reply.parent_header.as_ref().map(|h| h.msg_id.as_str()) else {
+            return;
+        };
+        // `obj?` and `%load` typed at the console
+        if [&self.console.running, &self.console.finished]
+            .iter()
+            .any(|id| id.as_deref() == Some(msg_id))
+        {
+            for payload in &content.payload {
+                self.handle_payload(None, payload);
+            }
+            return;
         }
 
-        let code = cell.source.clone();
-        cell.clear_outputs();
-        cell.execution_state = ExecutionState::Running;
+        let cell_id = self.executing_cells.get(msg_id).cloned().or_else(|| {
+            self.finished_runs
+                .iter()
+                .find(|(id, _)| id == msg_id)
+                .map(|(_, cell_id)| cell_id.clone())
+        });
 
-        // Invalidate cached image states for this cell
-        self.image_states
-            .retain(|&(ci, _), _| ci != self.selected_cell);
+        // Shell and IOPub aren't ordered, so only the reply reliably says
+        // whether the run failed
+        if self.queue_running.as_deref() == Some(msg_id) {
+            let failed = matches!(content.status, ReplyStatus::Error | ReplyStatus::Aborted);
+            self.finish_queued_run(cell_id.as_deref(), failed);
+        }
 
-        let msg_id = self.kernel_client.execute(&code).await?;
-        self.executing_cells.insert(msg_id, self.selected_cell);
+        let Some(cell) = cell_id.and_then(|id| self.notebook.cell_mut(&id)) else {
+            return;
+        };
 
-        Ok(())
+        // Cells without an execute_result only learn their count here
+        let mut changed = false;
+        if content.execution_count.value() > 0
+            && cell.execution_count != Some(content.execution_count.value())
+        {
+            cell.execution_count = Some(content.execution_count.value());
+            changed = true;
+        }
+        match content.status {
+            // Errors usually also arrive as an IOPub error output; this
+            // catches kernels that only report them in the reply
+            ReplyStatus::Error if cell.execution_state != ExecutionState::Error => {
+                cell.execution_state = ExecutionState::Error;
+                if let Some(error) = &content.error
+                    && !cell
+                        .outputs
+                        .iter()
+                        .any(|o| matches!(o, CellOutput::Error { .. }))
+                {
+                    cell.outputs.push(CellOutput::Error {
+                        ename: error.ename.clone(),
+                        evalue: error.evalue.clone(),
+                        traceback: error.traceback.clone(),
+                    });
+                    changed = true;
+                }
+            }
+            // The kernel dropped the request (e.g. after an earlier error)
+            ReplyStatus::Aborted => cell.execution_state = ExecutionState::Skipped,
+            _ => {}
+        }
+
+        if self.record_timing {
+            let timing = cell.execution_timing();
+            timing.shell_execute_reply = Some(timing_timestamp(&reply.header.date));
+            // ipykernel reports when it actually began executing
+            if let Some(started) = reply.metadata.get("started").and_then(|v| v.as_str()) {
+                timing.shell_execute_reply_started = Some(started.to_string());
+            }
+            changed = true;
+        }
+        let cell_id = cell.id.clone();
+        if changed {
+            self.notebook.dirty = true;
+        }
+
+        for payload in &content.payload {
+            self.handle_payload(Some(&cell_id), payload);
+        }
     }
 
-    /// Execute all code cells in order.
-    pub async fn execute_all_cells(&mut self) -> Result<()> {
-        // If we're editing, sync first
-        self.sync_editor_to_cell();
+    /// The queue's running cell finished: stop the queue if it failed,
+    /// otherwise let `run_next_queued` send the next cell.
+    fn finish_queued_run(&mut self, cell_id: Option<&str>, failed: bool) {
+        self.queue_running = None;
+        let cell_idx = cell_id.and_then(|id| self.notebook.cell_index(id));
+        let cell_name = match cell_idx {
+            Some(idx) => format!("cell {}", idx + 1),
+            None => "deleted cell".to_string(),
+        };
 
-        for idx in 0..self.notebook.cells.len() {
-            let cell = &mut self.notebook.cells[idx];
+        if failed && !self.execution_queue.is_empty() {
+            let skipped = self.skip_queued_cells();
+            self.status_message = format!(
+                "Error in {}; skipped {} queued cell{}",
+                cell_name,
+                skipped,
+                if skipped == 1 { "" } else { "s" }
+            );
+        } else if self.execution_queue.is_empty() && self.status_message.starts_with("Running ") {
+            self.status_message = if failed {
+                format!("Error in {}", cell_name)
+            } else {
+                "Done".to_string()
+            };
+        }
 
-            // Markdown cells: "executing" just renders them
-            if cell.cell_type == CellType::Markdown {
-                cell.rendered = true;
-                continue;
-            }
+        // The whole run is over: tell the user if it took a while
+        if self.execution_queue.is_empty()
+            && let Some(started) = self.queue_started.take()
+        {
+            let outcome = if failed {
+                format!("Error in {}", cell_name)
+            } else {
+                match cell_idx {
+                    Some(idx) => format!("Cell {} finished", idx + 1),
+                    None => "Deleted cell finished".to_string(),
+                }
+            };
+            self.notify_if_long(started.elapsed(), &outcome);
+        }
+    }
 
-            if cell.cell_type != CellType::Code {
-                continue;
+    /// Act on an execute_reply payload from the cell `cell_id` ran
+    /// (None for the console).
+    fn handle_payload(&mut self, cell_id: Option<&str>, payload: &Payload) {
+        match payload {
+            // The console has no next cell: put the text at its prompt
+            Payload::SetNextInput { text, .. } if cell_id.is_none() => {
+                self.console.set_input(text);
             }
+            // `%load`, `%recall`, `get_ipython().set_next_input(...)`
+            Payload::SetNextInput { text, replace } => {
+                let Some(cell_idx) = cell_id.and_then(|id| self.notebook.cell_index(id)) else {
+                    return;
+                };
+                if *replace {
+                    self.notebook.cells[cell_idx].source = text.clone();
+                    if self.mode.is_in_cell() && self.selected_cell == cell_idx {
+                        self.enter_cell(); // Reload the editor
+                    }
+                    self.status_message = format!("Replaced cell {}", cell_idx + 1);
+                } else {
+                    self.notebook
+                        .insert_cell_after(cell_idx, Cell::new_code(text));
+                    if self.selected_cell > cell_idx {
+                        self.selected_cell += 1;
+                    }
+                    self.status_message = format!("Inserted cell {}", cell_idx + 2);
+                }
+                self.notebook.dirty = true;
+            }
+            // `obj?`, `%magic`, `help()`
+            Payload::Page { data, start } => {
+                let Some(text) = MimeBundle::from_media(data)
+                    .text("text/plain")
+                    .map(str::to_string)
+                else {
+                    return;
+                };
+                self.last_page = Some(text.clone());
+                if matches!(self.mode, Mode::Normal | Mode::Pager | Mode::Console)
+                    || self.mode.is_in_cell()
+                {
+                    self.open_pager(text, *start as u16, None);
+                } else {
+                    self.status_message = "Kernel sent pager output; :pager to view".to_string();
+                }
+            }
+            Payload::EditMagic { .. } | Payload::AskExit { .. } => {}
+        }
+    }
 
-            let code = cell.source.clone();
-            cell.clear_outputs();
-            cell.execution_state = ExecutionState::Running;
-
-            let msg_id = self.kernel_client.execute(&code).await?;
-            self.executing_cells.insert(msg_id, idx);
+    /// Resolve the cell an IOPub output belongs to, first applying any
+    /// deferred clear_output(wait=True) for that cell.
+    fn output_cell(&mut self, parent_msg_id: Option<&str>) -> Option<usize> {
+        let cell_idx = self.executing_cell(parent_msg_id)?;
+        if self.pending_clear.remove(&self.notebook.cells[cell_idx].id) {
+            self.clear_cell_outputs(cell_idx);
         }
+        Some(cell_idx)
+    }
 
-        // Clear all cached image states when running all cells
-        self.image_states.clear();
+    /// Current index of the cell running the execute_request `parent_msg_id`
+    /// (None if it isn't ours or the cell has been deleted).
+    fn executing_cell(&self, parent_msg_id: Option<&str>) -> Option<usize> {
+        let cell_id = self.executing_cells.get(parent_msg_id?)?;
+        self.notebook.cell_index(cell_id)
+    }
 
-        self.status_message = "Running all cells...".to_string();
-        Ok(())
+    /// Remove a cell's outputs (keeping its execution state), along with
+    /// display_id registrations and cached images that point into it.
+    fn clear_cell_outputs(&mut self, cell_idx: usize) {
+        self.notebook.cells[cell_idx].outputs.clear();
+        self.forget_cell_outputs(cell_idx);
     }
 
-    /// Restart the kernel and reconnect.
-    pub async fn restart_kernel(&mut self) -> Result<()> {
-        self.status_message = "Restarting kernel...".to_string();
-        self.kernel_status = "restarting".to_string();
-        self.executing_cells.clear();
-        self.image_states.clear();
+    /// Drop per-output bookkeeping for a cell whose outputs were cleared.
+    fn forget_cell_outputs(&mut self, cell_idx: usize) {
+        let cell_id = self.notebook.cells[cell_idx].id.clone();
+        self.forget_outputs_of(&cell_id);
+    }
 
-        // Restart the kernel process
-        self.kernel_manager.restart().await?;
+    /// Drop per-output bookkeeping for the cell with id `cell_id`.
+    fn forget_outputs_of(&mut self, cell_id: &str) {
+        for targets in self.display_outputs.values_mut() {
+            targets.retain(|(id, _)| id != cell_id);
+        }
+        self.display_outputs
+            .retain(|_, targets| !targets.is_empty());
+        self.render_cache.forget_cell(cell_id);
+        self.table_scroll.remove(cell_id);
+        self.json_views.retain(|(id, _), _| id != cell_id);
+        if self
+            .json_focus
+            .as_ref()
+            .is_some_and(|(id, _)| id == cell_id)
+        {
+            self.leave_json_tree();
+        }
+        self.pending_clear.remove(cell_id);
+    }
 
-        // Reconnect to the kernel
-        let (kernel_client, mut kernel_rx) =
-            KernelClient::connect(self.kernel_manager.connection_info())
-                .await
-                .context("Failed to reconnect to kernel")?;
+    /// Scroll the HTML tables in the selected cell's outputs sideways by
+    /// `delta` columns, stopping at the last column of the widest.
+    pub fn scroll_tables(&mut self, delta: isize) {
+        let Some(cell) = self.notebook.cells.get(self.selected_cell) else {
+            return;
+        };
+        // Tables are parsed when the cell is drawn; reuse that
+        let Some(columns) = self.render_cache.table_columns(&cell.id) else {
+            self.status_message = "No table in this cell".to_string();
+            return;
+        };
+        let scroll = self.table_scroll.entry(cell.id.clone()).or_default();
+        *scroll = scroll
+            .saturating_add_signed(delta)
+            .min(columns.saturating_sub(1));
+    }
 
-        self.kernel_client = kernel_client;
+    /// Remember which output shows a display_id, if the message carried one.
+    fn register_display(
+        &mut self,
+        transient: Option<&Transient>,
+        cell_idx: usize,
+        output_idx: usize,
+    ) {
+        if let Some(display_id) = transient.and_then(|t| t.display_id.as_ref()) {
+            let cell_id = self.notebook.cells[cell_idx].id.clone();
+            self.display_outputs
+                .entry(display_id.clone())
+                .or_default()
+                .push((cell_id, output_idx));
+        }
+    }
 
-        // Re-spawn IOPub forwarding to the event channel
-        let tx = self.event_tx.clone();
-        tokio::spawn(async move {
-            while let Some(msg) = kernel_rx.recv().await {
-                if tx.send(crate::event::AppEvent::Kernel(msg)).is_err() {
-                    break;
-                }
+    /// Replace the data of every output showing `display_id` (update_display_data).
+    fn update_display(
+        &mut self,
+        display_id: &str,
+        media: &jupyter_protocol::Media,
+        new_metadata: &serde_json::Map<String, serde_json::Value>,
+    ) {
+        let Some(targets) = self.display_outputs.get(display_id) else {
+            return;
+        };
+        for (cell_id, output_idx) in targets {
+            let output = self
+                .notebook
+                .cell_mut(cell_id)
+                .and_then(|cell| cell.outputs.get_mut(*output_idx));
+            if let Some(
+                CellOutput::DisplayData { data, metadata }
+                | CellOutput::ExecuteResult { data, metadata, .. },
+            ) = output
+            {
+                *data = MimeBundle::from_media(media);
+                *metadata = new_metadata.clone();
+                self.render_cache
+                    .forget_output(&(cell_id.clone(), *output_idx));
+                self.json_views.remove(&(cell_id.clone(), *output_idx));
             }
-        });
+        }
+    }
 
-        // Request kernel info to trigger status update
-        let _ = self.kernel_client.request_kernel_info().await;
+    /// `t`: browse the selected cell's first JSON output as a tree, or its
+    /// next one when already browsing.
+    pub fn focus_json_tree(&mut self) {
+        let Some(cell) = self.notebook.cells.get(self.selected_cell) else {
+            return;
+        };
+        let outputs: Vec<usize> = cell
+            .outputs
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| json_tree::output_json(output).is_some())
+            .map(|(i, _)| i)
+            .collect();
+        let current = self
+            .json_focus
+            .as_ref()
+            .filter(|(id, _)| *id == cell.id)
+            .map(|(_, i)| *i);
+        let next = outputs
+            .iter()
+            .copied()
+            .find(|&i| current.is_none_or(|current| i > current))
+            .or(outputs.first().copied());
+        let Some(next) = next else {
+            self.status_message = "No JSON output in this cell".to_string();
+            return;
+        };
+        self.json_focus = Some((cell.id.clone(), next));
+        self.json_search = None;
+        self.mode = Mode::JsonTree;
+    }
+
+    pub fn leave_json_tree(&mut self) {
+        self.json_focus = None;
+        self.json_search = None;
+        self.mode = Mode::Normal;
+    }
+
+    /// The browsed JSON and its tree state, if the output still exists.
+    pub fn focused_json(&mut self) -> Option<(&serde_json::Value, &mut JsonView)> {
+        let (cell_id, output_idx) = self.json_focus.as_ref()?;
+        let output = self
+            .notebook
+            .cells
+            .iter()
+            .find(|cell| cell.id == *cell_id)?
+            .outputs
+            .get(*output_idx)?;
+        let root = json_tree::output_json(output)?;
+        let view = self
+            .json_views
+            .entry((cell_id.clone(), *output_idx))
+            .or_default();
+        Some((root, view))
+    }
+
+    /// Run the search typed after `/` in the JSON tree, or repeat the last one.
+    pub fn search_json_tree(&mut self, backwards: bool) {
+        let typed = self.json_search.take().filter(|query| !query.is_empty());
+        let Some((root, view)) = self.focused_json() else {
+            return;
+        };
+        if typed.is_some() {
+            view.query = typed;
+        }
+        let message = match view.query.clone() {
+            Some(query) if !view.find(root, &query, backwards) => {
+                format!("Pattern not found: {}", query)
+            }
+            Some(_) => String::new(),
+            None => "No previous search".to_string(),
+        };
+        self.status_message = message;
+    }
+
+    /// `y` / `Y`: copy the selected row's path, or its value as JSON, to the
+    /// system clipboard.
+    pub fn copy_json_selection(&mut self, value: bool) {
+        let Some((root, view)) = self.focused_json() else {
+            return;
+        };
+        let path = view.cursor_path(root);
+        let shown = match json_tree::format_path(&path) {
+            formatted if formatted.is_empty() => "(root)".to_string(),
+            formatted => formatted,
+        };
+        let (text, message) = if value {
+            let json = json_tree::lookup(root, &path)
+                .and_then(|v| serde_json::to_string_pretty(v).ok())
+                .unwrap_or_default();
+            (json, format!("Copied value at {}", shown))
+        } else {
+            (json_tree::format_path(&path), format!("Copied {}", shown))
+        };
+        ui::clipboard::copy(&text);
+        self.status_message = message;
+    }
+
+    /// Delete the selected cell into the yank buffer. If it is running or
+    /// queued, it is dropped from the queue and its remaining output is
+    /// discarded.
+    pub fn delete_selected_cell(&mut self) {
+        let Some(removed) = self.notebook.delete_cell(self.selected_cell) else {
+            return;
+        };
+        let was_running = self.executing_cells.values().any(|id| *id == removed.id);
+        self.execution_queue.retain(|id| *id != removed.id);
+        self.forget_outputs_of(&removed.id);
+        self.yanked_cell = Some(removed);
+
+        if self.selected_cell >= self.notebook.cells.len() {
+            self.selected_cell = self.notebook.cells.len() - 1;
+        }
+        self.status_message = if was_running {
+            "Cell deleted (yanked); its running output will be discarded".to_string()
+        } else {
+            "Cell deleted (yanked)".to_string()
+        };
+    }
+
+    /// Send the typed answer for the pending input_request and echo the
+    /// prompt and answer into the cell's stdout, as JupyterLab does.
+    pub fn submit_input(&mut self) {
+        let Some(prompt) = self.input_prompt.take() else {
+            return;
+        };
+        self.mode = prompt.return_mode.clone();
+
+        if let Err(e) = self
+            .kernel_client
+            .send_input_reply(&prompt.request, prompt.buffer.clone())
+        {
+            self.status_message = format!("Error: {}", e);
+            return;
+        }
+
+        let echoed = if prompt.password {
+            "········"
+        } else {
+            prompt.buffer.as_str()
+        };
+        let echo = format!("{}{}\n", prompt.prompt, echoed);
+        if let Some(cell_idx) = self.executing_cell(prompt.execute_msg_id()) {
+            self.notebook.cells[cell_idx].append_stream("stdout", &echo);
+        } else if prompt.execute_msg_id() == self.console.running.as_deref() {
+            self.console.append_stream("stdout", &echo);
+        }
+    }
+
+    /// Drop the pending input prompt without answering it.
+    fn dismiss_input_prompt(&mut self) {
+        if let Some(prompt) = self.input_prompt.take() {
+            self.mode = prompt.return_mode;
+        }
+    }
+
+    /// Execute the currently selected cell.
+    pub async fn execute_selected_cell(&mut self) -> Result<()> {
+        // If we're in insert mode, sync the editor content first
+        self.sync_editor_to_cell();
+
+        let cell = &mut self.notebook.cells[self.selected_cell];
+
+        // Markdown cells: "executing" just renders them
+        if cell.cell_type == CellType::Markdown {
+            cell.rendered = true;
+            return Ok(());
+        }
+
+        if cell.cell_type != CellType::Code {
+            self.status_message = "Can only execute code cells".to_string();
+            return Ok(());
+        }
+
+        if self.kernel_status == "dead" {
+            self.status_message = "Kernel is dead (use :restart)".to_string();
+            return Ok(());
+        }
+
+        self.queue_cells(std::iter::once(self.selected_cell)).await
+    }
+
+    /// Execute all cells in order (markdown cells are rendered).
+    pub async fn execute_all_cells(&mut self) -> Result<()> {
+        self.status_message = "Running all cells...".to_string();
+        self.queue_cells(0..self.notebook.cells.len()).await
+    }
+
+    /// Execute every cell above the selected one.
+    pub async fn execute_cells_above(&mut self) -> Result<()> {
+        self.status_message = "Running cells above...".to_string();
+        self.queue_cells(0..self.selected_cell).await
+    }
+
+    /// Execute the selected cell and every cell below it.
+    pub async fn execute_cells_below(&mut self) -> Result<()> {
+        self.status_message = "Running cells below...".to_string();
+        self.queue_cells(self.selected_cell..self.notebook.cells.len())
+            .await
+    }
+
+    /// Execute cells `first..=last` (0-indexed, clamped to the notebook).
+    pub async fn execute_cell_range(&mut self, first: usize, last: usize) -> Result<()> {
+        let end = (last + 1).min(self.notebook.cells.len());
+        self.status_message = format!("Running cells {}-{}...", first + 1, end);
+        self.queue_cells(first..end).await
+    }
+
+    /// Add cells to the execution queue and start it if idle. Markdown
+    /// cells are rendered right away; raw cells are ignored.
+    async fn queue_cells(&mut self, cells: impl IntoIterator<Item = usize>) -> Result<()> {
+        // If we're editing, sync first
+        self.sync_editor_to_cell();
+
+        if self.kernel_status == "dead" {
+            self.status_message = "Kernel is dead (use :restart)".to_string();
+            return Ok(());
+        }
+
+        for idx in cells {
+            let Some(cell) = self.notebook.cells.get_mut(idx) else {
+                continue;
+            };
+            match cell.cell_type {
+                CellType::Markdown => cell.rendered = true,
+                CellType::Code if !self.execution_queue.contains(&cell.id) => {
+                    cell.execution_state = ExecutionState::Queued;
+                    self.execution_queue.push_back(cell.id.clone());
+                }
+                _ => {}
+            }
+        }
+
+        self.run_next_queued().await;
+        Ok(())
+    }
+
+    /// Send the next queued cell unless one is already running.
+    /// If the request can't be sent, the cell fails and the rest of the
+    /// queue is skipped.
+    async fn run_next_queued(&mut self) {
+        if self.queue_running.is_some() || self.kernel_status == "dead" {
+            return;
+        }
+        while let Some(cell_id) = self.execution_queue.pop_front() {
+            // Cells deleted while queued are dropped
+            let Some(idx) = self.notebook.cell_index(&cell_id) else {
+                continue;
+            };
+            let cell = &mut self.notebook.cells[idx];
+            if cell.cell_type != CellType::Code {
+                continue;
+            }
+
+            let code = cell.source.clone();
+            cell.clear_outputs();
+            cell.metadata.execution = None;
+            cell.execution_state = ExecutionState::Running;
+
+            // Invalidate cached image states and display_ids for this cell
+            self.forget_cell_outputs(idx);
+
+            let msg_id = match self.kernel_client.execute(&code).await {
+                Ok(msg_id) => msg_id,
+                Err(e) => {
+                    self.notebook.cells[idx].execution_state = ExecutionState::Error;
+                    let skipped = self.skip_queued_cells();
+                    self.queue_started = None;
+                    self.status_message = format!(
+                        "Failed to run cell {}: {:#}; skipped {} queued cell{}",
+                        idx + 1,
+                        e,
+                        skipped,
+                        if skipped == 1 { "" } else { "s" }
+                    );
+                    break;
+                }
+            };
+            self.executing_cells.insert(msg_id.clone(), cell_id);
+            self.queue_running = Some(msg_id);
+            self.queue_started.get_or_insert_with(Instant::now);
+            break;
+        }
+    }
+
+    /// Drop the rest of the queue, marking those cells as skipped.
+    /// Returns how many were skipped.
+    fn skip_queued_cells(&mut self) -> usize {
+        self.queue_running = None;
+        let skipped = self.execution_queue.len();
+        for cell_id in self.execution_queue.drain(..) {
+            if let Some(cell) = self.notebook.cell_mut(&cell_id) {
+                cell.execution_state = ExecutionState::Skipped;
+            }
+        }
+        skipped
+    }
+
+    /// Restart the kernel and reconnect.
+    pub async fn restart_kernel(&mut self) -> Result<()> {
+        if self.kernel_manager.is_attached() {
+            self.status_message =
+                "Cannot restart a kernel started outside jupyter_tui (attached)".to_string();
+            return Ok(());
+        }
+
+        self.status_message = "Restarting kernel...".to_string();
+        self.kernel_status = "restarting".to_string();
+        self.reset_kernel_state("Kernel restarted");
+
+        // Restart the kernel process
+        self.kernel_manager.restart().await?;
+
+        self.connect_kernel_client().await?;
 
         self.status_message = "Kernel restarted".to_string();
 
         Ok(())
     }
 
+    /// Open the kernelspec picker popup, preselecting the current kernel.
+    pub async fn open_kernel_picker(&mut self) {
+        let kernels = match self.kernel_manager.kernelspecs().await {
+            Ok(kernels) if !kernels.is_empty() => kernels,
+            Ok(_) => {
+                self.status_message = "No kernelspecs found".to_string();
+                return;
+            }
+            Err(e) => {
+                self.status_message = format!("Error: {:#}", e);
+                return;
+            }
+        };
+        let current = self.notebook.metadata.kernel_name();
+        let selected = kernels
+            .iter()
+            .position(|k| Some(k.name.as_str()) == current)
+            .unwrap_or(0);
+
+        self.kernel_picker = Some(KernelPicker {
+            kernels,
+            selected,
+            return_mode: self.mode.clone(),
+        });
+        self.mode = Mode::KernelPicker;
+    }
+
+    /// Close the kernelspec picker without switching.
+    pub fn close_kernel_picker(&mut self) {
+        if let Some(picker) = self.kernel_picker.take() {
+            self.mode = picker.return_mode;
+        }
+    }
+
+    /// Replace the kernel with kernelspec `name` and record it in the
+    /// notebook metadata. Outputs stay; the new kernel starts with empty state.
+    pub async fn switch_kernel(&mut self, name: &str) -> Result<()> {
+        if self.kernel_manager.is_attached() {
+            self.status_message =
+                "Cannot switch a kernel started outside jupyter_tui (attached)".to_string();
+            return Ok(());
+        }
+
+        let specs = self.kernel_manager.kernelspecs().await?;
+        let spec = specs.iter().find(|s| s.name == name).with_context(|| {
+            let available: Vec<&str> = specs.iter().map(|s| s.name.as_str()).collect();
+            format!(
+                "Kernelspec '{}' not found. Available: {:?}",
+                name, available
+            )
+        })?;
+
+        self.status_message = format!("Starting {}...", spec.display_name);
+        self.kernel_status = "starting".to_string();
+
+        // Keep the current session's state until the new kernel is reachable
+        self.kernel_manager.change_kernel(name).await?;
+        let (kernel_client, kernel_rx) = self
+            .kernel_manager
+            .connect()
+            .await
+            .context("Failed to connect to kernel")?;
+        self.reset_kernel_state(&format!("Switched to {}", spec.display_name));
+        self.install_kernel_client(kernel_client, kernel_rx).await;
+
+        self.record_kernelspec(spec);
+        self.status_message = format!("Switched to {}", spec.display_name);
+        Ok(())
+    }
+
+    /// Write a kernelspec into the notebook metadata.
+    fn record_kernelspec(&mut self, spec: &KernelspecInfo) {
+        self.notebook.metadata.set_kernelspec(
+            &spec.name,
+            &spec.display_name,
+            spec.language.as_deref(),
+        );
+        self.notebook.dirty = true;
+    }
+
+    /// Leave the current kernel (shutting it down if we own it, detaching
+    /// otherwise) and attach to an existing kernel via its connection file.
+    pub async fn connect_existing(&mut self, connection_file: &std::path::Path) -> Result<()> {
+        let manager = KernelManager::attach(connection_file).await?;
+        // Connect before letting go of the current kernel, so a bad
+        // connection file leaves it running
+        let (kernel_client, kernel_rx) = manager
+            .connect()
+            .await
+            .context("Failed to connect to kernel")?;
+
+        self.shutdown().await?;
+        self.kernel_status = "starting".to_string();
+        self.reset_kernel_state("Attached to another kernel");
+        self.kernel_manager = Box::new(manager);
+        self.install_kernel_client(kernel_client, kernel_rx).await;
+
+        self.status_message = format!("Attached to {}", self.kernel_manager.describe());
+        Ok(())
+    }
+
+    /// Forget everything tied to the current kernel session. Cells still
+    /// running end in an error saying why (`reason`, e.g. "Kernel
+    /// restarted"): the new kernel never reports them idle.
+    fn reset_kernel_state(&mut self, reason: &str) {
+        self.dismiss_input_prompt();
+        self.skip_queued_cells();
+        self.queue_started = None;
+        for (_, cell_id) in self.executing_cells.drain() {
+            if let Some(cell) = self.notebook.cell_mut(&cell_id) {
+                cell.outputs.push(CellOutput::Error {
+                    ename: "Interrupted".to_string(),
+                    evalue: reason.to_string(),
+                    traceback: vec![format!("\x1b[31mInterrupted\x1b[0m: {}", reason)],
+                });
+                cell.execution_state = ExecutionState::Error;
+                cell.run_started = None;
+            }
+        }
+        self.finished_runs.clear();
+        self.console.running = None;
+        self.console.finished = None;
+        self.console_check = None;
+        self.variables_stale = true;
+        self.display_outputs.clear();
+        self.pending_clear.clear();
+        self.render_cache.clear();
+        if let Some(monitor) = self.heartbeat_monitor.take() {
+            monitor.abort();
+        }
+        self.kernel_session += 1;
+    }
+
+    /// (Re)connect the client to `kernel_manager`'s kernel.
+    async fn connect_kernel_client(&mut self) -> Result<()> {
+        let (kernel_client, kernel_rx) = self
+            .kernel_manager
+            .connect()
+            .await
+            .context("Failed to connect to kernel")?;
+        self.install_kernel_client(kernel_client, kernel_rx).await;
+        Ok(())
+    }
+
+    /// Make `kernel_client` the current client, forward its messages into
+    /// the event loop and start a heartbeat monitor.
+    async fn install_kernel_client(
+        &mut self,
+        kernel_client: Box<dyn KernelConnection>,
+        mut kernel_rx: mpsc::UnboundedReceiver<KernelMessage>,
+    ) {
+        self.kernel_client = kernel_client;
+
+        // Re-spawn IOPub forwarding to the event channel
+        let tx = self.event_tx.clone();
+        tokio::spawn(async move {
+            while let Some(msg) = kernel_rx.recv().await {
+                if tx.send(crate::event::AppEvent::Kernel(msg)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        self.heartbeat_monitor = self.kernel_manager.connection_info().map(|info| {
+            monitor::spawn_heartbeat_monitor(
+                info.clone(),
+                self.kernel_session,
+                self.event_tx.clone(),
+            )
+        });
+
+        // Request kernel info to trigger status update
+        let _ = self.kernel_client.request_kernel_info().await;
+    }
+
     /// Enter the cell in CellNormal mode: create a TextArea from the current cell's source.
     pub fn enter_cell(&mut self) {
         let cell = &mut self.notebook.cells[self.selected_cell];
@@ -570,59 +1843,993 @@ impl App {
         }
     }
 
-    /// Clear the completion panel state.
+    /// Close the completion panel and forget pending or scheduled requests.
     pub fn clear_completions(&mut self) {
-        self.completions.clear();
-        self.completion_selected = 0;
+        self.completion = None;
+        self.completion_request = None;
+        self.completion_due = None;
+        self.completion_generation += 1;
     }
 
-    /// Request tab completion from the kernel for the current cursor position.
-    async fn request_completion(&mut self) {
-        if let Some(editor) = &self.editor {
-            let lines = editor.lines();
-            let source = lines.join("\n");
-            let (cursor_row, cursor_col) = editor.cursor();
+    /// Request completions automatically once typing pauses.
+    pub fn schedule_completion(&mut self) {
+        if self.auto_complete {
+            self.completion_due = Some(Instant::now() + AUTO_COMPLETE_DELAY);
+        }
+    }
 
-            // Convert (row, col) to byte offset in the full source
-            let mut offset = 0;
-            for (i, line) in lines.iter().enumerate() {
-                if i == cursor_row {
-                    offset += cursor_col;
-                    break;
+    /// Push back a scheduled automatic request while an identifier is typed.
+    pub fn defer_completion(&mut self) {
+        if self.completion_due.is_some() {
+            self.completion_due = Some(Instant::now() + AUTO_COMPLETE_DELAY);
+        }
+    }
+
+    /// The editor's source and the cursor's offset into it, in code points
+    /// (the unit of Jupyter's cursor_pos).
+    fn editor_cursor_offset(&self) -> Option<(String, usize)> {
+        let editor = self.editor.as_ref()?;
+        let lines = editor.lines();
+        let (cursor_row, cursor_col) = editor.cursor();
+
+        // The editor's column already counts chars
+        let mut offset = 0;
+        for (i, line) in lines.iter().enumerate() {
+            if i == cursor_row {
+                offset += cursor_col;
+                break;
+            }
+            offset += line.chars().count() + 1; // +1 for newline
+        }
+        Some((lines.join("\n"), offset))
+    }
+
+    /// Request completions from the kernel for the current cursor position.
+    /// The reply arrives later as `AppEvent::CompletionReply`, so typing
+    /// continues while the kernel answers.
+    async fn request_completion(&mut self, manual: bool) {
+        let Some((source, offset)) = self.editor_cursor_offset() else {
+            return;
+        };
+        self.clear_completions();
+        match self.kernel_client.complete(&source, offset).await {
+            Ok(reply) => {
+                let generation = self.completion_generation;
+                self.completion_request = Some(CompletionRequest {
+                    generation,
+                    offset,
+                    manual,
+                });
+                let tx = self.event_tx.clone();
+                tokio::spawn(async move {
+                    let reply = reply.await.map_err(|e| e.to_string());
+                    let _ = tx.send(AppEvent::CompletionReply { generation, reply });
+                });
+            }
+            Err(e) => {
+                self.status_message = format!("Completion error: {}", e);
+            }
+        }
+    }
+
+    /// Open the completion panel for a reply, narrowed to whatever was typed
+    /// since the request. Replies to superseded requests are ignored.
+    fn show_completions(
+        &mut self,
+        generation: u64,
+        reply: std::result::Result<jupyter_protocol::CompleteReply, String>,
+    ) {
+        let Some(request) = self
+            .completion_request
+            .take_if(|r| r.generation == generation)
+        else {
+            return;
+        };
+        if self.mode != Mode::CellInsert {
+            return;
+        }
+        let reply = match reply {
+            Ok(reply) => reply,
+            Err(e) => {
+                if request.manual {
+                    self.status_message = format!("Completion error: {}", e);
                 }
-                offset += line.len() + 1; // +1 for newline
+                return;
             }
+        };
 
-            match self.kernel_client.complete(&source, offset).await {
-                Ok(reply) => {
-                    if reply.matches.is_empty() {
-                        self.status_message = "No completions".to_string();
-                    } else {
-                        self.completions = reply.matches;
-                        self.completion_selected = 0;
-                        self.completion_cursor_start = reply.cursor_start;
-                        self.completion_cursor_end = reply.cursor_end;
+        let completion = self.editor_cursor_offset().and_then(|(source, offset)| {
+            let start = char_to_byte(&source, reply.cursor_start)?;
+            let query = source.get(start..char_to_byte(&source, offset)?)?;
+            Completion::from_reply(reply, request.offset, query)
+        });
+        if completion.is_none() && request.manual {
+            self.status_message = "No completions".to_string();
+        }
+        self.completion = completion;
+    }
+
+    /// Re-filter the open completion panel after the text before the cursor
+    /// changed. Closes it when nothing matches or the cursor left the word.
+    pub fn refilter_completions(&mut self) {
+        let Some(completion) = &self.completion else {
+            return;
+        };
+        let start = completion.cursor_start;
+        let query = self.editor_cursor_offset().and_then(|(source, offset)| {
+            let range = char_to_byte(&source, start)?..char_to_byte(&source, offset)?;
+            source.get(range).map(str::to_string)
+        });
+        let keep = match (query, &mut self.completion) {
+            (Some(query), Some(completion)) => completion.filter(&query),
+            _ => false,
+        };
+        if !keep {
+            self.clear_completions();
+        }
+    }
+
+    /// Ask the kernel about the name under the cursor; the reply opens the
+    /// documentation popup.
+    pub async fn inspect_at_cursor(&mut self) {
+        if let Some((code, cursor_pos)) = self.editor_cursor_offset() {
+            self.request_inspect(code, cursor_pos, 0).await;
+        }
+    }
+
+    /// Re-ask for the documentation in the pager at the other detail level
+    /// (`obj?` <-> `obj??`).
+    pub async fn toggle_inspect_detail(&mut self) {
+        if let Some(target) = self.pager.as_ref().and_then(|p| p.inspect.as_ref()) {
+            let (code, cursor_pos) = (target.code.clone(), target.cursor_pos);
+            let detail_level = 1 - target.detail_level;
+            self.request_inspect(code, cursor_pos, detail_level).await;
+        }
+    }
+
+    /// Show `text` in the pager popup, replacing what it showed before.
+    fn open_pager(&mut self, text: String, scroll: u16, inspect: Option<InspectTarget>) {
+        let return_mode = match self.pager.take() {
+            Some(previous) => previous.return_mode,
+            None => self.mode.clone(),
+        };
+        self.pager = Some(Pager {
+            text,
+            scroll,
+            inspect,
+            return_mode,
+        });
+        self.mode = Mode::Pager;
+    }
+
+    /// Reopen the last `page` output from the kernel (`:pager`).
+    pub fn reopen_page(&mut self) {
+        match self.last_page.clone() {
+            Some(text) => self.open_pager(text, 0, None),
+            None => self.status_message = "No pager output".to_string(),
+        }
+    }
+
+    /// Close the pager popup.
+    pub fn close_pager(&mut self) {
+        self.inspect_request = None;
+        if let Some(pager) = self.pager.take() {
+            self.mode = pager.return_mode;
+        }
+    }
+
+    /// Send an inspect_request. The reply arrives later as
+    /// `AppEvent::InspectReply`, so keys keep working while the kernel answers.
+    async fn request_inspect(&mut self, code: String, cursor_pos: usize, detail_level: usize) {
+        match self
+            .kernel_client
+            .inspect(&code, cursor_pos, detail_level)
+            .await
+        {
+            Ok(reply) => {
+                self.inspect_generation += 1;
+                let generation = self.inspect_generation;
+                self.inspect_request = Some(PendingInspect {
+                    generation,
+                    code,
+                    cursor_pos,
+                    detail_level,
+                    mode: self.mode.clone(),
+                });
+                let tx = self.event_tx.clone();
+                tokio::spawn(async move {
+                    let reply = reply.await.map_err(|e| e.to_string());
+                    let _ = tx.send(AppEvent::InspectReply { generation, reply });
+                });
+            }
+            Err(e) => {
+                self.status_message = format!("Inspect error: {}", e);
+            }
+        }
+    }
+
+    /// Show an inspect_reply's text/plain data: open the documentation popup,
+    /// or update it when toggling the detail level. Replies to superseded
+    /// requests, or arriving after the user moved on, are ignored; failures
+    /// and empty replies are reported on the status line.
+    fn show_inspect(
+        &mut self,
+        generation: u64,
+        reply: std::result::Result<jupyter_protocol::InspectReply, String>,
+    ) {
+        let Some(request) = self.inspect_request.take_if(|r| r.generation == generation) else {
+            return;
+        };
+        if self.mode != request.mode {
+            return;
+        }
+        let text = match reply {
+            Ok(reply) if reply.found => {
+                match MimeBundle::from_media(&reply.data).text("text/plain") {
+                    Some(text) => text.to_string(),
+                    None => {
+                        self.status_message = "No plain-text documentation".to_string();
+                        return;
                     }
                 }
-                Err(e) => {
-                    self.status_message = format!("Completion error: {}", e);
+            }
+            Ok(_) => {
+                self.status_message = "No documentation found".to_string();
+                return;
+            }
+            Err(e) => {
+                self.status_message = format!("Inspect error: {}", e);
+                return;
+            }
+        };
+
+        let target = InspectTarget {
+            code: request.code,
+            cursor_pos: request.cursor_pos,
+            detail_level: request.detail_level,
+        };
+        self.open_pager(text, 0, Some(target));
+    }
+
+    /// Show the console pane and move focus to its prompt. The kernel's
+    /// input history is fetched the first time, for Up/Down recall.
+    pub async fn focus_console(&mut self) {
+        use ratatui::style::{Color, Style};
+        if self.mode != Mode::Console {
+            self.console.return_mode = self.mode.clone();
+        }
+        self.console.visible = true;
+        self.console
+            .input
+            .set_cursor_style(Style::default().fg(Color::Reset).bg(Color::White));
+        self.mode = Mode::Console;
+        self.status_message = String::new();
+
+        if !self.console.history_requested {
+            self.console.history_requested = true;
+            match self.kernel_client.history(HISTORY_LENGTH).await {
+                Ok(reply) => {
+                    let tx = self.event_tx.clone();
+                    tokio::spawn(async move {
+                        let reply = reply.await.map_err(|e| e.to_string());
+                        let _ = tx.send(AppEvent::HistoryReply(reply));
+                    });
                 }
+                Err(e) => self.status_message = format!("History error: {}", e),
+            }
+        }
+    }
+
+    /// Move focus back to where it was before the console; the pane stays open.
+    pub fn leave_console(&mut self) {
+        use ratatui::style::Style;
+        self.console.input.set_cursor_style(Style::default());
+        self.console_check = None;
+        self.mode = self.console.return_mode.clone();
+    }
+
+    /// `:console`: hide the pane if it is shown, otherwise open and focus it.
+    pub async fn toggle_console(&mut self) {
+        if !self.console.visible {
+            self.focus_console().await;
+            return;
+        }
+        if self.mode == Mode::Console {
+            self.leave_console();
+        }
+        self.console.visible = false;
+    }
+
+    /// Enter at the console prompt: ask the kernel whether the input is a
+    /// complete statement. The reply (`AppEvent::IsCompleteReply`) either
+    /// runs it or continues it on a new line.
+    pub async fn console_enter(&mut self) {
+        let code = self.console.input_text();
+        if code.trim().is_empty() {
+            return;
+        }
+        match self.kernel_client.is_complete(&code).await {
+            Ok(reply) => {
+                self.console_generation += 1;
+                let generation = self.console_generation;
+                self.console_check = Some(ConsoleCheck { generation, code });
+                let tx = self.event_tx.clone();
+                tokio::spawn(async move {
+                    let reply = reply.await.map_err(|e| e.to_string());
+                    let _ = tx.send(AppEvent::IsCompleteReply { generation, reply });
+                });
+            }
+            // Without an answer, run it and let the kernel report syntax errors
+            Err(_) => self.execute_console().await,
+        }
+    }
+
+    /// Act on an is_complete_reply: incomplete input gets a new line with the
+    /// kernel's suggested indent; anything else (complete, invalid, unknown,
+    /// or no reply in time) is executed. Replies are dropped if the input
+    /// changed while the kernel was answering.
+    async fn handle_is_complete(
+        &mut self,
+        generation: u64,
+        reply: std::result::Result<jupyter_protocol::IsCompleteReply, String>,
+    ) {
+        let Some(check) = self.console_check.take_if(|c| c.generation == generation) else {
+            return;
+        };
+        if self.mode != Mode::Console || self.console.input_text() != check.code {
+            return;
+        }
+        match reply {
+            Ok(reply) if reply.status == IsCompleteReplyStatus::Incomplete => {
+                self.console.input.insert_newline();
+                self.console.input.insert_str(&reply.indent);
             }
+            _ => self.execute_console().await,
+        }
+    }
+
+    /// Run the console's input. Its output goes to the console scrollback
+    /// rather than a cell.
+    pub async fn execute_console(&mut self) {
+        let code = self.console.input_text();
+        if code.trim().is_empty() {
+            return;
+        }
+        if self.console.running.is_some() {
+            self.status_message = "Console is still running (Ctrl-c interrupts)".to_string();
+            return;
+        }
+        match self.kernel_client.execute(&code).await {
+            Ok(msg_id) => self.console.submit(&code, msg_id),
+            Err(e) => self.status_message = format!("Execute error: {}", e),
         }
     }
 
-    /// Draw the UI.
+    /// Show the variable panel and move focus to it.
+    pub async fn focus_variables(&mut self) {
+        if self.mode != Mode::Variables {
+            self.variables.return_mode = self.mode.clone();
+        }
+        let was_visible = self.variables.visible;
+        self.variables.visible = true;
+        self.mode = Mode::Variables;
+        if !was_visible {
+            self.refresh_variables().await;
+        }
+    }
+
+    /// Move focus back to where it was before the panel; the panel stays open.
+    pub fn leave_variables(&mut self) {
+        self.mode = self.variables.return_mode.clone();
+    }
+
+    /// `:vars`: hide the panel if it is shown, otherwise show it (without focus).
+    pub async fn toggle_variables(&mut self) {
+        if self.variables.visible {
+            if self.mode == Mode::Variables {
+                self.leave_variables();
+            }
+            self.variables.visible = false;
+        } else {
+            self.variables.visible = true;
+            self.refresh_variables().await;
+        }
+    }
+
+    /// Ask the kernel for its user namespace. The listing arrives later as
+    /// `AppEvent::VariablesReply`. Only Python kernels are supported.
+    pub async fn refresh_variables(&mut self) {
+        self.variables_stale = false;
+        if !self.variables.visible {
+            return;
+        }
+        if let Some(language) = self.notebook.metadata.language()
+            && language != "python"
+        {
+            self.variables.error = Some(format!(
+                "The variable explorer needs a Python kernel ({} kernel running)",
+                language
+            ));
+            return;
+        }
+        match self
+            .kernel_client
+            .evaluate(variables::namespace_request())
+            .await
+        {
+            Ok(reply) => {
+                self.variables_generation += 1;
+                let generation = self.variables_generation;
+                let tx = self.event_tx.clone();
+                tokio::spawn(async move {
+                    let reply = reply
+                        .await
+                        .and_then(|results| variables::parse_namespace(&results))
+                        .map_err(|e| e.to_string());
+                    let _ = tx.send(AppEvent::VariablesReply { generation, reply });
+                });
+            }
+            Err(e) => self.variables.error = Some(e.to_string()),
+        }
+    }
+
+    /// Show a namespace listing, unless a newer request superseded it.
+    fn show_variables(
+        &mut self,
+        generation: u64,
+        reply: std::result::Result<Vec<Variable>, String>,
+    ) {
+        if generation != self.variables_generation {
+            return;
+        }
+        match reply {
+            Ok(variables) => self.variables.update(variables),
+            Err(e) => self.variables.error = Some(e),
+        }
+    }
+
+    /// Ask for the full repr (or DataFrame text) of the selected variable;
+    /// the reply opens it in the pager.
+    pub async fn open_selected_variable(&mut self) {
+        let Some(name) = self.variables.selected_variable().map(|v| v.name.clone()) else {
+            return;
+        };
+        match self
+            .kernel_client
+            .evaluate(variables::detail_request(&name))
+            .await
+        {
+            Ok(reply) => {
+                self.variable_detail_generation += 1;
+                let generation = self.variable_detail_generation;
+                let tx = self.event_tx.clone();
+                tokio::spawn(async move {
+                    let reply = reply
+                        .await
+                        .and_then(|results| variables::parse_detail(&results))
+                        .map_err(|e| e.to_string());
+                    let _ = tx.send(AppEvent::VariableDetail { generation, reply });
+                });
+            }
+            Err(e) => self.status_message = format!("Variable error: {}", e),
+        }
+    }
+
+    /// Open a variable's full view in the pager, if the panel still has focus.
+    fn show_variable_detail(
+        &mut self,
+        generation: u64,
+        reply: std::result::Result<String, String>,
+    ) {
+        if generation != self.variable_detail_generation || self.mode != Mode::Variables {
+            return;
+        }
+        match reply {
+            Ok(text) => self.open_pager(text, 0, None),
+            Err(e) => self.status_message = format!("Variable error: {}", e),
+        }
+    }
+
+    /// Draw the UI, if anything changed since the last frame.
     pub fn draw(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        if !self.needs_redraw {
+            return Ok(());
+        }
         terminal.draw(|frame| {
             ui::layout::render(frame, self);
         })?;
+        self.needs_redraw = false;
         Ok(())
     }
 
     /// Graceful shutdown.
+    /// An attached kernel is left running; we only detach from it.
     pub async fn shutdown(&mut self) -> Result<()> {
-        let _ = self.kernel_client.shutdown(false).await;
+        if !self.kernel_manager.is_attached() {
+            let _ = self.kernel_client.shutdown(false).await;
+        }
         self.kernel_manager.shutdown().await?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::ConsoleEntry;
+    use crate::kernel::backend::NoKernel;
+    use jupyter_protocol::{
+        ClearOutput, DisplayData, ExecuteReply, ExecuteRequest, ExecutionCount, Media, MediaType,
+        StreamContent, UpdateDisplayData,
+    };
+
+    fn app() -> App {
+        let mut notebook = Notebook::new();
+        notebook.cells = vec![Cell::new_code("import time; time.sleep(10)")];
+        let (event_tx, _event_rx) = mpsc::unbounded_channel();
+        App::with_kernel(
+            notebook,
+            Box::new(NoKernel),
+            Box::new(NoKernel),
+            event_tx,
+            Picker::from_fontsize((8, 16)),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_failed_execute_request_skips_the_queue() {
+        let mut app = app();
+        app.notebook.cells.push(Cell::new_code("print(1)"));
+
+        app.execute_all_cells().await.unwrap();
+        assert_eq!(app.notebook.cells[0].execution_state, ExecutionState::Error);
+        assert_eq!(
+            app.notebook.cells[1].execution_state,
+            ExecutionState::Skipped
+        );
+        assert!(app.execution_queue.is_empty());
+        assert!(app.queue_running.is_none());
+        assert!(
+            app.status_message
+                .starts_with("Failed to run cell 1: No kernel; skipped 1 queued cell"),
+            "{}",
+            app.status_message
+        );
+    }
+
+    #[tokio::test]
+    async fn test_idle_ticks_do_not_redraw() {
+        let mut app = app();
+        assert!(app.needs_redraw, "the first frame is always drawn");
+
+        app.needs_redraw = false;
+        app.handle_event(AppEvent::Tick).await.unwrap();
+        assert!(!app.needs_redraw);
+
+        app.handle_event(AppEvent::Resize(80, 24)).await.unwrap();
+        assert!(app.needs_redraw);
+
+        // A running cell's timer redraws on every tick until it finishes
+        app.needs_redraw = false;
+        app.notebook.cells[0].run_started = Some(Instant::now());
+        app.handle_event(AppEvent::Tick).await.unwrap();
+        assert!(app.needs_redraw);
+
+        app.needs_redraw = false;
+        app.notebook.cells[0].run_started = None;
+        app.handle_event(AppEvent::Tick).await.unwrap();
+        assert!(!app.needs_redraw);
+    }
+
+    #[tokio::test]
+    async fn test_failed_auto_restart_keeps_the_app_running() {
+        let mut app = app();
+        app.auto_restart = true;
+
+        app.handle_event(AppEvent::KernelDied {
+            session: 0,
+            reason: "segfault".to_string(),
+        })
+        .await
+        .unwrap();
+        assert_eq!(app.kernel_status, "dead");
+        assert!(app.status_message.contains("restart failed"));
+
+        // A status the kernel sent before it died arrives late
+        let status = JupyterMessage::new(jupyter_protocol::Status::idle(), None);
+        app.handle_event(AppEvent::Kernel(KernelMessage::IoPub(status)))
+            .await
+            .unwrap();
+        assert_eq!(app.kernel_status, "dead");
+    }
+
+    #[test]
+    fn test_reset_kernel_state_ends_running_cells() {
+        let mut app = app();
+        start_run(&mut app, 0);
+        app.notebook.cells[0].execution_state = ExecutionState::Running;
+        app.notebook.cells[0].run_started = Some(Instant::now());
+
+        app.reset_kernel_state("Kernel restarted");
+        let cell = &app.notebook.cells[0];
+        assert_eq!(cell.execution_state, ExecutionState::Error);
+        assert!(cell.run_started.is_none());
+        assert!(matches!(
+            cell.outputs.last(),
+            Some(CellOutput::Error { evalue, .. }) if evalue == "Kernel restarted"
+        ));
+        assert!(app.executing_cells.is_empty());
+    }
+
+    /// A local kernel that can switch to any kernelspec, reached through
+    /// a `NoKernel` connection.
+    struct SwitchableKernel;
+
+    #[async_trait::async_trait]
+    impl KernelBackend for SwitchableKernel {
+        async fn connect(
+            &self,
+        ) -> Result<(
+            Box<dyn KernelConnection>,
+            mpsc::UnboundedReceiver<KernelMessage>,
+        )> {
+            Ok((Box::new(NoKernel), mpsc::unbounded_channel().1))
+        }
+
+        async fn restart(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn kernelspecs(&self) -> Result<Vec<KernelspecInfo>> {
+            Ok(vec![KernelspecInfo {
+                name: "julia".to_string(),
+                display_name: "Julia".to_string(),
+                language: Some("julia".to_string()),
+                location: String::new(),
+            }])
+        }
+
+        async fn change_kernel(&mut self, _name: &str) -> Result<()> {
+            Ok(())
+        }
+
+        async fn shutdown(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn exit_status(&mut self) -> Option<String> {
+            None
+        }
+
+        fn is_attached(&self) -> bool {
+            false
+        }
+
+        fn describe(&self) -> String {
+            "switchable kernel".to_string()
+        }
+
+        fn connection_info(&self) -> Option<&jupyter_protocol::ConnectionInfo> {
+            None
+        }
+    }
+
+    #[tokio::test]
+    async fn test_switch_kernel_ends_the_running_queue() {
+        let mut app = app();
+        app.kernel_manager = Box::new(SwitchableKernel);
+        app.notebook.cells.push(Cell::new_code("print(1)"));
+        let request = start_run(&mut app, 0);
+        app.queue_running = Some(request.header.msg_id.clone());
+        app.notebook.cells[0].execution_state = ExecutionState::Running;
+        let queued = app.notebook.cells[1].id.clone();
+        app.execution_queue.push_back(queued);
+        app.notebook.cells[1].execution_state = ExecutionState::Queued;
+
+        app.switch_kernel("julia").await.unwrap();
+        assert_eq!(app.status_message, "Switched to Julia");
+        assert!(matches!(
+            app.notebook.cells[0].outputs.last(),
+            Some(CellOutput::Error { evalue, .. }) if evalue == "Switched to Julia"
+        ));
+        assert_eq!(app.notebook.cells[0].execution_state, ExecutionState::Error);
+        assert_eq!(
+            app.notebook.cells[1].execution_state,
+            ExecutionState::Skipped
+        );
+        assert!(app.queue_running.is_none());
+        assert!(app.executing_cells.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_timing_is_only_recorded_when_enabled() {
+        let mut app = app();
+        let cell_id = app.notebook.cells[0].id.clone();
+        let run = |app: &mut App| {
+            let request = JupyterMessage::new(ExecuteRequest::new("1".to_string()), None);
+            app.executing_cells
+                .insert(request.header.msg_id.clone(), cell_id.clone());
+            let reply = ExecuteReply {
+                execution_count: ExecutionCount::new(1),
+                ..Default::default()
+            }
+            .as_child_of(&request);
+            app.handle_kernel_message(KernelMessage::ShellReply(reply));
+        };
+
+        run(&mut app);
+        assert_eq!(app.notebook.cells[0].execution_count, Some(1));
+        assert!(app.notebook.cells[0].metadata.execution.is_none());
+
+        app.notebook.dirty = false;
+        run(&mut app);
+        assert!(!app.notebook.dirty, "an unchanged reply is not an edit");
+
+        app.record_timing = true;
+        run(&mut app);
+        let timing = app.notebook.cells[0].metadata.execution.as_ref().unwrap();
+        assert!(timing.shell_execute_reply.is_some());
+        assert!(app.notebook.dirty);
+    }
+
+    #[tokio::test]
+    async fn test_queue_moves_on_at_the_execute_reply() {
+        let mut app = app();
+        app.notebook.cells.push(Cell::new_code("print(1)"));
+        let first = app.notebook.cells[0].id.clone();
+        let second = app.notebook.cells[1].id.clone();
+        let start = |app: &mut App| {
+            let request = JupyterMessage::new(ExecuteRequest::new(String::new()), None);
+            app.executing_cells
+                .insert(request.header.msg_id.clone(), first.clone());
+            app.queue_running = Some(request.header.msg_id.clone());
+            app.execution_queue = VecDeque::from([second.clone()]);
+            app.notebook.cells[0].execution_state = ExecutionState::Running;
+            app.notebook.cells[1].execution_state = ExecutionState::Queued;
+            request
+        };
+
+        // Idle alone (the reply may still be in flight) doesn't move the queue
+        let request = start(&mut app);
+        let mut idle = JupyterMessage::new(jupyter_protocol::Status::idle(), None);
+        idle.parent_header = Some(request.header.clone());
+        app.handle_kernel_message(KernelMessage::IoPub(idle));
+        assert!(app.queue_running.is_some());
+        assert_eq!(app.notebook.cells[0].execution_state, ExecutionState::Done);
+
+        let reply = ExecuteReply::default().as_child_of(&request);
+        app.handle_kernel_message(KernelMessage::ShellReply(reply));
+        assert!(app.queue_running.is_none());
+        assert_eq!(app.execution_queue.front(), Some(&second));
+
+        // A failed reply stops the queue even before IOPub reports the error
+        let request = start(&mut app);
+        let reply = ExecuteReply {
+            status: ReplyStatus::Error,
+            ..Default::default()
+        }
+        .as_child_of(&request);
+        app.handle_kernel_message(KernelMessage::ShellReply(reply));
+        assert!(app.execution_queue.is_empty());
+        assert_eq!(app.notebook.cells[0].execution_state, ExecutionState::Error);
+        assert_eq!(
+            app.notebook.cells[1].execution_state,
+            ExecutionState::Skipped
+        );
+        assert_eq!(app.status_message, "Error in cell 1; skipped 1 queued cell");
+    }
+
+    /// Register an execute_request for cell `cell_idx`, as if it were sent.
+    fn start_run(app: &mut App, cell_idx: usize) -> JupyterMessage {
+        let request = JupyterMessage::new(ExecuteRequest::new(String::new()), None);
+        let cell_id = app.notebook.cells[cell_idx].id.clone();
+        app.executing_cells
+            .insert(request.header.msg_id.clone(), cell_id);
+        request
+    }
+
+    /// Deliver an IOPub message sent in reply to `parent`.
+    fn iopub(app: &mut App, content: impl Into<JupyterMessageContent>, parent: &JupyterMessage) {
+        let mut msg = JupyterMessage::new(content, None);
+        msg.parent_header = Some(parent.header.clone());
+        app.handle_kernel_message(KernelMessage::IoPub(msg));
+    }
+
+    fn plain_text(output: &CellOutput) -> Option<&str> {
+        match output {
+            CellOutput::DisplayData { data, .. } => data.text("text/plain"),
+            CellOutput::Stream { text, .. } => Some(text.rendered()),
+            _ => None,
+        }
+    }
+
+    fn display(text: &str, display_id: &str) -> DisplayData {
+        DisplayData {
+            transient: Some(Transient {
+                display_id: Some(display_id.to_string()),
+            }),
+            ..DisplayData::new(Media::new(vec![MediaType::Plain(text.to_string())]))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_clear_output_wait_defers_until_the_next_output() {
+        let mut app = app();
+        let run = start_run(&mut app, 0);
+        iopub(&mut app, StreamContent::stdout("frame 1\n"), &run);
+
+        iopub(&mut app, ClearOutput { wait: true }, &run);
+        assert_eq!(
+            app.notebook.cells[0].outputs.len(),
+            1,
+            "still showing frame 1"
+        );
+
+        iopub(&mut app, StreamContent::stdout("frame 2\n"), &run);
+        let outputs = &app.notebook.cells[0].outputs;
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(plain_text(&outputs[0]), Some("frame 2\n"));
+
+        iopub(&mut app, ClearOutput { wait: false }, &run);
+        assert!(app.notebook.cells[0].outputs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_console_clear_output_wait_defers_until_the_next_output() {
+        let mut app = app();
+        let run = JupyterMessage::new(ExecuteRequest::new(String::new()), None);
+        app.console
+            .submit("show_progress()", run.header.msg_id.clone());
+        let outputs = |app: &App| {
+            app.console
+                .entries
+                .iter()
+                .filter_map(|entry| match entry {
+                    ConsoleEntry::Output(output) => plain_text(output).map(str::to_string),
+                    ConsoleEntry::Input { .. } => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        iopub(&mut app, StreamContent::stdout("frame 1\n"), &run);
+
+        iopub(&mut app, ClearOutput { wait: true }, &run);
+        assert_eq!(outputs(&app), ["frame 1\n"], "still showing frame 1");
+
+        iopub(&mut app, StreamContent::stdout("frame 2\n"), &run);
+        assert_eq!(outputs(&app), ["frame 2\n"]);
+
+        iopub(&mut app, ClearOutput { wait: false }, &run);
+        assert!(outputs(&app).is_empty());
+        assert_eq!(app.console.entries.len(), 1, "the input stays");
+    }
+
+    #[tokio::test]
+    async fn test_update_display_data_reaches_every_output_with_the_id() {
+        let mut app = app();
+        app.notebook
+            .cells
+            .push(Cell::new_code("display(h, display_id=True)"));
+        let first = start_run(&mut app, 0);
+        iopub(&mut app, display("0%", "progress"), &first);
+        let second = start_run(&mut app, 1);
+        iopub(&mut app, display("0%", "progress"), &second);
+        iopub(&mut app, display("other", "other"), &second);
+
+        // An update may come from any execution, even one that isn't ours
+        let elsewhere = JupyterMessage::new(ExecuteRequest::new(String::new()), None);
+        let update = UpdateDisplayData::new(
+            Media::new(vec![MediaType::Plain("100%".to_string())]),
+            "progress",
+        );
+        iopub(&mut app, update, &elsewhere);
+        assert_eq!(plain_text(&app.notebook.cells[0].outputs[0]), Some("100%"));
+        assert_eq!(plain_text(&app.notebook.cells[1].outputs[0]), Some("100%"));
+        assert_eq!(plain_text(&app.notebook.cells[1].outputs[1]), Some("other"));
+
+        // Cleared outputs no longer receive updates
+        iopub(&mut app, ClearOutput { wait: false }, &second);
+        let update = UpdateDisplayData::new(
+            Media::new(vec![MediaType::Plain("done".to_string())]),
+            "progress",
+        );
+        iopub(&mut app, update, &elsewhere);
+        assert_eq!(plain_text(&app.notebook.cells[0].outputs[0]), Some("done"));
+        assert!(app.notebook.cells[1].outputs.is_empty());
+        assert_eq!(app.display_outputs["progress"].len(), 1);
+    }
+
+    /// Deliver an execute_reply for cell `cell_idx` carrying `payload`.
+    fn reply_with_payload(app: &mut App, cell_idx: usize, payload: Payload) {
+        let request = start_run(app, cell_idx);
+        let reply = ExecuteReply {
+            payload: vec![payload],
+            ..Default::default()
+        }
+        .as_child_of(&request);
+        app.handle_kernel_message(KernelMessage::ShellReply(reply));
+    }
+
+    #[tokio::test]
+    async fn test_set_next_input_replaces_or_inserts() {
+        let mut app = app();
+        app.notebook.cells.push(Cell::new_code("below"));
+        app.selected_cell = 1;
+
+        reply_with_payload(
+            &mut app,
+            0,
+            Payload::SetNextInput {
+                text: "%load script.py".to_string(),
+                replace: true,
+            },
+        );
+        assert_eq!(app.notebook.cells.len(), 2);
+        assert_eq!(app.notebook.cells[0].source, "%load script.py");
+        assert_eq!(app.status_message, "Replaced cell 1");
+
+        reply_with_payload(
+            &mut app,
+            0,
+            Payload::SetNextInput {
+                text: "x = 1".to_string(),
+                replace: false,
+            },
+        );
+        let sources: Vec<&str> = app
+            .notebook
+            .cells
+            .iter()
+            .map(|c| c.source.as_str())
+            .collect();
+        assert_eq!(sources, ["%load script.py", "x = 1", "below"]);
+        assert_eq!(app.selected_cell, 2, "the selection stays on the same cell");
+        assert_eq!(app.status_message, "Inserted cell 2");
+        assert!(app.notebook.dirty);
+    }
+
+    #[tokio::test]
+    async fn test_page_payload_opens_the_pager() {
+        let mut app = app();
+        reply_with_payload(
+            &mut app,
+            0,
+            Payload::Page {
+                data: Media::new(vec![MediaType::Plain("Docstring: ...".to_string())]),
+                start: 3,
+            },
+        );
+        assert_eq!(app.mode, Mode::Pager);
+        let pager = app.pager.as_ref().unwrap();
+        assert_eq!(pager.text, "Docstring: ...");
+        assert_eq!(pager.scroll, 3);
+        assert_eq!(pager.return_mode, Mode::Normal);
+        assert_eq!(app.last_page.as_deref(), Some("Docstring: ..."));
+    }
+
+    #[tokio::test]
+    async fn test_console_page_payload_opens_the_pager() {
+        let mut app = app();
+        app.mode = Mode::Console;
+        let request = JupyterMessage::new(ExecuteRequest::new("len?".to_string()), None);
+        app.console.submit("len?", request.header.msg_id.clone());
+
+        // The reply may arrive after IOPub reported idle
+        iopub(&mut app, jupyter_protocol::Status::idle(), &request);
+        assert!(app.console.running.is_none());
+
+        let reply = ExecuteReply {
+            payload: vec![Payload::Page {
+                data: Media::new(vec![MediaType::Plain("Signature: len(obj)".to_string())]),
+                start: 0,
+            }],
+            ..Default::default()
+        }
+        .as_child_of(&request);
+        app.handle_kernel_message(KernelMessage::ShellReply(reply));
+        assert_eq!(app.mode, Mode::Pager);
+        let pager = app.pager.as_ref().unwrap();
+        assert_eq!(pager.text, "Signature: len(obj)");
+        assert_eq!(pager.return_mode, Mode::Console);
+        assert!(app.notebook.cells[0].outputs.is_empty());
+    }
+}
@@ -0,0 +1,286 @@
+use crate::app::Mode;
+use crate::notebook::model::CellOutput;
+use crate::notebook::stream::StreamText;
+use tui_textarea::{CursorMove, TextArea};
+
+/// How many inputs to ask the kernel's history for when the console opens.
+pub const HISTORY_LENGTH: usize = 1000;
+
+/// One item in the console scrollback.
+#[derive(Debug)]
+pub enum ConsoleEntry {
+    /// Submitted code, numbered once the kernel reports its execution count
+    Input {
+        code: String,
+        execution_count: Option<usize>,
+    },
+    Output(CellOutput),
+}
+
+/// Inputs recalled with Up/Down: the kernel's history from earlier sessions
+/// followed by what was run since.
+#[derive(Debug, Default)]
+pub struct History {
+    /// Oldest first
+    entries: Vec<String>,
+    /// Index of the entry being shown, None while editing new input
+    position: Option<usize>,
+    /// The unsubmitted input, restored when stepping past the newest entry
+    draft: String,
+}
+
+impl History {
+    /// Put the kernel's history (oldest first) before the inputs run so far.
+    pub fn load(&mut self, older: impl IntoIterator<Item = String>) {
+        let recent = std::mem::take(&mut self.entries);
+        for entry in older.into_iter().chain(recent) {
+            self.push(&entry);
+        }
+    }
+
+    /// Record a submitted input and stop browsing.
+    pub fn push(&mut self, code: &str) {
+        if !code.trim().is_empty() && self.entries.last().is_none_or(|last| last != code) {
+            self.entries.push(code.to_string());
+        }
+        self.position = None;
+        self.draft.clear();
+    }
+
+    /// Step to the previous (older) entry. `current` is the input being
+    /// edited, kept as the draft when browsing starts.
+    pub fn prev(&mut self, current: &str) -> Option<&str> {
+        let position = match self.position {
+            None if self.entries.is_empty() => return None,
+            None => {
+                self.draft = current.to_string();
+                self.entries.len() - 1
+            }
+            Some(0) => return None,
+            Some(position) => position - 1,
+        };
+        self.position = Some(position);
+        Some(&self.entries[position])
+    }
+
+    /// Step to the next (newer) entry, or back to the draft after the newest.
+    pub fn next(&mut self) -> Option<&str> {
+        let position = self.position?;
+        if position + 1 < self.entries.len() {
+            self.position = Some(position + 1);
+            Some(&self.entries[position + 1])
+        } else {
+            self.position = None;
+            Some(&self.draft)
+        }
+    }
+}
+
+/// The scratch console: a REPL on the notebook's kernel whose outputs go
+/// to its own scrollback instead of a cell.
+pub struct Console {
+    /// Whether the pane is shown (the scrollback is kept while hidden)
+    pub visible: bool,
+    pub input: TextArea<'static>,
+    pub entries: Vec<ConsoleEntry>,
+    /// Lines scrolled up from the bottom of the scrollback
+    pub scroll: usize,
+    pub history: History,
+    /// The kernel's history has been asked for
+    pub history_requested: bool,
+    /// msg_id of the console's execute_request while it runs
+    pub running: Option<String>,
+    /// msg_id of the last finished request, whose execute_reply may
+    /// arrive after IOPub reported idle
+    pub finished: Option<String>,
+    /// A `clear_output(wait=True)` waits for the next output
+    pub pending_clear: bool,
+    /// Mode to return to when the console loses focus
+    pub return_mode: Mode,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            input: TextArea::default(),
+            entries: Vec::new(),
+            scroll: 0,
+            history: History::default(),
+            history_requested: false,
+            running: None,
+            finished: None,
+            pending_clear: false,
+            return_mode: Mode::Normal,
+        }
+    }
+
+    /// The code typed at the prompt.
+    pub fn input_text(&self) -> String {
+        self.input.lines().join("\n")
+    }
+
+    /// Replace the prompt's text, leaving the cursor at the end.
+    pub fn set_input(&mut self, text: &str) {
+        let mut input = TextArea::new(text.split('\n').map(str::to_string).collect());
+        input.set_cursor_style(self.input.cursor_style());
+        input.set_cursor_line_style(self.input.cursor_line_style());
+        input.move_cursor(CursorMove::Bottom);
+        input.move_cursor(CursorMove::End);
+        self.input = input;
+    }
+
+    /// Whether the cursor is on the prompt's first line (Up recalls history).
+    pub fn cursor_on_first_line(&self) -> bool {
+        self.input.cursor().0 == 0
+    }
+
+    /// Whether the cursor is on the prompt's last line (Down recalls history).
+    pub fn cursor_on_last_line(&self) -> bool {
+        self.input.cursor().0 + 1 >= self.input.lines().len()
+    }
+
+    /// Show the previous (older) history entry at the prompt.
+    pub fn recall_prev(&mut self) {
+        let current = self.input_text();
+        if let Some(entry) = self.history.prev(&current).map(str::to_string) {
+            self.set_input(&entry);
+        }
+    }
+
+    /// Show the next (newer) history entry, or the draft after the newest.
+    pub fn recall_next(&mut self) {
+        if let Some(entry) = self.history.next().map(str::to_string) {
+            self.set_input(&entry);
+        }
+    }
+
+    /// Move submitted code from the prompt into the scrollback.
+    pub fn submit(&mut self, code: &str, msg_id: String) {
+        self.history.push(code);
+        self.entries.push(ConsoleEntry::Input {
+            code: code.to_string(),
+            execution_count: None,
+        });
+        self.set_input("");
+        self.running = Some(msg_id);
+        self.pending_clear = false;
+        self.scroll = 0;
+    }
+
+    /// Number the most recent input.
+    pub fn set_execution_count(&mut self, count: usize) {
+        if let Some(ConsoleEntry::Input {
+            execution_count, ..
+        }) = self
+            .entries
+            .iter_mut()
+            .rev()
+            .find(|e| matches!(e, ConsoleEntry::Input { .. }))
+        {
+            *execution_count = Some(count);
+        }
+    }
+
+    /// Append stream text, continuing the previous output if it is the same stream.
+    pub fn append_stream(&mut self, stream_name: &str, new_text: &str) {
+        self.apply_pending_clear();
+        if let Some(ConsoleEntry::Output(CellOutput::Stream { name, text })) =
+            self.entries.last_mut()
+            && name == stream_name
+        {
+            text.push_str(new_text);
+            return;
+        }
+        self.push_output(CellOutput::Stream {
+            name: stream_name.to_string(),
+            text: StreamText::new(new_text),
+        });
+    }
+
+    pub fn push_output(&mut self, output: CellOutput) {
+        self.apply_pending_clear();
+        self.entries.push(ConsoleEntry::Output(output));
+    }
+
+    /// Drop the outputs of the most recent input (`clear_output`), or with
+    /// `wait`, just before the next output arrives.
+    pub fn clear_output(&mut self, wait: bool) {
+        if wait {
+            self.pending_clear = true;
+        } else {
+            self.clear_last_outputs();
+        }
+    }
+
+    fn apply_pending_clear(&mut self) {
+        if std::mem::take(&mut self.pending_clear) {
+            self.clear_last_outputs();
+        }
+    }
+
+    /// Drop the outputs of the most recent input.
+    pub fn clear_last_outputs(&mut self) {
+        while matches!(self.entries.last(), Some(ConsoleEntry::Output(_))) {
+            self.entries.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_recall_restores_draft() {
+        let mut history = History::default();
+        assert_eq!(history.prev("x"), None);
+        history.push("a = 1");
+        history.push("b = 2");
+        history.push("b = 2");
+
+        assert_eq!(history.prev("draft"), Some("b = 2"));
+        assert_eq!(history.prev("ignored"), Some("a = 1"));
+        assert_eq!(history.prev("ignored"), None);
+        assert_eq!(history.next(), Some("b = 2"));
+        assert_eq!(history.next(), Some("draft"));
+        assert_eq!(history.next(), None);
+    }
+
+    #[test]
+    fn test_history_load_goes_before_recent_inputs() {
+        let mut history = History::default();
+        history.push("recent");
+        history.load(["old".to_string(), "old".to_string(), "older".to_string()]);
+
+        assert_eq!(history.prev(""), Some("recent"));
+        assert_eq!(history.prev(""), Some("older"));
+        assert_eq!(history.prev(""), Some("old"));
+        assert_eq!(history.prev(""), None);
+    }
+
+    #[test]
+    fn test_console_streams_merge_until_the_next_output() {
+        let mut console = Console::new();
+        console.submit("print(1)", "m1".to_string());
+        console.set_execution_count(3);
+        console.append_stream("stdout", "1\n");
+        console.append_stream("stdout", "2\n");
+        console.append_stream("stderr", "warn\n");
+        console.append_stream("stdout", "3\n");
+
+        assert!(matches!(
+            &console.entries[0],
+            ConsoleEntry::Input { code, execution_count: Some(3) } if code == "print(1)"
+        ));
+        assert_eq!(console.entries.len(), 4);
+        let ConsoleEntry::Output(CellOutput::Stream { text, .. }) = &console.entries[1] else {
+            panic!("Expected stdout");
+        };
+        assert_eq!(text.raw(), "1\n2\n");
+
+        console.clear_last_outputs();
+        assert_eq!(console.entries.len(), 1);
+        assert_eq!(console.input_text(), "");
+    }
+}
@@ -1,6 +1,8 @@
 use crate::kernel::client::KernelMessage;
+use crate::variables::Variable;
 use crossterm::event::{Event as CrosstermEvent, EventStream, KeyEvent};
 use futures::StreamExt;
+use jupyter_protocol::{CompleteReply, HistoryReply, InspectReply, IsCompleteReply};
 use tokio::sync::mpsc;
 
 /// Unified application event type.
@@ -8,14 +10,46 @@ use tokio::sync::mpsc;
 /// Merges terminal input events with kernel messages
 /// into a single stream for the main event loop.
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum AppEvent {
     /// A key was pressed
     Key(KeyEvent),
     /// Terminal was resized
+    #[allow(dead_code)]
     Resize(u16, u16),
     /// A message arrived from the kernel
     Kernel(KernelMessage),
-    /// Render tick (for periodic redraws if needed)
+    /// The kernel stopped answering heartbeats (see `kernel::monitor`)
+    KernelDied { session: u64, reason: String },
+    /// A complete_reply arrived (or the request failed)
+    CompletionReply {
+        generation: u64,
+        reply: Result<CompleteReply, String>,
+    },
+    /// An inspect_reply arrived (or the request failed)
+    InspectReply {
+        generation: u64,
+        reply: Result<InspectReply, String>,
+    },
+    /// A history_reply arrived for the console (or the request failed)
+    HistoryReply(Result<HistoryReply, String>),
+    /// An is_complete_reply arrived for the console's input (or the request failed)
+    IsCompleteReply {
+        generation: u64,
+        reply: Result<IsCompleteReply, String>,
+    },
+    /// The variable explorer's namespace listing arrived (or the request failed)
+    VariablesReply {
+        generation: u64,
+        reply: Result<Vec<Variable>, String>,
+    },
+    /// The full view of a variable arrived (or the request failed)
+    VariableDetail {
+        generation: u64,
+        reply: Result<String, String>,
+    },
+    /// Periodic tick: timers, debounced completion and kernel exit polling.
+    /// Only redraws while something on screen is animated.
     Tick,
 }
 
@@ -34,18 +68,13 @@ pub async fn run_event_loop(
             maybe_event = reader.next() => {
                 match maybe_event {
                     Some(Ok(event)) => {
-                        match event {
-                            CrosstermEvent::Key(key) => {
-                                if tx.send(AppEvent::Key(key)).is_err() {
-                                    break;
-                                }
-                            }
-                            CrosstermEvent::Resize(w, h) => {
-                                if tx.send(AppEvent::Resize(w, h)).is_err() {
-                                    break;
-                                }
-                            }
-                            _ => {}
+                        let app_event = match event {
+                            CrosstermEvent::Key(key) => AppEvent::Key(key),
+                            CrosstermEvent::Resize(w, h) => AppEvent::Resize(w, h),
+                            _ => continue,
+                        };
+                        if tx.send(app_event).is_err() {
+                            break;
                         }
                     }
                     Some(Err(_)) => break,
@@ -58,7 +87,7 @@ pub async fn run_event_loop(
                     break;
                 }
             }
-            // Periodic tick for redraws
+            // Periodic tick (see `AppEvent::Tick`)
             _ = tick_interval.tick() => {
                 if tx.send(AppEvent::Tick).is_err() {
                     break;
@@ -0,0 +1,253 @@
+use jupyter_protocol::CompleteReply;
+use std::time::Duration;
+
+/// Pause after typing `.` before completions are requested automatically.
+pub const AUTO_COMPLETE_DELAY: Duration = Duration::from_millis(150);
+
+/// Whether typing `c` continues the word being completed.
+pub fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// One completion candidate, with the type information IPython sends in
+/// `metadata._jupyter_types_experimental` when available.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompletionItem {
+    pub text: String,
+    /// Jedi type: function, class, module, instance, keyword, ...
+    pub kind: Option<String>,
+    /// Call signature, e.g. `(a, b=1)`
+    pub signature: Option<String>,
+}
+
+impl CompletionItem {
+    /// Short tag shown before the match in the completion panel.
+    pub fn kind_tag(&self) -> &str {
+        match self.kind.as_deref() {
+            Some("function") => "fn",
+            Some("class") => "cls",
+            Some("module") => "mod",
+            Some("instance") | Some("statement") => "var",
+            Some("keyword") => "kw",
+            Some("param") => "arg",
+            Some("property") => "prop",
+            Some("path") => "path",
+            Some("magic") => "%",
+            Some(other) => other,
+            None => "",
+        }
+    }
+}
+
+/// The open completion panel: the kernel's matches and the subset that
+/// fuzzy-matches what has been typed since.
+#[derive(Debug)]
+pub struct Completion {
+    pub items: Vec<CompletionItem>,
+    /// Indices into `items` that match the typed text, best first
+    pub visible: Vec<usize>,
+    /// Index into `visible`
+    pub selected: usize,
+    /// Code point offset where the replaced text starts
+    pub cursor_start: usize,
+    /// Code points after the cursor that a completion also replaces
+    pub tail: usize,
+}
+
+impl Completion {
+    /// Build the panel from a reply to a request sent with the cursor at
+    /// `request_offset`. Returns None when nothing matches `query` (the text
+    /// typed between `cursor_start` and the cursor).
+    pub fn from_reply(reply: CompleteReply, request_offset: usize, query: &str) -> Option<Self> {
+        let tail = reply.cursor_end.saturating_sub(request_offset);
+        let mut completion = Completion {
+            items: completion_items(reply.matches, &reply.metadata),
+            visible: Vec::new(),
+            selected: 0,
+            cursor_start: reply.cursor_start,
+            tail,
+        };
+        completion.filter(query).then_some(completion)
+    }
+
+    /// Narrow the visible matches to those fuzzy-matching `query`.
+    /// Returns false when none do.
+    pub fn filter(&mut self, query: &str) -> bool {
+        let mut scored: Vec<(i64, usize)> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, item)| fuzzy_score(query, &item.text).map(|score| (score, i)))
+            .collect();
+        // Best score first; ties keep the kernel's order
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        self.visible = scored.into_iter().map(|(_, i)| i).collect();
+        self.selected = 0;
+        !self.visible.is_empty()
+    }
+
+    /// The highlighted match.
+    pub fn selected_item(&self) -> Option<&CompletionItem> {
+        self.visible.get(self.selected).map(|&i| &self.items[i])
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % self.visible.len();
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self
+            .selected
+            .checked_sub(1)
+            .unwrap_or(self.visible.len() - 1);
+    }
+}
+
+/// Pair matches with their `_jupyter_types_experimental` entries.
+fn completion_items(
+    matches: Vec<String>,
+    metadata: &serde_json::Map<String, serde_json::Value>,
+) -> Vec<CompletionItem> {
+    let types = metadata
+        .get("_jupyter_types_experimental")
+        .and_then(|t| t.as_array());
+    let lookup = |text: &str, field: &str| {
+        types?
+            .iter()
+            .find(|t| t.get("text").and_then(|v| v.as_str()) == Some(text))?
+            .get(field)?
+            .as_str()
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+    };
+
+    matches
+        .into_iter()
+        .map(|text| CompletionItem {
+            kind: lookup(&text, "type"),
+            signature: lookup(&text, "signature"),
+            text,
+        })
+        .collect()
+}
+
+/// Score `candidate` against `query` as an in-order subsequence match, or
+/// None if it doesn't match. Prefix, consecutive and word-start matches
+/// score higher; case is ignored unless the query contains uppercase.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    if query.is_empty() {
+        return Some(0);
+    }
+    let smart_case = query.chars().any(char::is_uppercase);
+    let same = |a: char, b: char| {
+        if smart_case {
+            a == b
+        } else {
+            a.to_lowercase().eq(b.to_lowercase())
+        }
+    };
+
+    let candidate: Vec<char> = candidate.chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut previous: Option<usize> = None;
+    for q in query.chars() {
+        let found = (pos..candidate.len()).find(|&i| same(q, candidate[i]))?;
+        score += 1;
+        if found == 0 {
+            score += 8;
+        } else if candidate[found - 1] == '_'
+            || candidate[found - 1] == '.'
+            || (candidate[found].is_uppercase() && candidate[found - 1].is_lowercase())
+        {
+            score += 4;
+        }
+        match previous {
+            Some(p) if found == p + 1 => score += 5,
+            Some(p) => score -= (found - p - 1) as i64,
+            None => score -= found as i64,
+        }
+        previous = Some(found);
+        pos = found + 1;
+    }
+    // Prefer shorter candidates among equal matches
+    Some(score * 16 - candidate.len() as i64)
+}
+
+/// Byte index of code point `pos` in `s` (None past the end).
+/// Jupyter's cursor_pos, cursor_start and cursor_end count code points.
+pub fn char_to_byte(s: &str, pos: usize) -> Option<usize> {
+    s.char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(s.len()))
+        .nth(pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(matches: &[&str], metadata: serde_json::Value) -> CompleteReply {
+        CompleteReply {
+            matches: matches.iter().map(|m| m.to_string()).collect(),
+            cursor_start: 3,
+            cursor_end: 5,
+            metadata: metadata.as_object().unwrap().clone(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fuzzy_score_prefers_prefix_and_consecutive_matches() {
+        assert!(fuzzy_score("xyz", "path").is_none());
+        assert!(fuzzy_score("pth", "path").is_some());
+        assert!(fuzzy_score("pa", "path") > fuzzy_score("pa", "sep_path"));
+        assert!(fuzzy_score("jn", "join") < fuzzy_score("jo", "join"));
+        assert!(fuzzy_score("rs", "read_split") > fuzzy_score("rs", "parse"));
+        // Smart case: uppercase queries match case-sensitively
+        assert!(fuzzy_score("DF", "dataframe").is_none());
+        assert!(fuzzy_score("df", "DataFrame").is_some());
+    }
+
+    #[test]
+    fn test_completion_reads_type_metadata_and_filters() {
+        let metadata = serde_json::json!({
+            "_jupyter_types_experimental": [
+                {"text": "join", "type": "function", "signature": "(a, *p)"},
+                {"text": "sep", "type": "instance", "signature": ""},
+            ]
+        });
+        let mut completion =
+            Completion::from_reply(reply(&["join", "sep", "exists"], metadata), 5, "").unwrap();
+        assert_eq!(completion.tail, 0);
+        assert_eq!(completion.visible, [0, 1, 2]);
+        let join = completion.selected_item().unwrap();
+        assert_eq!(join.kind_tag(), "fn");
+        assert_eq!(join.signature.as_deref(), Some("(a, *p)"));
+        assert_eq!(completion.items[1].signature, None);
+        assert_eq!(completion.items[2].kind, None);
+
+        assert!(completion.filter("es"));
+        assert_eq!(completion.visible, [2]);
+        assert!(!completion.filter("q"));
+    }
+
+    #[test]
+    fn test_char_to_byte_counts_code_points() {
+        let source = "é = 1\nπ.";
+        assert_eq!(char_to_byte(source, 0), Some(0));
+        assert_eq!(char_to_byte(source, 1), Some(2));
+        assert_eq!(char_to_byte(source, 8), Some(source.len()));
+        assert_eq!(char_to_byte(source, 9), None);
+    }
+
+    #[test]
+    fn test_completion_selection_wraps() {
+        let mut completion =
+            Completion::from_reply(reply(&["a", "b"], serde_json::json!({})), 5, "").unwrap();
+        completion.select_prev();
+        assert_eq!(completion.selected_item().unwrap().text, "b");
+        completion.select_next();
+        assert_eq!(completion.selected_item().unwrap().text, "a");
+    }
+}
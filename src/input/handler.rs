@@ -1,4 +1,5 @@
 use crate::app::{App, Mode, SearchDirection};
+use crate::input::completion;
 use crate::input::vim::CellVimAction;
 use crate::notebook::model::{Cell, CellType};
 use anyhow::Result;
@@ -64,6 +65,10 @@ pub async fn handle_normal_mode(app: &mut App, key: KeyEvent) -> Result<()> {
             app.selected_cell = app.selected_cell.saturating_sub(n);
         }
 
+        // Scroll the selected cell's HTML tables sideways
+        KeyCode::Char('h') | KeyCode::Left => app.scroll_tables(-(n as isize)),
+        KeyCode::Char('l') | KeyCode::Right => app.scroll_tables(n as isize),
+
         // Move cell down/up (Shift+J/K)
         KeyCode::Char('J') => {
             for _ in 0..n {
@@ -117,13 +122,7 @@ pub async fn handle_normal_mode(app: &mut App, key: KeyEvent) -> Result<()> {
         }
         KeyCode::Char('d') => {
             // Delete selected cell, yank it into buffer
-            if let Some(removed) = app.notebook.delete_cell(app.selected_cell) {
-                app.yanked_cell = Some(removed);
-                if app.selected_cell >= app.notebook.cells.len() {
-                    app.selected_cell = app.notebook.cells.len() - 1;
-                }
-                app.status_message = "Cell deleted (yanked)".to_string();
-            }
+            app.delete_selected_cell();
         }
 
         // Yank cell
@@ -191,6 +190,15 @@ pub async fn handle_normal_mode(app: &mut App, key: KeyEvent) -> Result<()> {
             app.command_buffer.clear();
         }
 
+        // Scratch console
+        KeyCode::Char('`') => app.focus_console().await,
+
+        // Variable explorer
+        KeyCode::Char('V') => app.focus_variables().await,
+
+        // Browse the selected cell's JSON output as a tree
+        KeyCode::Char('t') => app.focus_json_tree(),
+
         // Quick save
         KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
             match app.notebook.save(None) {
@@ -300,6 +308,9 @@ pub async fn handle_cell_normal_mode(app: &mut App, key: KeyEvent) -> Result<()>
             app.execute_selected_cell().await?;
             app.exit_cell();
         }
+        CellVimAction::Inspect => {
+            app.inspect_at_cursor().await;
+        }
     }
 
     Ok(())
@@ -313,26 +324,33 @@ pub enum CellInsertAction {
     ExecuteAndExit,
     /// Request tab completion from the kernel
     RequestCompletion,
+    /// Show the kernel's documentation for the name under the cursor (Shift-Tab)
+    Inspect,
 }
 
 /// Handle key events in CellInsert mode (typing in a cell).
 /// Esc returns to CellNormal (not App Normal).
 pub fn handle_cell_insert_mode(app: &mut App, key: KeyEvent) -> CellInsertAction {
+    let typed = match key.code {
+        KeyCode::Char(c)
+            if !key
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+        {
+            Some(c)
+        }
+        _ => None,
+    };
+
     // If completions are showing, handle navigation keys
-    if !app.completions.is_empty() {
+    if let Some(completion) = &mut app.completion {
         match key.code {
-            KeyCode::Tab => {
-                // Cycle forward through completions
-                app.completion_selected = (app.completion_selected + 1) % app.completions.len();
+            KeyCode::Tab | KeyCode::Down => {
+                completion.select_next();
                 return CellInsertAction::None;
             }
-            KeyCode::BackTab => {
-                // Cycle backward through completions
-                if app.completion_selected == 0 {
-                    app.completion_selected = app.completions.len() - 1;
-                } else {
-                    app.completion_selected -= 1;
-                }
+            KeyCode::BackTab | KeyCode::Up => {
+                completion.select_prev();
                 return CellInsertAction::None;
             }
             KeyCode::Enter if !key.modifiers.contains(KeyModifiers::SHIFT) => {
@@ -345,18 +363,20 @@ pub fn handle_cell_insert_mode(app: &mut App, key: KeyEvent) -> CellInsertAction
                 app.clear_completions();
                 return CellInsertAction::None;
             }
-            KeyCode::Up => {
-                // Navigate up in completion list
-                if app.completion_selected == 0 {
-                    app.completion_selected = app.completions.len() - 1;
-                } else {
-                    app.completion_selected -= 1;
+            KeyCode::Backspace => {
+                // Widen the matches again without asking the kernel
+                if let Some(editor) = &mut app.editor {
+                    editor.input(key);
                 }
+                app.refilter_completions();
                 return CellInsertAction::None;
             }
-            KeyCode::Down => {
-                // Navigate down in completion list
-                app.completion_selected = (app.completion_selected + 1) % app.completions.len();
+            _ if typed.is_some_and(completion::is_identifier_char) => {
+                // Narrow the matches as the word is typed, without asking the kernel
+                if let Some(editor) = &mut app.editor {
+                    editor.input(key);
+                }
+                app.refilter_completions();
                 return CellInsertAction::None;
             }
             _ => {
@@ -380,11 +400,25 @@ pub fn handle_cell_insert_mode(app: &mut App, key: KeyEvent) -> CellInsertAction
             // Request completion from kernel
             return CellInsertAction::RequestCompletion;
         }
+        KeyCode::BackTab => {
+            // Request documentation from kernel
+            return CellInsertAction::Inspect;
+        }
         _ => {
             // Forward the key event to the TextArea editor
             if let Some(editor) = &mut app.editor {
                 editor.input(key);
             }
+            // `.` asks for completions once typing pauses; finishing the
+            // word postpones that, anything else cancels it
+            match typed {
+                Some('.') => {
+                    app.clear_completions();
+                    app.schedule_completion();
+                }
+                Some(c) if completion::is_identifier_char(c) => app.defer_completion(),
+                _ => app.clear_completions(),
+            }
         }
     }
     CellInsertAction::None
@@ -410,16 +444,15 @@ pub fn handle_cell_visual_mode(app: &mut App, key: KeyEvent) -> bool {
     app.cell_vim = cell_vim;
 
     match action {
-        CellVimAction::Nop => {
-            // Visual actions like y/d return Nop but we go back to CellNormal
-            // Check if selection was cancelled (y/d/Esc all cancel it)
-            if key.code == KeyCode::Char('y')
-                || key.code == KeyCode::Char('d')
-                || key.code == KeyCode::Esc
-                || key.code == KeyCode::Char('v')
-            {
-                app.return_to_cell_normal();
-            }
+        // Visual actions like y/d return Nop but we go back to CellNormal
+        // Check if selection was cancelled (y/d/Esc all cancel it)
+        CellVimAction::Nop
+            if matches!(
+                key.code,
+                KeyCode::Char('y') | KeyCode::Char('d') | KeyCode::Esc | KeyCode::Char('v')
+            ) =>
+        {
+            app.return_to_cell_normal();
         }
         CellVimAction::EnterInsert => {
             // c in visual: cut selection then insert
@@ -550,6 +583,190 @@ pub fn handle_search_mode(app: &mut App, key: KeyEvent) {
     }
 }
 
+/// Handle key events while answering a kernel input_request.
+/// Enter sends the reply; Ctrl+C (handled globally) interrupts the kernel instead.
+pub fn handle_input_mode(app: &mut App, key: KeyEvent) {
+    let Some(prompt) = &mut app.input_prompt else {
+        app.mode = Mode::Normal;
+        return;
+    };
+
+    match key.code {
+        KeyCode::Enter => app.submit_input(),
+        KeyCode::Char(c) => prompt.buffer.push(c),
+        KeyCode::Backspace => {
+            prompt.buffer.pop();
+        }
+        _ => {}
+    }
+}
+
+/// Parse a 1-indexed inclusive cell range ("2,5" or "3") into 0-indexed bounds.
+fn parse_cell_range(range: &str, num_cells: usize) -> Option<(usize, usize)> {
+    let (first, last) = match range.trim().split_once(',') {
+        Some((first, last)) => (first.trim().parse().ok()?, last.trim().parse().ok()?),
+        None => {
+            let n: usize = range.trim().parse().ok()?;
+            (n, n)
+        }
+    };
+    if first == 0 || first > last || last > num_cells {
+        return None;
+    }
+    Some((first - 1, last - 1))
+}
+
+/// Handle key events in the kernelspec picker popup.
+pub async fn handle_kernel_picker_mode(app: &mut App, key: KeyEvent) -> Result<()> {
+    let Some(picker) = &mut app.kernel_picker else {
+        app.mode = Mode::Normal;
+        return Ok(());
+    };
+
+    match key.code {
+        KeyCode::Char('j') | KeyCode::Down => {
+            picker.selected = (picker.selected + 1).min(picker.kernels.len() - 1);
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            picker.selected = picker.selected.saturating_sub(1);
+        }
+        KeyCode::Enter => {
+            let name = picker.kernels[picker.selected].name.clone();
+            app.close_kernel_picker();
+            if let Err(e) = app.switch_kernel(&name).await {
+                app.status_message = format!("Kernel switch failed: {:#}", e);
+            }
+        }
+        KeyCode::Esc | KeyCode::Char('q') => app.close_kernel_picker(),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Handle key events at the scratch console prompt.
+/// Enter runs complete input or continues incomplete input on a new line;
+/// Up/Down on the first/last line recall history.
+pub async fn handle_console_mode(app: &mut App, key: KeyEvent) {
+    let console = &mut app.console;
+    match key.code {
+        KeyCode::Esc => app.leave_console(),
+        KeyCode::Enter if key.modifiers.contains(KeyModifiers::ALT) => {
+            console.input.insert_newline();
+        }
+        KeyCode::Enter if key.modifiers.contains(KeyModifiers::SHIFT) => {
+            app.execute_console().await;
+        }
+        KeyCode::Enter => app.console_enter().await,
+        KeyCode::Up if console.cursor_on_first_line() => console.recall_prev(),
+        KeyCode::Down if console.cursor_on_last_line() => console.recall_next(),
+        KeyCode::PageUp => console.scroll += 10,
+        KeyCode::PageDown => console.scroll = console.scroll.saturating_sub(10),
+        KeyCode::Char('l') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            console.entries.clear();
+            console.scroll = 0;
+        }
+        KeyCode::Tab => {
+            console.input.insert_str("    ");
+        }
+        _ => {
+            console.input.input(key);
+        }
+    }
+}
+
+/// Handle key events in the variable explorer panel.
+pub async fn handle_variables_mode(app: &mut App, key: KeyEvent) {
+    let panel = &mut app.variables;
+    let last = panel.variables.len().saturating_sub(1);
+    match key.code {
+        KeyCode::Char('j') | KeyCode::Down => panel.selected = (panel.selected + 1).min(last),
+        KeyCode::Char('k') | KeyCode::Up => panel.selected = panel.selected.saturating_sub(1),
+        KeyCode::Char('g') => panel.selected = 0,
+        KeyCode::Char('G') => panel.selected = last,
+        KeyCode::Enter => app.open_selected_variable().await,
+        KeyCode::Char('r') => app.refresh_variables().await,
+        KeyCode::Esc | KeyCode::Char('q') => app.leave_variables(),
+        _ => {}
+    }
+}
+
+/// Handle key events while browsing a JSON output tree.
+pub fn handle_json_tree_mode(app: &mut App, key: KeyEvent) {
+    // Typing a search after `/`
+    if let Some(query) = &mut app.json_search {
+        match key.code {
+            KeyCode::Esc => app.json_search = None,
+            KeyCode::Enter => app.search_json_tree(false),
+            KeyCode::Backspace => {
+                query.pop();
+            }
+            KeyCode::Char(c) => query.push(c),
+            _ => {}
+        }
+        return;
+    }
+
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') => return app.leave_json_tree(),
+        KeyCode::Char('/') => return app.json_search = Some(String::new()),
+        KeyCode::Char('n') => return app.search_json_tree(false),
+        KeyCode::Char('N') => return app.search_json_tree(true),
+        KeyCode::Char('y') => return app.copy_json_selection(false),
+        KeyCode::Char('Y') => return app.copy_json_selection(true),
+        KeyCode::Tab => return app.focus_json_tree(),
+        _ => {}
+    }
+
+    let Some((root, view)) = app.focused_json() else {
+        // The output was cleared or replaced
+        app.leave_json_tree();
+        return;
+    };
+    match key.code {
+        KeyCode::Char('j') | KeyCode::Down => view.move_cursor(root, 1),
+        KeyCode::Char('k') | KeyCode::Up => view.move_cursor(root, -1),
+        KeyCode::Char('d') | KeyCode::PageDown => view.move_cursor(root, 10),
+        KeyCode::Char('u') | KeyCode::PageUp => view.move_cursor(root, -10),
+        KeyCode::Char('g') => view.move_to_top(),
+        KeyCode::Char('G') => view.move_to_bottom(root),
+        KeyCode::Char('l') | KeyCode::Right => view.expand(root),
+        KeyCode::Char('h') | KeyCode::Left => view.collapse(root),
+        KeyCode::Enter | KeyCode::Char(' ') => view.toggle(root),
+        KeyCode::Char('L') => view.set_subtree_expanded(root, true),
+        KeyCode::Char('H') => view.set_subtree_expanded(root, false),
+        _ => {}
+    }
+}
+
+/// Handle key events in the pager popup.
+pub async fn handle_pager_mode(app: &mut App, key: KeyEvent) {
+    let Some(panel) = &mut app.pager else {
+        app.mode = Mode::Normal;
+        return;
+    };
+
+    let max_scroll = panel.text.lines().count().saturating_sub(1) as u16;
+    match key.code {
+        KeyCode::Char('j') | KeyCode::Down => {
+            panel.scroll = (panel.scroll + 1).min(max_scroll);
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            panel.scroll = panel.scroll.saturating_sub(1);
+        }
+        KeyCode::Char('d') | KeyCode::PageDown => {
+            panel.scroll = (panel.scroll + 10).min(max_scroll);
+        }
+        KeyCode::Char('u') | KeyCode::PageUp => {
+            panel.scroll = panel.scroll.saturating_sub(10);
+        }
+        KeyCode::Char('g') => panel.scroll = 0,
+        KeyCode::Char('G') => panel.scroll = max_scroll,
+        KeyCode::Char('?') => app.toggle_inspect_detail().await,
+        KeyCode::Esc | KeyCode::Char('q') => app.close_pager(),
+        _ => {}
+    }
+}
+
 /// Search for the last_search pattern across cells starting from the current position.
 /// `reverse` flips the direction relative to `app.search_direction`.
 /// Stays in Normal mode and highlights matches across all cells.
@@ -672,36 +889,41 @@ fn find_all_matches_in_cells(app: &mut App, pattern: &str) {
 }
 
 /// Apply the currently selected completion to the editor.
-/// Replaces the text between cursor_start and cursor_end with the selected match.
+/// Replaces the word being completed (from cursor_start to the cursor, plus
+/// any tail the kernel asked to replace) with the selected match.
 fn apply_completion(app: &mut App) {
-    let selected = app.completion_selected;
-    if selected >= app.completions.len() {
-        app.clear_completions();
+    let Some(completion) = app.completion.take() else {
         return;
-    }
-
-    let completion = app.completions[selected].clone();
-    let cursor_start = app.completion_cursor_start;
-    let cursor_end = app.completion_cursor_end;
+    };
     app.clear_completions();
+    let Some(item) = completion.selected_item() else {
+        return;
+    };
+    let completion_text = item.text.clone();
+    let cursor_start = completion.cursor_start;
 
     if let Some(editor) = &mut app.editor {
         // Build the full source, replace the range, and reload the editor
         let lines = editor.lines();
         let source = lines.join("\n");
-
-        // cursor_start and cursor_end are byte offsets into the full source
-        if cursor_start <= source.len() && cursor_end <= source.len() && cursor_start <= cursor_end
+        let (cursor_row, cursor_col) = editor.cursor();
+        let cursor = lines[..cursor_row]
+            .iter()
+            .map(|line| line.chars().count() + 1)
+            .sum::<usize>()
+            + cursor_col;
+        let cursor_end = cursor + completion.tail;
+
+        // cursor_start and cursor_end count code points; slice by bytes
+        if let (Some(start), Some(end)) = (
+            completion::char_to_byte(&source, cursor_start),
+            completion::char_to_byte(&source, cursor_end),
+        ) && start <= end
         {
-            let new_source = format!(
-                "{}{}{}",
-                &source[..cursor_start],
-                completion,
-                &source[cursor_end..]
-            );
+            let new_source = format!("{}{}{}", &source[..start], completion_text, &source[end..]);
 
             // Calculate the cursor position after insertion
-            let new_cursor_byte = cursor_start + completion.len();
+            let new_cursor_byte = start + completion_text.len();
             let (new_row, new_col) = byte_offset_to_row_col(&new_source, new_cursor_byte);
 
             // Reload editor with new content
@@ -805,6 +1027,12 @@ async fn execute_command(app: &mut App, cmd: &str) -> Result<()> {
         "run-all" | "ra" => {
             app.execute_all_cells().await?;
         }
+        "run-above" | "rab" => {
+            app.execute_cells_above().await?;
+        }
+        "run-below" | "rbe" => {
+            app.execute_cells_below().await?;
+        }
         "restart" => {
             app.restart_kernel().await?;
         }
@@ -817,6 +1045,33 @@ async fn execute_command(app: &mut App, cmd: &str) -> Result<()> {
             let _ = app.kernel_client.interrupt().await;
             app.status_message = "Interrupt sent to kernel".to_string();
         }
+        "kernel" => {
+            app.open_kernel_picker().await;
+        }
+        "pager" => app.reopen_page(),
+        "console" => app.toggle_console().await,
+        "vars" => app.toggle_variables().await,
+        "set autorestart" | "set noautorestart" => {
+            app.auto_restart = cmd == "set autorestart";
+            app.status_message = format!(
+                "Auto-restart {}",
+                if app.auto_restart { "on" } else { "off" }
+            );
+        }
+        "set recordtiming" | "set norecordtiming" => {
+            app.record_timing = cmd == "set recordtiming";
+            app.status_message = format!(
+                "Timing recording {}",
+                if app.record_timing { "on" } else { "off" }
+            );
+        }
+        "set autocomplete" | "set noautocomplete" => {
+            app.auto_complete = cmd == "set autocomplete";
+            app.status_message = format!(
+                "Auto-complete {}",
+                if app.auto_complete { "on" } else { "off" }
+            );
+        }
         _ => {
             // Check for :Nc pattern (go to cell N)
             // e.g., :3c goes to cell 3
@@ -858,6 +1113,49 @@ async fn execute_command(app: &mut App, cmd: &str) -> Result<()> {
                         format!("Unknown command: {} (use :{}c for cell)", cmd, cmd);
                 }
             }
+            // :set notifyafter=N - notify when runs take N seconds or more (0 = off)
+            else if let Some(secs) = cmd.strip_prefix("set notifyafter=") {
+                match secs.trim().parse::<u64>() {
+                    Ok(0) => {
+                        app.notify_after = None;
+                        app.status_message = "Notifications off".to_string();
+                    }
+                    Ok(secs) => {
+                        app.notify_after = Some(std::time::Duration::from_secs(secs));
+                        app.status_message = format!("Notifying when runs take {}s or more", secs);
+                    }
+                    Err(_) => app.status_message = format!("Invalid number of seconds: {}", secs),
+                }
+            }
+            // :run-selection N,M - run cells N through M
+            else if let Some(range) = cmd
+                .strip_prefix("run-selection ")
+                .or_else(|| cmd.strip_prefix("rs "))
+            {
+                match parse_cell_range(range, app.notebook.cells.len()) {
+                    Some((first, last)) => app.execute_cell_range(first, last).await?,
+                    None => {
+                        app.status_message = format!(
+                            "Invalid cell range: {} (expected N,M within 1-{})",
+                            range.trim(),
+                            app.notebook.cells.len()
+                        )
+                    }
+                }
+            }
+            // :kernel <name> - switch to another kernelspec
+            else if let Some(name) = cmd.strip_prefix("kernel ") {
+                if let Err(e) = app.switch_kernel(name.trim()).await {
+                    app.status_message = format!("Kernel switch failed: {:#}", e);
+                }
+            }
+            // :connect <connection-file> - attach to an existing kernel
+            else if let Some(file) = cmd.strip_prefix("connect ") {
+                let path = std::path::Path::new(file.trim());
+                if let Err(e) = app.connect_existing(path).await {
+                    app.status_message = format!("Connect failed: {:#}", e);
+                }
+            }
             // :w <filename> - save to specific file
             else if let Some(filename) = cmd.strip_prefix("w ") {
                 let path = std::path::Path::new(filename.trim());
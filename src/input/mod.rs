@@ -1,2 +1,3 @@
+pub mod completion;
 pub mod handler;
 pub mod vim;
@@ -42,6 +42,8 @@ pub enum CellVimAction {
     SearchPrev,
     /// Execute cell and exit to Normal mode (Shift+Enter)
     ExecuteCellAndExit,
+    /// Show the kernel's documentation for the name under the cursor (K)
+    Inspect,
 }
 
 /// Vim state machine for in-cell editing.
@@ -366,6 +368,11 @@ impl CellVim {
                 return CellVimAction::ExecuteCell;
             }
 
+            // -- Documentation lookup --
+            KeyCode::Char('K') if !ctrl => {
+                return CellVimAction::Inspect;
+            }
+
             // -- Execute cell and exit to Normal mode --
             KeyCode::Enter if shift => {
                 return CellVimAction::ExecuteCellAndExit;
@@ -498,7 +505,7 @@ fn move_n(
     n: usize,
 ) {
     for _ in 0..n {
-        textarea.move_cursor(cursor_move.clone());
+        textarea.move_cursor(cursor_move);
     }
 }
 
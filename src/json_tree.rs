@@ -0,0 +1,418 @@
+use crate::notebook::mime::MimeBundle;
+use crate::notebook::model::CellOutput;
+use crate::ui::html;
+use crate::ui::image::has_renderable_image;
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// Containers shallower than this start expanded.
+const DEFAULT_EXPANDED_DEPTH: usize = 2;
+
+/// Rows a tree takes in its cell at most; taller trees scroll with the cursor.
+pub const MAX_TREE_HEIGHT: usize = 30;
+
+/// One step from a container to an entry.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// A line of the tree: a value and where it sits.
+#[derive(Debug)]
+pub struct JsonRow<'a> {
+    pub path: Vec<PathSegment>,
+    pub value: &'a Value,
+    /// The row is an object or array showing its entries below it
+    pub expanded: bool,
+}
+
+/// The JSON an output shows as a tree: `application/json`, or an
+/// `application/vnd.*+json` payload when nothing better can be shown (no
+/// image, no readable HTML and no text/plain beyond the `<... object>` repr).
+pub fn json_payload(data: &MimeBundle) -> Option<&Value> {
+    data.get(json_mime(data)?)
+}
+
+/// The MIME type of `json_payload`.
+pub fn json_mime(data: &MimeBundle) -> Option<&str> {
+    if data.contains("application/json") {
+        return Some("application/json");
+    }
+    let (mime, _) = data
+        .iter()
+        .find(|(mime, _)| mime.starts_with("application/vnd.") && mime.ends_with("+json"))?;
+    let has_text = data
+        .text("text/plain")
+        .is_some_and(|text| !html::is_placeholder_repr(text));
+    let has_html = html::displayed_blocks(data).is_some_and(|blocks| !blocks.is_empty());
+    (!has_renderable_image(data) && !has_text && !has_html).then_some(mime)
+}
+
+/// The JSON tree an output shows, if any (see `json_payload`).
+pub fn output_json(output: &CellOutput) -> Option<&Value> {
+    match output {
+        CellOutput::ExecuteResult { data, .. } | CellOutput::DisplayData { data, .. } => {
+            json_payload(data)
+        }
+        _ => None,
+    }
+}
+
+fn is_container(value: &Value) -> bool {
+    matches!(value, Value::Object(_) | Value::Array(_))
+}
+
+/// The entries of a container with their path segments.
+fn children(value: &Value) -> Vec<(PathSegment, &Value)> {
+    match value {
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| (PathSegment::Key(key.clone()), value))
+            .collect(),
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, value)| (PathSegment::Index(i), value))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Format a path as Python/JavaScript subscripts (`["items"][0]["name"]`),
+/// ready to paste after the variable holding the JSON.
+pub fn format_path(path: &[PathSegment]) -> String {
+    path.iter()
+        .map(|segment| match segment {
+            PathSegment::Key(key) => format!("[{}]", Value::String(key.clone())),
+            PathSegment::Index(i) => format!("[{}]", i),
+        })
+        .collect()
+}
+
+/// Whether the row's key or (scalar) value contains `query`, ignoring case.
+pub fn row_matches(path: &[PathSegment], value: &Value, query: &str) -> bool {
+    let query = query.to_lowercase();
+    let key_matches = match path.last() {
+        Some(PathSegment::Key(key)) => key.to_lowercase().contains(&query),
+        _ => false,
+    };
+    let value_matches = match value {
+        Value::String(s) => s.to_lowercase().contains(&query),
+        Value::Object(_) | Value::Array(_) => false,
+        scalar => scalar.to_string().contains(&query),
+    };
+    key_matches || value_matches
+}
+
+/// Expansion, cursor and search state of one output's tree.
+#[derive(Debug, Default)]
+pub struct JsonView {
+    /// Containers whose expansion differs from the default
+    toggled: HashSet<Vec<PathSegment>>,
+    /// Index of the selected row
+    pub cursor: usize,
+    /// First row shown when the tree is taller than `MAX_TREE_HEIGHT`
+    pub scroll: usize,
+    /// Last search, highlighted and repeated with n / N
+    pub query: Option<String>,
+}
+
+impl JsonView {
+    pub fn is_expanded(&self, path: &[PathSegment]) -> bool {
+        (path.len() < DEFAULT_EXPANDED_DEPTH) != self.toggled.contains(path)
+    }
+
+    fn set_expanded(&mut self, path: &[PathSegment], expanded: bool) {
+        if self.is_expanded(path) != expanded && !self.toggled.remove(path) {
+            self.toggled.insert(path.to_vec());
+        }
+    }
+
+    /// The visible rows: the root, then the entries of every expanded container.
+    pub fn rows<'a>(&self, root: &'a Value) -> Vec<JsonRow<'a>> {
+        let mut rows = Vec::new();
+        self.push_rows(root, &mut Vec::new(), &mut rows);
+        rows
+    }
+
+    fn push_rows<'a>(
+        &self,
+        value: &'a Value,
+        path: &mut Vec<PathSegment>,
+        rows: &mut Vec<JsonRow<'a>>,
+    ) {
+        let expanded = is_container(value) && self.is_expanded(path);
+        rows.push(JsonRow {
+            path: path.clone(),
+            value,
+            expanded,
+        });
+        if expanded {
+            for (segment, child) in children(value) {
+                path.push(segment);
+                self.push_rows(child, path, rows);
+                path.pop();
+            }
+        }
+    }
+
+    /// Rows the tree takes in its cell.
+    pub fn height(&self, root: &Value) -> usize {
+        self.rows(root).len().min(MAX_TREE_HEIGHT)
+    }
+
+    /// Clamp the cursor to the rows and scroll it into view.
+    fn settle(&mut self, row_count: usize) {
+        self.cursor = self.cursor.min(row_count.saturating_sub(1));
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor >= self.scroll + MAX_TREE_HEIGHT {
+            self.scroll = self.cursor + 1 - MAX_TREE_HEIGHT;
+        }
+        self.scroll = self.scroll.min(row_count.saturating_sub(MAX_TREE_HEIGHT));
+    }
+
+    /// Path of the selected row.
+    pub fn cursor_path(&self, root: &Value) -> Vec<PathSegment> {
+        self.rows(root)
+            .into_iter()
+            .nth(self.cursor)
+            .map(|row| row.path)
+            .unwrap_or_default()
+    }
+
+    pub fn move_cursor(&mut self, root: &Value, delta: isize) {
+        self.cursor = self.cursor.saturating_add_signed(delta);
+        self.settle(self.rows(root).len());
+    }
+
+    pub fn move_to_top(&mut self) {
+        self.cursor = 0;
+        self.scroll = 0;
+    }
+
+    pub fn move_to_bottom(&mut self, root: &Value) {
+        self.cursor = usize::MAX;
+        self.settle(self.rows(root).len());
+    }
+
+    /// Expand the selected container, or step into it if already expanded.
+    pub fn expand(&mut self, root: &Value) {
+        let rows = self.rows(root);
+        let Some(row) = rows.get(self.cursor) else {
+            return;
+        };
+        if row.expanded {
+            if rows
+                .get(self.cursor + 1)
+                .is_some_and(|next| next.path.len() > row.path.len())
+            {
+                self.cursor += 1;
+            }
+        } else if is_container(row.value) {
+            let path = row.path.clone();
+            self.set_expanded(&path, true);
+        }
+        self.settle(self.rows(root).len());
+    }
+
+    /// Collapse the selected container, or step out to its parent.
+    pub fn collapse(&mut self, root: &Value) {
+        let rows = self.rows(root);
+        let Some(row) = rows.get(self.cursor) else {
+            return;
+        };
+        if row.expanded {
+            let path = row.path.clone();
+            self.set_expanded(&path, false);
+        } else if let Some(parent) = row.path.split_last().map(|(_, parent)| parent) {
+            self.cursor = rows
+                .iter()
+                .position(|r| r.path == parent)
+                .unwrap_or(self.cursor);
+        }
+        self.settle(self.rows(root).len());
+    }
+
+    pub fn toggle(&mut self, root: &Value) {
+        let rows = self.rows(root);
+        if let Some(row) = rows.get(self.cursor).filter(|row| is_container(row.value)) {
+            let path = row.path.clone();
+            let expanded = row.expanded;
+            self.set_expanded(&path, !expanded);
+        }
+        self.settle(self.rows(root).len());
+    }
+
+    /// Expand or collapse the selected container and everything inside it.
+    pub fn set_subtree_expanded(&mut self, root: &Value, expanded: bool) {
+        let path = self.cursor_path(root);
+        if let Some(value) = lookup(root, &path) {
+            self.set_recursive(value, &mut path.clone(), expanded);
+        }
+        self.settle(self.rows(root).len());
+    }
+
+    fn set_recursive(&mut self, value: &Value, path: &mut Vec<PathSegment>, expanded: bool) {
+        if !is_container(value) {
+            return;
+        }
+        self.set_expanded(path, expanded);
+        for (segment, child) in children(value) {
+            path.push(segment);
+            self.set_recursive(child, path, expanded);
+            path.pop();
+        }
+    }
+
+    /// Move to the next (or previous) row matching `query` in the whole
+    /// tree, wrapping around and expanding its parents. Returns false if
+    /// nothing matches.
+    pub fn find(&mut self, root: &Value, query: &str, backwards: bool) -> bool {
+        let mut all = Vec::new();
+        collect_paths(root, &mut Vec::new(), &mut all);
+        let current = self.cursor_path(root);
+        let start = all
+            .iter()
+            .position(|(path, _)| *path == current)
+            .unwrap_or(0);
+
+        let count = all.len();
+        let target = (1..=count)
+            .map(|step| {
+                if backwards {
+                    (start + count - step) % count
+                } else {
+                    (start + step) % count
+                }
+            })
+            .find(|&i| row_matches(&all[i].0, all[i].1, query));
+        let Some(target) = target else {
+            return false;
+        };
+        let path = all[target].0.clone();
+        for depth in 0..path.len() {
+            self.set_expanded(&path[..depth], true);
+        }
+        let rows = self.rows(root);
+        self.cursor = rows.iter().position(|r| r.path == path).unwrap_or(0);
+        self.settle(rows.len());
+        true
+    }
+}
+
+/// Every path in the tree in display order, expanded or not.
+fn collect_paths<'a>(
+    value: &'a Value,
+    path: &mut Vec<PathSegment>,
+    all: &mut Vec<(Vec<PathSegment>, &'a Value)>,
+) {
+    all.push((path.clone(), value));
+    for (segment, child) in children(value) {
+        path.push(segment);
+        collect_paths(child, path, all);
+        path.pop();
+    }
+}
+
+/// The value at `path`, if it exists.
+pub fn lookup<'a>(root: &'a Value, path: &[PathSegment]) -> Option<&'a Value> {
+    path.iter().try_fold(root, |value, segment| match segment {
+        PathSegment::Key(key) => value.get(key),
+        PathSegment::Index(i) => value.get(i),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn paths(view: &JsonView, root: &Value) -> Vec<String> {
+        view.rows(root)
+            .iter()
+            .map(|row| format_path(&row.path))
+            .collect()
+    }
+
+    #[test]
+    fn test_rows_expand_and_collapse() {
+        let root = json!({"a": {"b": {"c": 1}}, "list": [true, null]});
+        let mut view = JsonView::default();
+        assert_eq!(
+            paths(&view, &root),
+            [
+                "",
+                r#"["a"]"#,
+                r#"["a"]["b"]"#,
+                r#"["list"]"#,
+                r#"["list"][0]"#,
+                r#"["list"][1]"#
+            ]
+        );
+
+        // l on a collapsed container expands it, then steps into it
+        view.cursor = 2;
+        view.expand(&root);
+        assert!(paths(&view, &root).contains(&r#"["a"]["b"]["c"]"#.to_string()));
+        view.expand(&root);
+        assert_eq!(view.cursor, 3);
+
+        // h on a leaf goes to the parent, then collapses it
+        view.collapse(&root);
+        assert_eq!(view.cursor, 2);
+        view.collapse(&root);
+        assert_eq!(view.rows(&root).len(), 6);
+
+        view.cursor = 0;
+        view.set_subtree_expanded(&root, false);
+        assert_eq!(paths(&view, &root), [""]);
+        view.set_subtree_expanded(&root, true);
+        assert_eq!(view.rows(&root).len(), 7);
+    }
+
+    #[test]
+    fn test_find_expands_to_match_and_wraps() {
+        let root = json!({"a": {"deep": {"name": "Needle"}}, "needle": 1});
+        let mut view = JsonView::default();
+        assert!(view.find(&root, "needle", false));
+        assert_eq!(
+            format_path(&view.cursor_path(&root)),
+            r#"["a"]["deep"]["name"]"#
+        );
+        assert!(view.find(&root, "needle", false));
+        assert_eq!(format_path(&view.cursor_path(&root)), r#"["needle"]"#);
+        assert!(view.find(&root, "needle", false));
+        assert_eq!(
+            format_path(&view.cursor_path(&root)),
+            r#"["a"]["deep"]["name"]"#
+        );
+        assert!(view.find(&root, "NEEDLE", true));
+        assert_eq!(format_path(&view.cursor_path(&root)), r#"["needle"]"#);
+        assert!(!view.find(&root, "missing", false));
+    }
+
+    #[test]
+    fn test_json_payload_prefers_better_renderers() {
+        let bundle =
+            |entries: Value| MimeBundle::from_media(&serde_json::from_value(entries).unwrap());
+        let plain = bundle(json!({
+            "application/json": {"a": 1},
+            "text/plain": "<IPython.core.display.JSON object>",
+        }));
+        assert_eq!(json_payload(&plain), Some(&json!({"a": 1})));
+
+        let vega = bundle(json!({
+            "application/vnd.vegalite.v4+json": {"mark": "bar"},
+            "text/plain": "<VegaLite 4 object>\n\nIf you see this message, ...",
+        }));
+        assert_eq!(json_payload(&vega), Some(&json!({"mark": "bar"})));
+
+        let widget = bundle(json!({
+            "application/vnd.jupyter.widget-view+json": {"model_id": "abc"},
+            "text/plain": "IntSlider(value=0)",
+        }));
+        assert_eq!(json_payload(&widget), None);
+    }
+}
@@ -0,0 +1,277 @@
+use crate::kernel::client::KernelMessage;
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use jupyter_protocol::{
+    CompleteReply, ConnectionInfo, ExpressionResult, HistoryReply, InspectReply, IsCompleteReply,
+    JupyterMessage, JupyterMessageContent,
+};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+/// How long a request waits for its shell reply.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A reply still on its way from the kernel; await it off the event loop.
+pub type PendingReply<T> = Pin<Box<dyn Future<Output = Result<T>> + Send>>;
+
+/// Shell replies awaited by a request, keyed by the request's msg_id.
+/// Filled by the connection's reader task.
+pub type PendingReplies = Arc<Mutex<HashMap<String, oneshot::Sender<JupyterMessage>>>>;
+
+/// Register a waiter for the reply to `msg_id`. Call before sending the
+/// request so a fast reply can't slip past.
+pub fn expect_reply(
+    pending: &PendingReplies,
+    msg_id: &str,
+    reply_type: &'static str,
+) -> PendingReply<JupyterMessageContent> {
+    let (waiter, reply) = oneshot::channel();
+    pending.lock().unwrap().insert(msg_id.to_string(), waiter);
+    let pending = pending.clone();
+    let msg_id = msg_id.to_string();
+    Box::pin(async move {
+        let reply = tokio::time::timeout(REPLY_TIMEOUT, reply).await;
+        pending.lock().unwrap().remove(&msg_id);
+        match reply {
+            Ok(Ok(msg)) => Ok(msg.content),
+            Ok(Err(_)) => bail!("Kernel connection closed waiting for {}", reply_type),
+            Err(_) => bail!("Timeout waiting for {}", reply_type),
+        }
+    })
+}
+
+/// The execute_reply `user_expressions` results as JSON text, the form
+/// `variables` parses them from.
+pub fn expression_texts(
+    results: Option<HashMap<String, ExpressionResult>>,
+) -> HashMap<String, String> {
+    results
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(name, result)| Some((name, serde_json::to_string(&result).ok()?)))
+        .collect()
+}
+
+/// Where the kernel comes from, as chosen on the command line.
+pub enum KernelSource {
+    /// Start a local kernel process from its kernelspec
+    Local,
+    /// Attach to a running local kernel via its connection file (`--existing`)
+    Existing(PathBuf),
+    /// Start a kernel on a Jupyter Server (`--server`; token from `$JUPYTER_TOKEN` or `--token-file`)
+    Server { url: String, token: Option<String> },
+}
+
+/// A kernelspec a backend can start, for the kernel picker.
+#[derive(Debug, Clone)]
+pub struct KernelspecInfo {
+    pub name: String,
+    pub display_name: String,
+    pub language: Option<String>,
+    /// Where the kernelspec lives (its directory, or the server URL)
+    pub location: String,
+}
+
+/// Lifecycle of a kernel, independent of the transport used to reach it.
+///
+/// Implemented by `KernelManager` (local ZMQ kernels) and `ServerKernel`
+/// (kernels hosted by a Jupyter Server).
+#[async_trait]
+pub trait KernelBackend: Send {
+    /// Open the kernel's channels. Messages from the kernel arrive on the receiver.
+    async fn connect(
+        &self,
+    ) -> Result<(
+        Box<dyn KernelConnection>,
+        mpsc::UnboundedReceiver<KernelMessage>,
+    )>;
+
+    /// Restart the kernel. Existing connections should be replaced via `connect`.
+    async fn restart(&mut self) -> Result<()>;
+
+    /// Kernelspecs this backend can start.
+    async fn kernelspecs(&self) -> Result<Vec<KernelspecInfo>>;
+
+    /// Replace the kernel with a fresh one started from kernelspec `name`.
+    async fn change_kernel(&mut self, name: &str) -> Result<()>;
+
+    /// Stop the kernel (or just detach, for kernels we don't own).
+    async fn shutdown(&mut self) -> Result<()>;
+
+    /// If the kernel process is known to have exited, describe how.
+    fn exit_status(&mut self) -> Option<String>;
+
+    /// Whether the kernel was started by someone else (quitting detaches).
+    fn is_attached(&self) -> bool;
+
+    /// Human-readable description of the kernel, for status messages.
+    fn describe(&self) -> String;
+
+    /// ZMQ connection info, if the kernel is reachable over ZMQ (enables the heartbeat monitor).
+    fn connection_info(&self) -> Option<&ConnectionInfo>;
+}
+
+/// Requests the app sends to a connected kernel.
+///
+/// Implemented by `KernelClient` (ZMQ) and `ServerKernelClient` (WebSocket).
+#[async_trait]
+pub trait KernelConnection: Send {
+    /// Send an execute_request; returns its msg_id for correlating IOPub messages.
+    async fn execute(&mut self, code: &str) -> Result<String>;
+
+    /// Send a kernel_info_request.
+    async fn request_kernel_info(&mut self) -> Result<()>;
+
+    /// Answer an input_request on the stdin channel.
+    fn send_input_reply(&self, request: &JupyterMessage, value: String) -> Result<()>;
+
+    /// Ask the kernel to shut down.
+    async fn shutdown(&mut self, restart: bool) -> Result<()>;
+
+    /// Interrupt the running execution.
+    async fn interrupt(&mut self) -> Result<()>;
+
+    /// Send a complete_request. The reply arrives on the returned future.
+    async fn complete(
+        &mut self,
+        code: &str,
+        cursor_pos: usize,
+    ) -> Result<PendingReply<CompleteReply>>;
+
+    /// Send an inspect_request. The reply arrives on the returned future.
+    ///
+    /// `detail_level` 0 is IPython's `obj?`, 1 is `obj??` (includes source).
+    async fn inspect(
+        &mut self,
+        code: &str,
+        cursor_pos: usize,
+        detail_level: usize,
+    ) -> Result<PendingReply<InspectReply>>;
+
+    /// Send a history_request for the last `n` inputs across sessions.
+    /// The reply arrives on the returned future.
+    async fn history(&mut self, n: usize) -> Result<PendingReply<HistoryReply>>;
+
+    /// Send an is_complete_request, asking whether `code` is ready to run.
+    /// The reply arrives on the returned future.
+    async fn is_complete(&mut self, code: &str) -> Result<PendingReply<IsCompleteReply>>;
+
+    /// Evaluate `expressions` (key -> expression) in the kernel's namespace
+    /// with a silent execute_request, leaving the execution count and history
+    /// alone. The reply maps each key to its `user_expressions` result as JSON.
+    async fn evaluate(
+        &mut self,
+        expressions: HashMap<String, String>,
+    ) -> Result<PendingReply<HashMap<String, String>>>;
+}
+
+/// A kernel that is never there: every request fails. For tests that need
+/// an `App` but no kernel.
+#[cfg(test)]
+pub struct NoKernel;
+
+#[cfg(test)]
+#[async_trait]
+impl KernelBackend for NoKernel {
+    async fn connect(
+        &self,
+    ) -> Result<(
+        Box<dyn KernelConnection>,
+        mpsc::UnboundedReceiver<KernelMessage>,
+    )> {
+        bail!("No kernel")
+    }
+
+    async fn restart(&mut self) -> Result<()> {
+        bail!("No kernel")
+    }
+
+    async fn kernelspecs(&self) -> Result<Vec<KernelspecInfo>> {
+        Ok(Vec::new())
+    }
+
+    async fn change_kernel(&mut self, _name: &str) -> Result<()> {
+        bail!("No kernel")
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn exit_status(&mut self) -> Option<String> {
+        None
+    }
+
+    fn is_attached(&self) -> bool {
+        false
+    }
+
+    fn describe(&self) -> String {
+        "no kernel".to_string()
+    }
+
+    fn connection_info(&self) -> Option<&ConnectionInfo> {
+        None
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl KernelConnection for NoKernel {
+    async fn execute(&mut self, _code: &str) -> Result<String> {
+        bail!("No kernel")
+    }
+
+    async fn request_kernel_info(&mut self) -> Result<()> {
+        bail!("No kernel")
+    }
+
+    fn send_input_reply(&self, _request: &JupyterMessage, _value: String) -> Result<()> {
+        bail!("No kernel")
+    }
+
+    async fn shutdown(&mut self, _restart: bool) -> Result<()> {
+        Ok(())
+    }
+
+    async fn interrupt(&mut self) -> Result<()> {
+        bail!("No kernel")
+    }
+
+    async fn complete(
+        &mut self,
+        _code: &str,
+        _cursor_pos: usize,
+    ) -> Result<PendingReply<CompleteReply>> {
+        bail!("No kernel")
+    }
+
+    async fn inspect(
+        &mut self,
+        _code: &str,
+        _cursor_pos: usize,
+        _detail_level: usize,
+    ) -> Result<PendingReply<InspectReply>> {
+        bail!("No kernel")
+    }
+
+    async fn history(&mut self, _n: usize) -> Result<PendingReply<HistoryReply>> {
+        bail!("No kernel")
+    }
+
+    async fn is_complete(&mut self, _code: &str) -> Result<PendingReply<IsCompleteReply>> {
+        bail!("No kernel")
+    }
+
+    async fn evaluate(
+        &mut self,
+        _expressions: HashMap<String, String>,
+    ) -> Result<PendingReply<HashMap<String, String>>> {
+        bail!("No kernel")
+    }
+}
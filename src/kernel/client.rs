@@ -1,36 +1,48 @@
+use crate::kernel::backend::{
+    KernelConnection, PendingReplies, PendingReply, expect_reply, expression_texts,
+};
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use jupyter_protocol::{
-    CompleteReply, CompleteRequest, ConnectionInfo, ExecuteRequest, JupyterMessage,
-    JupyterMessageContent, KernelInfoRequest, ShutdownRequest,
+    CompleteReply, CompleteRequest, ConnectionInfo, ExecuteRequest, HistoryReply, HistoryRequest,
+    InputReply, InspectReply, InspectRequest, IsCompleteReply, IsCompleteRequest, JupyterMessage,
+    JupyterMessageContent, KernelInfoRequest, ReplyStatus, ShutdownRequest,
 };
 use runtimelib::{
-    ClientControlConnection, ClientShellConnection, ClientStdinConnection,
-    create_client_control_connection, create_client_iopub_connection,
-    create_client_shell_connection, create_client_stdin_connection,
+    ClientControlConnection, RuntimeError, create_client_control_connection,
+    create_client_iopub_connection, create_client_shell_connection_with_identity,
+    create_client_stdin_connection_with_identity, peer_identity_for_session,
 };
-use tokio::sync::mpsc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, oneshot};
 
 /// Messages sent from the kernel client to the application.
 #[derive(Debug)]
 pub enum KernelMessage {
     /// A message received on the IOPub channel
     IoPub(JupyterMessage),
-    /// A reply received on the shell channel
+    /// A shell reply no request was waiting for (execute_reply, kernel_info_reply)
     ShellReply(JupyterMessage),
+    /// An input_request received on the stdin channel (`input()`, `getpass`, `pdb`)
+    StdinRequest(JupyterMessage),
     /// The IOPub listener encountered an error
     IoPubError(String),
 }
 
 /// Async client for communicating with a Jupyter kernel over ZMQ.
 ///
-/// Owns the shell and control connections for sending requests.
-/// Spawns a background task to listen on IOPub and forward messages
-/// through an mpsc channel.
+/// Owns the control connection for sending requests.
+/// Spawns background tasks to listen on IOPub and stdin and forward messages
+/// through an mpsc channel, and a shell task that sends requests, hands
+/// replies to whichever request is waiting on them and forwards the rest.
 pub struct KernelClient {
-    shell: ClientShellConnection,
+    /// Outgoing shell requests, each acknowledged once sent
+    shell_tx: mpsc::UnboundedSender<(JupyterMessage, oneshot::Sender<Result<()>>)>,
+    pending: PendingReplies,
     control: ClientControlConnection,
-    #[allow(dead_code)]
-    stdin: ClientStdinConnection,
+    /// Outgoing input_reply messages, sent by the stdin task
+    stdin_tx: mpsc::UnboundedSender<JupyterMessage>,
 }
 
 impl KernelClient {
@@ -42,9 +54,18 @@ impl KernelClient {
     ) -> Result<(Self, mpsc::UnboundedReceiver<KernelMessage>)> {
         let session_id = uuid::Uuid::new_v4().to_string();
 
-        let shell = create_client_shell_connection(connection_info, &session_id)
-            .await
-            .context("Failed to connect to shell channel")?;
+        // The kernel routes input_request to the ZMQ identity that sent the
+        // execute_request, so shell and stdin must share one identity.
+        let identity =
+            peer_identity_for_session(&session_id).context("Failed to create ZMQ identity")?;
+
+        let mut shell = create_client_shell_connection_with_identity(
+            connection_info,
+            &session_id,
+            identity.clone(),
+        )
+        .await
+        .context("Failed to connect to shell channel")?;
 
         let mut iopub = create_client_iopub_connection(connection_info, "", &session_id)
             .await
@@ -54,11 +75,82 @@ impl KernelClient {
             .await
             .context("Failed to connect to control channel")?;
 
-        let stdin = create_client_stdin_connection(connection_info, &session_id)
-            .await
-            .context("Failed to connect to stdin channel")?;
+        let mut stdin =
+            create_client_stdin_connection_with_identity(connection_info, &session_id, identity)
+                .await
+                .context("Failed to connect to stdin channel")?;
 
         let (tx, rx) = mpsc::unbounded_channel();
+        let (stdin_tx, mut stdin_rx) = mpsc::unbounded_channel::<JupyterMessage>();
+
+        // Spawn stdin listener: forwards input_requests and sends back replies.
+        // The kernel blocks until it gets a reply, so a read is never in flight
+        // when a reply is sent.
+        let stdin_events = tx.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    request = stdin.read() => match request {
+                        Ok(msg) => {
+                            if stdin_events.send(KernelMessage::StdinRequest(msg)).is_err() {
+                                break;
+                            }
+                        }
+                        Err(_) => break,
+                    },
+                    reply = stdin_rx.recv() => match reply {
+                        Some(reply) => {
+                            if stdin.send(reply).await.is_err() {
+                                break;
+                            }
+                        }
+                        None => break, // Client dropped
+                    },
+                }
+            }
+        });
+
+        // Spawn shell task: sends requests and routes replies by parent msg_id,
+        // either to a waiting request or to the app.
+        // Reads are cancel-safe, so a send can interrupt a pending read.
+        let shell_events = tx.clone();
+        let (shell_tx, mut shell_rx) =
+            mpsc::unbounded_channel::<(JupyterMessage, oneshot::Sender<Result<()>>)>();
+        let pending: PendingReplies = Arc::new(Mutex::new(HashMap::new()));
+        let waiters = pending.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    request = shell_rx.recv() => match request {
+                        Some((message, sent)) => {
+                            let _ = sent.send(shell.send(message).await.map_err(Into::into));
+                        }
+                        None => break, // Client dropped
+                    },
+                    reply = shell.read() => match reply {
+                        Ok(reply) => {
+                            let waiter = reply
+                                .parent_header
+                                .as_ref()
+                                .and_then(|h| waiters.lock().unwrap().remove(&h.msg_id));
+                            match waiter {
+                                Some(waiter) => {
+                                    let _ = waiter.send(reply);
+                                }
+                                None => {
+                                    if shell_events.send(KernelMessage::ShellReply(reply)).is_err() {
+                                        break;
+                                    }
+                                }
+                            }
+                        }
+                        // Skip replies we can't decode rather than losing the channel
+                        Err(RuntimeError::ParseError { .. } | RuntimeError::SerdeError(_)) => {}
+                        Err(_) => break,
+                    },
+                }
+            }
+        });
 
         // Spawn IOPub listener as a background task
         tokio::spawn(async move {
@@ -79,48 +171,83 @@ impl KernelClient {
 
         Ok((
             Self {
-                shell,
+                shell_tx,
+                pending,
                 control,
-                stdin,
+                stdin_tx,
             },
             rx,
         ))
     }
 
+    /// Send a message on the shell channel.
+    async fn send_shell(&self, message: JupyterMessage) -> Result<()> {
+        let (sent, ack) = oneshot::channel();
+        self.shell_tx
+            .send((message, sent))
+            .ok()
+            .context("Shell channel is closed")?;
+        ack.await.context("Shell channel is closed")?
+    }
+
+    /// Send a shell request; its reply arrives on the returned future.
+    async fn shell_request(
+        &self,
+        message: JupyterMessage,
+        reply_type: &'static str,
+    ) -> Result<PendingReply<JupyterMessageContent>> {
+        let reply = expect_reply(&self.pending, &message.header.msg_id, reply_type);
+        self.send_shell(message)
+            .await
+            .with_context(|| format!("Failed to send request awaiting {}", reply_type))?;
+        Ok(reply)
+    }
+}
+
+#[async_trait]
+impl KernelConnection for KernelClient {
     /// Send an execute_request to the kernel.
     /// Returns the msg_id of the sent message for correlating IOPub responses.
-    pub async fn execute(&mut self, code: &str) -> Result<String> {
-        let request = ExecuteRequest::new(code.to_string());
+    async fn execute(&mut self, code: &str) -> Result<String> {
+        let request = ExecuteRequest {
+            allow_stdin: true,
+            ..ExecuteRequest::new(code.to_string())
+        };
         let message: JupyterMessage = request.into();
         let msg_id = message.header.msg_id.clone();
-        self.shell
-            .send(message)
+        self.send_shell(message)
             .await
             .context("Failed to send execute request")?;
         Ok(msg_id)
     }
 
     /// Send a kernel_info_request.
-    pub async fn request_kernel_info(&mut self) -> Result<()> {
+    async fn request_kernel_info(&mut self) -> Result<()> {
         let request = KernelInfoRequest {};
         let message: JupyterMessage = request.into();
-        self.shell
-            .send(message)
+        self.send_shell(message)
             .await
             .context("Failed to send kernel_info_request")?;
         Ok(())
     }
 
-    /// Read a reply from the shell channel (blocking until one arrives).
-    pub async fn read_shell_reply(&mut self) -> Result<JupyterMessage> {
-        self.shell
-            .read()
-            .await
-            .context("Failed to read shell reply")
+    /// Answer an input_request on the stdin channel.
+    fn send_input_reply(&self, request: &JupyterMessage, value: String) -> Result<()> {
+        let reply = InputReply {
+            value,
+            status: ReplyStatus::Ok,
+            error: None,
+        }
+        .as_child_of(request);
+        self.stdin_tx
+            .send(reply)
+            .ok()
+            .context("Stdin channel is closed")?;
+        Ok(())
     }
 
     /// Send a shutdown request on the control channel.
-    pub async fn shutdown(&mut self, restart: bool) -> Result<()> {
+    async fn shutdown(&mut self, restart: bool) -> Result<()> {
         let request = ShutdownRequest { restart };
         let message: JupyterMessage = request.into();
         self.control
@@ -131,7 +258,7 @@ impl KernelClient {
     }
 
     /// Send an interrupt request on the control channel.
-    pub async fn interrupt(&mut self) -> Result<()> {
+    async fn interrupt(&mut self) -> Result<()> {
         let request = jupyter_protocol::InterruptRequest {};
         let message: JupyterMessage = request.into();
         self.control
@@ -141,52 +268,103 @@ impl KernelClient {
         Ok(())
     }
 
-    /// Send a complete_request and read back the reply.
-    /// Returns the CompleteReply with match suggestions, or an error on timeout.
-    pub async fn complete(&mut self, code: &str, cursor_pos: usize) -> Result<CompleteReply> {
+    /// Send a complete_request.
+    /// The returned future yields the CompleteReply, or an error on timeout.
+    async fn complete(
+        &mut self,
+        code: &str,
+        cursor_pos: usize,
+    ) -> Result<PendingReply<CompleteReply>> {
         let request = CompleteRequest {
             code: code.to_string(),
             cursor_pos,
         };
-        let message: JupyterMessage = request.into();
-        let msg_id = message.header.msg_id.clone();
-        self.shell
-            .send(message)
-            .await
-            .context("Failed to send complete request")?;
-
-        // Read shell replies until we get our complete_reply (with timeout)
-        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(2);
-        loop {
-            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
-            if remaining.is_zero() {
-                anyhow::bail!("Timeout waiting for complete_reply");
+        let reply = self.shell_request(request.into(), "complete_reply").await?;
+        Ok(Box::pin(async move {
+            match reply.await? {
+                JupyterMessageContent::CompleteReply(reply) => Ok(reply),
+                other => anyhow::bail!("Expected complete_reply, got {}", other.message_type()),
             }
+        }))
+    }
 
-            match tokio::time::timeout(remaining, self.shell.read()).await {
-                Ok(Ok(reply)) => {
-                    // Check if this is our complete_reply
-                    let is_ours = reply
-                        .parent_header
-                        .as_ref()
-                        .map(|h| h.msg_id == msg_id)
-                        .unwrap_or(false);
-
-                    if is_ours {
-                        if let JupyterMessageContent::CompleteReply(complete_reply) = reply.content
-                        {
-                            return Ok(complete_reply);
-                        }
-                    }
-                    // Not our reply (e.g., an execute_reply); discard and keep reading
-                }
-                Ok(Err(e)) => {
-                    anyhow::bail!("Shell read error during completion: {}", e);
-                }
-                Err(_) => {
-                    anyhow::bail!("Timeout waiting for complete_reply");
+    /// Send an inspect_request.
+    /// The returned future yields the InspectReply, or an error on timeout.
+    async fn inspect(
+        &mut self,
+        code: &str,
+        cursor_pos: usize,
+        detail_level: usize,
+    ) -> Result<PendingReply<InspectReply>> {
+        let request = InspectRequest {
+            code: code.to_string(),
+            cursor_pos,
+            detail_level: Some(detail_level),
+        };
+        let reply = self.shell_request(request.into(), "inspect_reply").await?;
+        Ok(Box::pin(async move {
+            match reply.await? {
+                JupyterMessageContent::InspectReply(reply) => Ok(reply),
+                other => anyhow::bail!("Expected inspect_reply, got {}", other.message_type()),
+            }
+        }))
+    }
+
+    /// Send a history_request for the tail of the kernel's input history.
+    /// The returned future yields the HistoryReply, or an error on timeout.
+    async fn history(&mut self, n: usize) -> Result<PendingReply<HistoryReply>> {
+        let request = HistoryRequest::Tail {
+            n: n as i32,
+            output: false,
+            raw: true,
+        };
+        let reply = self.shell_request(request.into(), "history_reply").await?;
+        Ok(Box::pin(async move {
+            match reply.await? {
+                JupyterMessageContent::HistoryReply(reply) => Ok(reply),
+                other => anyhow::bail!("Expected history_reply, got {}", other.message_type()),
+            }
+        }))
+    }
+
+    /// Send an is_complete_request.
+    /// The returned future yields the IsCompleteReply, or an error on timeout.
+    async fn is_complete(&mut self, code: &str) -> Result<PendingReply<IsCompleteReply>> {
+        let request = IsCompleteRequest {
+            code: code.to_string(),
+        };
+        let reply = self
+            .shell_request(request.into(), "is_complete_reply")
+            .await?;
+        Ok(Box::pin(async move {
+            match reply.await? {
+                JupyterMessageContent::IsCompleteReply(reply) => Ok(reply),
+                other => anyhow::bail!("Expected is_complete_reply, got {}", other.message_type()),
+            }
+        }))
+    }
+
+    /// Send a silent execute_request with no code, only `user_expressions`.
+    /// The returned future yields the expression results, or an error on timeout.
+    async fn evaluate(
+        &mut self,
+        expressions: HashMap<String, String>,
+    ) -> Result<PendingReply<HashMap<String, String>>> {
+        let request = ExecuteRequest {
+            silent: true,
+            store_history: false,
+            user_expressions: Some(expressions),
+            stop_on_error: false,
+            ..ExecuteRequest::new(String::new())
+        };
+        let reply = self.shell_request(request.into(), "execute_reply").await?;
+        Ok(Box::pin(async move {
+            match reply.await? {
+                JupyterMessageContent::ExecuteReply(reply) => {
+                    Ok(expression_texts(reply.user_expressions))
                 }
+                other => anyhow::bail!("Expected execute_reply, got {}", other.message_type()),
             }
-        }
+        }))
     }
 }
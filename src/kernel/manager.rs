@@ -1,17 +1,23 @@
+use crate::kernel::backend::{KernelBackend, KernelConnection, KernelspecInfo};
+use crate::kernel::client::{KernelClient, KernelMessage};
 use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
 use jupyter_protocol::ConnectionInfo;
 use runtimelib::{KernelspecDir, list_kernelspecs, peek_ports, read_kernelspec_jsons};
 use std::net::IpAddr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tokio::process::Child;
+use tokio::sync::mpsc;
 
 /// Manages the lifecycle of a Jupyter kernel subprocess.
 ///
 /// Handles kernelspec discovery, connection file generation,
-/// process spawning, and graceful shutdown.
+/// process spawning, and graceful shutdown. A manager can also be
+/// attached to a kernel started elsewhere (`--existing`), in which
+/// case it never kills the kernel or deletes its connection file.
 pub struct KernelManager {
-    /// The spawned kernel process
-    process: Child,
+    /// The spawned kernel process (None when attached to an existing kernel)
+    process: Option<Child>,
     /// Connection info for communicating with the kernel
     connection_info: ConnectionInfo,
     /// Path to the connection file on disk
@@ -81,33 +87,57 @@ impl KernelManager {
         tokio::time::sleep(std::time::Duration::from_millis(500)).await;
 
         Ok(Self {
-            process,
+            process: Some(process),
             connection_info,
             connection_file_path,
         })
     }
 
-    /// Returns a reference to the connection info for this kernel.
-    pub fn connection_info(&self) -> &ConnectionInfo {
-        &self.connection_info
-    }
-
-    /// Attempt graceful shutdown, then force-kill if needed.
-    pub async fn shutdown(&mut self) -> Result<()> {
-        // Try SIGTERM first (kill_on_drop handles this, but let's be explicit)
-        let _ = self.process.kill().await;
+    /// Attach to a kernel started elsewhere (e.g. `jupyter console`), described
+    /// by its connection file. Bare file names are looked up in the Jupyter
+    /// runtime directory, like `jupyter console --existing`.
+    pub async fn attach(connection_file: &Path) -> Result<Self> {
+        let connection_file_path = resolve_connection_file(connection_file);
+        let contents = tokio::fs::read_to_string(&connection_file_path)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to read connection file {}",
+                    connection_file_path.display()
+                )
+            })?;
+        let connection_info: ConnectionInfo =
+            serde_json::from_str(&contents).context("Failed to parse connection file")?;
 
-        // Clean up the connection file
-        let _ = tokio::fs::remove_file(&self.connection_file_path).await;
+        Ok(Self {
+            process: None,
+            connection_info,
+            connection_file_path,
+        })
+    }
+}
 
-        Ok(())
+#[async_trait]
+impl KernelBackend for KernelManager {
+    async fn connect(
+        &self,
+    ) -> Result<(
+        Box<dyn KernelConnection>,
+        mpsc::UnboundedReceiver<KernelMessage>,
+    )> {
+        let (client, rx) = KernelClient::connect(&self.connection_info).await?;
+        Ok((Box::new(client), rx))
     }
 
     /// Restart the kernel: shut down the current process, start a new one
     /// using the same kernelspec and connection info.
-    pub async fn restart(&mut self) -> Result<()> {
+    async fn restart(&mut self) -> Result<()> {
+        let Some(process) = self.process.as_mut() else {
+            bail!("Cannot restart a kernel that was not started by jupyter_tui");
+        };
+
         // Kill the existing process
-        let _ = self.process.kill().await;
+        let _ = process.kill().await;
 
         // Determine kernel name
         let kernel_name = self
@@ -131,10 +161,11 @@ impl KernelManager {
             Some(std::process::Stdio::piped()),
         )?;
 
-        self.process = cmd
-            .kill_on_drop(true)
-            .spawn()
-            .context("Failed to spawn kernel process")?;
+        self.process = Some(
+            cmd.kill_on_drop(true)
+                .spawn()
+                .context("Failed to spawn kernel process")?,
+        );
 
         // Give the kernel a moment to start
         tokio::time::sleep(std::time::Duration::from_millis(500)).await;
@@ -142,17 +173,101 @@ impl KernelManager {
         Ok(())
     }
 
-    /// Returns available kernelspec names for display/selection.
-    pub async fn available_kernels() -> Result<Vec<String>> {
+    /// Kernelspecs installed locally (also listed when attached, though
+    /// switching is refused then).
+    async fn kernelspecs(&self) -> Result<Vec<KernelspecInfo>> {
         let specs = discover_kernelspecs().await;
-        Ok(specs.into_iter().map(|s| s.kernel_name).collect())
+        Ok(specs
+            .into_iter()
+            .map(|s| KernelspecInfo {
+                display_name: s.kernelspec.display_name,
+                language: Some(s.kernelspec.language),
+                location: s.path.display().to_string(),
+                name: s.kernel_name,
+            })
+            .collect())
+    }
+
+    /// Kill the current kernel and start kernelspec `name` on the same ports.
+    async fn change_kernel(&mut self, name: &str) -> Result<()> {
+        if self.is_attached() {
+            bail!("Cannot change a kernel that was not started by jupyter_tui");
+        }
+        // Fail before touching the running kernel if the spec is missing
+        find_kernelspec(&discover_kernelspecs().await, name)?;
+
+        self.connection_info.kernel_name = Some(name.to_string());
+        self.restart().await
+    }
+
+    /// Attempt graceful shutdown, then force-kill if needed.
+    /// An attached kernel is left running (we just detach).
+    async fn shutdown(&mut self) -> Result<()> {
+        let Some(process) = self.process.as_mut() else {
+            return Ok(());
+        };
+
+        // Try SIGTERM first (kill_on_drop handles this, but let's be explicit)
+        let _ = process.kill().await;
+
+        // Clean up the connection file
+        let _ = tokio::fs::remove_file(&self.connection_file_path).await;
+
+        Ok(())
+    }
+
+    /// If the kernel process has exited, describe how (e.g. "signal: 9 (SIGKILL)").
+    /// Always None when attached; the heartbeat monitor covers that case.
+    fn exit_status(&mut self) -> Option<String> {
+        match self.process.as_mut()?.try_wait() {
+            Ok(Some(status)) => Some(status.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Whether this manager is attached to a kernel it did not start.
+    fn is_attached(&self) -> bool {
+        self.process.is_none()
+    }
+
+    fn describe(&self) -> String {
+        if self.is_attached() {
+            format!("kernel {}", self.connection_file_path.display())
+        } else {
+            let name = self
+                .connection_info
+                .kernel_name
+                .as_deref()
+                .unwrap_or("python3");
+            format!("{} kernel", name)
+        }
+    }
+
+    fn connection_info(&self) -> Option<&ConnectionInfo> {
+        Some(&self.connection_info)
     }
 }
 
 impl Drop for KernelManager {
     fn drop(&mut self) {
-        // Best-effort cleanup -- connection file removal
-        let _ = std::fs::remove_file(&self.connection_file_path);
+        // Best-effort cleanup -- connection file removal (only for kernels we own)
+        if !self.is_attached() {
+            let _ = std::fs::remove_file(&self.connection_file_path);
+        }
+    }
+}
+
+/// Resolve a connection file argument: an existing path is used as-is,
+/// otherwise the name is looked up in the Jupyter runtime directory.
+fn resolve_connection_file(path: &Path) -> PathBuf {
+    if path.exists() {
+        return path.to_path_buf();
+    }
+    let in_runtime_dir = runtimelib::dirs::runtime_dir().join(path);
+    if in_runtime_dir.exists() {
+        in_runtime_dir
+    } else {
+        path.to_path_buf()
     }
 }
 
@@ -177,26 +292,26 @@ fn find_kernelspec(kernelspecs: &[KernelspecDir], name: &str) -> Result<Kernelsp
 /// jupyter command is unavailable.
 async fn discover_kernelspecs() -> Vec<KernelspecDir> {
     // Try ask_jupyter() to get the real data dirs
-    if let Ok(paths) = runtimelib::dirs::ask_jupyter().await {
-        if let Some(data_dirs) = paths.get("data").and_then(|v| v.as_array()) {
-            let mut kernelspecs = Vec::new();
-            let mut seen = std::collections::HashSet::new();
-
-            for dir_value in data_dirs {
-                if let Some(dir_str) = dir_value.as_str() {
-                    let data_dir = PathBuf::from(dir_str);
-                    let specs = read_kernelspec_jsons(&data_dir).await;
-                    for spec in specs {
-                        if seen.insert(spec.kernel_name.clone()) {
-                            kernelspecs.push(spec);
-                        }
+    if let Ok(paths) = runtimelib::dirs::ask_jupyter().await
+        && let Some(data_dirs) = paths.get("data").and_then(|v| v.as_array())
+    {
+        let mut kernelspecs = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+
+        for dir_value in data_dirs {
+            if let Some(dir_str) = dir_value.as_str() {
+                let data_dir = PathBuf::from(dir_str);
+                let specs = read_kernelspec_jsons(&data_dir).await;
+                for spec in specs {
+                    if seen.insert(spec.kernel_name.clone()) {
+                        kernelspecs.push(spec);
                     }
                 }
             }
+        }
 
-            if !kernelspecs.is_empty() {
-                return kernelspecs;
-            }
+        if !kernelspecs.is_empty() {
+            return kernelspecs;
         }
     }
 
@@ -219,6 +334,59 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_attach_existing_leaves_kernel_alone() {
+        let connection_info = ConnectionInfo {
+            ip: "127.0.0.1".to_string(),
+            transport: jupyter_protocol::connection_info::Transport::TCP,
+            shell_port: 50001,
+            iopub_port: 50002,
+            stdin_port: 50003,
+            control_port: 50004,
+            hb_port: 50005,
+            key: "secret".to_string(),
+            signature_scheme: "hmac-sha256".to_string(),
+            kernel_name: Some("python3".to_string()),
+        };
+        let path = std::env::temp_dir().join(format!("kernel-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, serde_json::to_string(&connection_info).unwrap()).unwrap();
+
+        let mut manager = KernelManager::attach(&path)
+            .await
+            .expect("Failed to attach");
+        assert!(manager.is_attached());
+        assert_eq!(manager.connection_info(), Some(&connection_info));
+        assert_eq!(manager.exit_status(), None);
+        assert!(manager.restart().await.is_err());
+
+        // Detaching must not delete someone else's connection file
+        manager.shutdown().await.expect("Failed to detach");
+        drop(manager);
+        assert!(path.exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_exit_status_reports_dead_kernel() {
+        let mut manager = KernelManager::start(Some("python3"))
+            .await
+            .expect("Failed to start kernel");
+        assert_eq!(
+            manager.exit_status(),
+            None,
+            "Fresh kernel should be running"
+        );
+
+        manager
+            .shutdown()
+            .await
+            .expect("Failed to shut down kernel");
+        assert!(
+            manager.exit_status().is_some(),
+            "Killed kernel should report an exit status"
+        );
+    }
+
     #[tokio::test]
     async fn test_start_kernel_and_execute() {
         use crate::kernel::client::{KernelClient, KernelMessage};
@@ -230,7 +398,7 @@ mod tests {
             .expect("Failed to start kernel");
 
         // Connect
-        let (mut client, mut rx) = KernelClient::connect(manager.connection_info())
+        let (mut client, mut rx) = KernelClient::connect(manager.connection_info().unwrap())
             .await
             .expect("Failed to connect to kernel");
 
@@ -280,10 +448,11 @@ mod tests {
             .await
             .expect("Failed to execute");
 
-        // Collect output
+        // Collect output and the execute_reply (routed by the shell task)
         let mut got_stream_output = false;
+        let mut got_execute_reply = false;
         let deadline = tokio::time::Instant::now() + tokio::time::Duration::from_secs(10);
-        while tokio::time::Instant::now() < deadline {
+        while tokio::time::Instant::now() < deadline && !(got_stream_output && got_execute_reply) {
             match tokio::time::timeout(tokio::time::Duration::from_secs(5), rx.recv()).await {
                 Ok(Some(KernelMessage::IoPub(msg))) => {
                     eprintln!("Exec iopub: {:?}", std::mem::discriminant(&msg.content));
@@ -291,18 +460,76 @@ mod tests {
                         eprintln!("  Stream: {}", stream.text);
                         if stream.text.contains("hello from test") {
                             got_stream_output = true;
-                            break;
                         }
                     }
                 }
+                Ok(Some(KernelMessage::ShellReply(msg))) => {
+                    if let JupyterMessageContent::ExecuteReply(_) = &msg.content {
+                        got_execute_reply = true;
+                    }
+                }
                 _ => break,
             }
         }
 
         assert!(got_stream_output, "Did not receive expected stream output");
+        assert!(got_execute_reply, "Did not receive the execute_reply");
 
         // Shutdown
         let _ = client.shutdown(false).await;
         manager.shutdown().await.expect("Failed to shutdown");
     }
+
+    #[tokio::test]
+    async fn test_input_request_round_trip() {
+        use crate::kernel::client::{KernelClient, KernelMessage};
+        use jupyter_protocol::JupyterMessageContent;
+
+        let mut manager = KernelManager::start(Some("python3"))
+            .await
+            .expect("Failed to start kernel");
+        let (mut client, mut rx) = KernelClient::connect(manager.connection_info().unwrap())
+            .await
+            .expect("Failed to connect to kernel");
+
+        client
+            .execute("name = input('Name: '); print('hello', name)")
+            .await
+            .expect("Failed to execute");
+
+        // Answer the input_request, then wait for the printed greeting
+        let mut answered = false;
+        let mut got_greeting = false;
+        let deadline = tokio::time::Instant::now() + tokio::time::Duration::from_secs(20);
+        while tokio::time::Instant::now() < deadline {
+            match tokio::time::timeout(tokio::time::Duration::from_secs(5), rx.recv()).await {
+                Ok(Some(KernelMessage::StdinRequest(msg))) => {
+                    if let JupyterMessageContent::InputRequest(req) = &msg.content {
+                        assert_eq!(req.prompt, "Name: ");
+                        assert!(!req.password);
+                    }
+                    client
+                        .send_input_reply(&msg, "tui".to_string())
+                        .expect("Failed to send input_reply");
+                    answered = true;
+                }
+                Ok(Some(KernelMessage::IoPub(msg))) => {
+                    if let JupyterMessageContent::StreamContent(stream) = &msg.content
+                        && stream.text.contains("hello tui")
+                    {
+                        got_greeting = true;
+                        break;
+                    }
+                }
+                Ok(Some(_)) => {}
+                _ => break,
+            }
+        }
+
+        assert!(answered, "Did not receive an input_request");
+        assert!(got_greeting, "Kernel did not receive the input_reply");
+
+        let _ = client.shutdown(false).await;
+        manager.shutdown().await.expect("Failed to shutdown");
+    }
 }
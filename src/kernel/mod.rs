@@ -1,2 +1,5 @@
+pub mod backend;
 pub mod client;
 pub mod manager;
+pub mod monitor;
+pub mod server;
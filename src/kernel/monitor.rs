@@ -0,0 +1,63 @@
+use crate::event::AppEvent;
+use jupyter_protocol::ConnectionInfo;
+use runtimelib::ClientHeartbeatConnection;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Time between heartbeat pings.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(3);
+
+/// How long to wait for the kernel to echo a ping.
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Consecutive missed heartbeats before the kernel is declared dead.
+const MAX_MISSED_HEARTBEATS: u32 = 3;
+
+/// Spawn a task that pings the kernel's heartbeat channel and sends
+/// `AppEvent::KernelDied` once it stops answering.
+///
+/// `session` identifies the kernel instance so the app can ignore a report
+/// from a monitor that belonged to a kernel it has since restarted.
+pub fn spawn_heartbeat_monitor(
+    connection_info: ConnectionInfo,
+    session: u64,
+    tx: mpsc::UnboundedSender<AppEvent>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut heartbeat: Option<ClientHeartbeatConnection> = None;
+        let mut missed = 0;
+
+        loop {
+            tokio::time::sleep(HEARTBEAT_INTERVAL).await;
+
+            if heartbeat.is_none() {
+                heartbeat = runtimelib::create_client_heartbeat_connection(&connection_info)
+                    .await
+                    .ok();
+            }
+
+            let alive = match heartbeat.as_mut() {
+                Some(hb) => matches!(
+                    tokio::time::timeout(HEARTBEAT_TIMEOUT, hb.single_heartbeat()).await,
+                    Ok(Ok(()))
+                ),
+                None => false,
+            };
+
+            if alive {
+                missed = 0;
+                continue;
+            }
+
+            // A REQ socket that missed its reply can't send again; reconnect next round.
+            heartbeat = None;
+            missed += 1;
+            if missed >= MAX_MISSED_HEARTBEATS {
+                let reason = format!("no heartbeat for {} checks", MAX_MISSED_HEARTBEATS);
+                let _ = tx.send(AppEvent::KernelDied { session, reason });
+                return;
+            }
+        }
+    })
+}
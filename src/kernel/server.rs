@@ -0,0 +1,773 @@
+use crate::kernel::backend::{
+    KernelBackend, KernelConnection, KernelspecInfo, PendingReplies, PendingReply, expect_reply,
+    expression_texts,
+};
+use crate::kernel::client::KernelMessage;
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use futures::{SinkExt, StreamExt};
+use jupyter_protocol::{
+    Channel, CompleteReply, CompleteRequest, ConnectionInfo, ExecuteRequest, HistoryReply,
+    HistoryRequest, InputReply, InspectReply, InspectRequest, IsCompleteReply, IsCompleteRequest,
+    JupyterMessage, JupyterMessageContent, KernelInfoRequest, ReplyStatus, ShutdownRequest,
+};
+use reqwest::header::AUTHORIZATION;
+use reqwest::{Method, Url};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+/// REST endpoint of a Jupyter Server, with its auth token.
+#[derive(Clone)]
+struct ServerApi {
+    http: reqwest::Client,
+    /// Base URL, e.g. `https://hub.example.org/user/alice`
+    base: Url,
+    token: Option<String>,
+}
+
+impl ServerApi {
+    fn new(url: &str, token: Option<String>) -> Result<Self> {
+        let base = Url::parse(url).with_context(|| format!("Invalid server URL: {}", url))?;
+        if !matches!(base.scheme(), "http" | "https") || base.host_str().is_none() {
+            bail!("Server URL must start with http:// or https://: {}", url);
+        }
+        Ok(Self {
+            http: reqwest::Client::new(),
+            base,
+            token,
+        })
+    }
+
+    /// `path` (relative to the base path, may include a query string) as a full URL.
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base.as_str().trim_end_matches('/'), path)
+    }
+
+    /// `host:port` of the server, for messages.
+    fn location(&self) -> String {
+        format!(
+            "{}:{}",
+            self.base.host_str().unwrap_or_default(),
+            self.base.port_or_known_default().unwrap_or_default()
+        )
+    }
+
+    /// Call a REST endpoint and parse the JSON response (Null for empty bodies).
+    async fn call(&self, method: Method, path: &str, body: Option<&Value>) -> Result<Value> {
+        let mut request = self.http.request(method.clone(), self.url(path));
+        if let Some(token) = &self.token {
+            request = request.header(AUTHORIZATION, format!("token {}", token));
+        }
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("Failed to connect to {}", self.location()))?;
+        let status = response.status();
+        let body = response.bytes().await?;
+        if !status.is_success() {
+            bail!(
+                "{} {} failed: HTTP {} {}",
+                method,
+                path,
+                status.as_u16(),
+                String::from_utf8_lossy(&body).trim()
+            );
+        }
+        if body.is_empty() {
+            return Ok(Value::Null);
+        }
+        serde_json::from_slice(&body)
+            .with_context(|| format!("Invalid JSON from {} {}", method, path))
+    }
+
+    /// Open a WebSocket to `path` (relative to the base path).
+    ///
+    /// Frame and message sizes are bounded by tungstenite's default limits.
+    async fn connect_websocket(
+        &self,
+        path: &str,
+    ) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>> {
+        // http -> ws, https -> wss
+        let url = self.url(path).replacen("http", "ws", 1);
+        let mut request = url.into_client_request()?;
+        if let Some(token) = &self.token {
+            request
+                .headers_mut()
+                .insert(AUTHORIZATION, format!("token {}", token).parse()?);
+        }
+        let (socket, _) = tokio_tungstenite::connect_async(request)
+            .await
+            .with_context(|| format!("Failed to connect to {}", self.location()))?;
+        Ok(socket)
+    }
+
+    /// Start a kernel (the server's default when `name` is None);
+    /// returns its id and kernelspec name.
+    async fn create_kernel(&self, name: Option<&str>) -> Result<(String, String)> {
+        let body = match name {
+            Some(name) => serde_json::json!({ "name": name }),
+            None => serde_json::json!({}),
+        };
+        let kernel = self.call(Method::POST, "/api/kernels", Some(&body)).await?;
+
+        let kernel_id = kernel
+            .get("id")
+            .and_then(Value::as_str)
+            .context("Server did not return a kernel id")?
+            .to_string();
+        let kernel_name = kernel
+            .get("name")
+            .and_then(Value::as_str)
+            .or(name)
+            .unwrap_or("python3")
+            .to_string();
+        Ok((kernel_id, kernel_name))
+    }
+}
+
+/// A kernel hosted by a Jupyter Server (`jupyter server`, JupyterLab, JupyterHub).
+///
+/// The kernel is started and stopped through the REST API
+/// (`/api/kernels`) and its channels are multiplexed over a single
+/// WebSocket (`/api/kernels/<id>/channels`).
+pub struct ServerKernel {
+    api: ServerApi,
+    kernel_id: String,
+    kernel_name: String,
+}
+
+impl ServerKernel {
+    /// Start a new kernel on the server at `url` (e.g. `http://localhost:8888`).
+    pub async fn start(
+        url: &str,
+        token: Option<String>,
+        kernel_name: Option<&str>,
+    ) -> Result<Self> {
+        let api = ServerApi::new(url, token)?;
+        let (kernel_id, kernel_name) = api
+            .create_kernel(kernel_name)
+            .await
+            .with_context(|| format!("Failed to start kernel on {}", url))?;
+
+        Ok(Self {
+            api,
+            kernel_id,
+            kernel_name,
+        })
+    }
+
+    fn kernel_path(&self, action: &str) -> String {
+        format!("/api/kernels/{}{}", self.kernel_id, action)
+    }
+}
+
+#[async_trait]
+impl KernelBackend for ServerKernel {
+    async fn connect(
+        &self,
+    ) -> Result<(
+        Box<dyn KernelConnection>,
+        mpsc::UnboundedReceiver<KernelMessage>,
+    )> {
+        let (client, rx) =
+            ServerKernelClient::connect(self.api.clone(), self.kernel_path("")).await?;
+        Ok((Box::new(client), rx))
+    }
+
+    async fn restart(&mut self) -> Result<()> {
+        self.api
+            .call(Method::POST, &self.kernel_path("/restart"), None)
+            .await
+            .context("Failed to restart server kernel")?;
+        Ok(())
+    }
+
+    /// Kernelspecs installed on the server (`GET /api/kernelspecs`).
+    async fn kernelspecs(&self) -> Result<Vec<KernelspecInfo>> {
+        let response = self
+            .api
+            .call(Method::GET, "/api/kernelspecs", None)
+            .await
+            .context("Failed to list server kernelspecs")?;
+        let location = self.api.location();
+
+        let mut specs: Vec<KernelspecInfo> = response
+            .get("kernelspecs")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+            .map(|(name, entry)| {
+                let spec = &entry["spec"];
+                KernelspecInfo {
+                    name: name.clone(),
+                    display_name: spec["display_name"].as_str().unwrap_or(name).to_string(),
+                    language: spec["language"].as_str().map(str::to_string),
+                    location: location.clone(),
+                }
+            })
+            .collect();
+        specs.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(specs)
+    }
+
+    /// Start the new kernel before deleting the old one, so a bad name
+    /// leaves the current kernel running.
+    async fn change_kernel(&mut self, name: &str) -> Result<()> {
+        let (kernel_id, kernel_name) = self
+            .api
+            .create_kernel(Some(name))
+            .await
+            .with_context(|| format!("Failed to start {} kernel on the server", name))?;
+        let _ = self
+            .api
+            .call(Method::DELETE, &self.kernel_path(""), None)
+            .await;
+
+        self.kernel_id = kernel_id;
+        self.kernel_name = kernel_name;
+        Ok(())
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        self.api
+            .call(Method::DELETE, &self.kernel_path(""), None)
+            .await
+            .context("Failed to shut down server kernel")?;
+        Ok(())
+    }
+
+    /// The server owns the process; death is reported over IOPub instead.
+    fn exit_status(&mut self) -> Option<String> {
+        None
+    }
+
+    fn is_attached(&self) -> bool {
+        false
+    }
+
+    fn describe(&self) -> String {
+        format!("{} kernel on {}", self.kernel_name, self.api.location())
+    }
+
+    fn connection_info(&self) -> Option<&ConnectionInfo> {
+        None
+    }
+}
+
+/// Client for a server-hosted kernel's WebSocket channels.
+///
+/// Messages are sent as JSON text frames tagged with their channel.
+/// A background task reads the socket, routes IOPub and stdin messages to
+/// the app, and hands shell replies to whichever request is waiting on them.
+pub struct ServerKernelClient {
+    api: ServerApi,
+    kernel_path: String,
+    session_id: String,
+    /// Outgoing messages, written by the writer task
+    outgoing: mpsc::UnboundedSender<Message>,
+    pending: PendingReplies,
+}
+
+impl ServerKernelClient {
+    async fn connect(
+        api: ServerApi,
+        kernel_path: String,
+    ) -> Result<(Self, mpsc::UnboundedReceiver<KernelMessage>)> {
+        let session_id = uuid::Uuid::new_v4().to_string();
+        let path = format!("{}/channels?session_id={}", kernel_path, session_id);
+        let socket = api
+            .connect_websocket(&path)
+            .await
+            .context("Failed to open kernel WebSocket")?;
+        let (mut writer, mut reader) = socket.split();
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<Message>();
+        let pending: PendingReplies = Arc::new(Mutex::new(HashMap::new()));
+
+        // Writer task: owns the write half so sends never block the app
+        tokio::spawn(async move {
+            while let Some(message) = outgoing_rx.recv().await {
+                if writer.send(message).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        // Reader task: route incoming messages by channel.
+        // tungstenite answers pings itself.
+        let waiters = pending.clone();
+        tokio::spawn(async move {
+            loop {
+                let text = match reader.next().await {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None => {
+                        let _ = tx.send(KernelMessage::IoPubError(
+                            "Kernel WebSocket closed by server".to_string(),
+                        ));
+                        break;
+                    }
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => {
+                        let _ = tx.send(KernelMessage::IoPubError(e.to_string()));
+                        break;
+                    }
+                };
+
+                // Messages of unknown types (e.g. from newer kernels) are skipped
+                let Ok(msg) = serde_json::from_str::<JupyterMessage>(&text) else {
+                    continue;
+                };
+                let event = match msg.channel {
+                    Some(Channel::IOPub) => KernelMessage::IoPub(msg),
+                    Some(Channel::Stdin) => KernelMessage::StdinRequest(msg),
+                    _ => {
+                        let waiter = msg
+                            .parent_header
+                            .as_ref()
+                            .and_then(|h| waiters.lock().unwrap().remove(&h.msg_id));
+                        match waiter {
+                            Some(waiter) => {
+                                let _ = waiter.send(msg);
+                                continue;
+                            }
+                            None => KernelMessage::ShellReply(msg),
+                        }
+                    }
+                };
+                if tx.send(event).is_err() {
+                    break; // Receiver dropped, shut down
+                }
+            }
+        });
+
+        Ok((
+            Self {
+                api,
+                kernel_path,
+                session_id,
+                outgoing,
+                pending,
+            },
+            rx,
+        ))
+    }
+
+    /// Send a message on `channel`, returning its msg_id.
+    fn send(&self, mut message: JupyterMessage, channel: Channel) -> Result<String> {
+        message.header.session = self.session_id.clone();
+        message.channel = Some(channel);
+        let msg_id = message.header.msg_id.clone();
+        let payload = serde_json::to_string(&message)?;
+        self.outgoing
+            .send(Message::Text(payload.into()))
+            .ok()
+            .context("Kernel WebSocket is closed")?;
+        Ok(msg_id)
+    }
+
+    /// Send a shell request; its reply arrives on the returned future.
+    fn shell_request(
+        &self,
+        request: JupyterMessage,
+        reply_type: &'static str,
+    ) -> Result<PendingReply<JupyterMessageContent>> {
+        let reply = expect_reply(&self.pending, &request.header.msg_id, reply_type);
+        self.send(request, Channel::Shell)
+            .with_context(|| format!("Failed to send request awaiting {}", reply_type))?;
+        Ok(reply)
+    }
+}
+
+#[async_trait]
+impl KernelConnection for ServerKernelClient {
+    async fn execute(&mut self, code: &str) -> Result<String> {
+        let request = ExecuteRequest {
+            allow_stdin: true,
+            ..ExecuteRequest::new(code.to_string())
+        };
+        self.send(request.into(), Channel::Shell)
+            .context("Failed to send execute request")
+    }
+
+    async fn request_kernel_info(&mut self) -> Result<()> {
+        self.send(KernelInfoRequest {}.into(), Channel::Shell)
+            .context("Failed to send kernel_info_request")?;
+        Ok(())
+    }
+
+    fn send_input_reply(&self, request: &JupyterMessage, value: String) -> Result<()> {
+        let reply = InputReply {
+            value,
+            status: ReplyStatus::Ok,
+            error: None,
+        }
+        .as_child_of(request);
+        self.send(reply, Channel::Stdin)?;
+        Ok(())
+    }
+
+    async fn shutdown(&mut self, restart: bool) -> Result<()> {
+        self.send(ShutdownRequest { restart }.into(), Channel::Control)
+            .context("Failed to send shutdown request")?;
+        Ok(())
+    }
+
+    /// Interrupts go through the REST API, which works for every kernel
+    /// regardless of its interrupt_mode.
+    async fn interrupt(&mut self) -> Result<()> {
+        self.api
+            .call(
+                Method::POST,
+                &format!("{}/interrupt", self.kernel_path),
+                None,
+            )
+            .await
+            .context("Failed to interrupt server kernel")?;
+        Ok(())
+    }
+
+    async fn complete(
+        &mut self,
+        code: &str,
+        cursor_pos: usize,
+    ) -> Result<PendingReply<CompleteReply>> {
+        let request = CompleteRequest {
+            code: code.to_string(),
+            cursor_pos,
+        };
+        let reply = self.shell_request(request.into(), "complete_reply")?;
+        Ok(Box::pin(async move {
+            match reply.await? {
+                JupyterMessageContent::CompleteReply(reply) => Ok(reply),
+                _ => bail!("Unexpected reply to complete_request"),
+            }
+        }))
+    }
+
+    async fn inspect(
+        &mut self,
+        code: &str,
+        cursor_pos: usize,
+        detail_level: usize,
+    ) -> Result<PendingReply<InspectReply>> {
+        let request = InspectRequest {
+            code: code.to_string(),
+            cursor_pos,
+            detail_level: Some(detail_level),
+        };
+        let reply = self.shell_request(request.into(), "inspect_reply")?;
+        Ok(Box::pin(async move {
+            match reply.await? {
+                JupyterMessageContent::InspectReply(reply) => Ok(reply),
+                _ => bail!("Unexpected reply to inspect_request"),
+            }
+        }))
+    }
+
+    async fn history(&mut self, n: usize) -> Result<PendingReply<HistoryReply>> {
+        let request = HistoryRequest::Tail {
+            n: n as i32,
+            output: false,
+            raw: true,
+        };
+        let reply = self.shell_request(request.into(), "history_reply")?;
+        Ok(Box::pin(async move {
+            match reply.await? {
+                JupyterMessageContent::HistoryReply(reply) => Ok(reply),
+                _ => bail!("Unexpected reply to history_request"),
+            }
+        }))
+    }
+
+    async fn is_complete(&mut self, code: &str) -> Result<PendingReply<IsCompleteReply>> {
+        let request = IsCompleteRequest {
+            code: code.to_string(),
+        };
+        let reply = self.shell_request(request.into(), "is_complete_reply")?;
+        Ok(Box::pin(async move {
+            match reply.await? {
+                JupyterMessageContent::IsCompleteReply(reply) => Ok(reply),
+                _ => bail!("Unexpected reply to is_complete_request"),
+            }
+        }))
+    }
+
+    async fn evaluate(
+        &mut self,
+        expressions: HashMap<String, String>,
+    ) -> Result<PendingReply<HashMap<String, String>>> {
+        let request = ExecuteRequest {
+            silent: true,
+            store_history: false,
+            user_expressions: Some(expressions),
+            stop_on_error: false,
+            ..ExecuteRequest::new(String::new())
+        };
+        let reply = self.shell_request(request.into(), "execute_reply")?;
+        Ok(Box::pin(async move {
+            match reply.await? {
+                JupyterMessageContent::ExecuteReply(reply) => {
+                    Ok(expression_texts(reply.user_expressions))
+                }
+                _ => bail!("Unexpected reply to execute_request"),
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jupyter_protocol::{
+        ExecuteReply, ExecutionCount, HistoryEntry, IsCompleteReplyStatus, Media, MediaType,
+        StreamContent,
+    };
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+    use tokio_tungstenite::tungstenite::protocol::Role;
+
+    /// The stub server's answer to a kernel message: a shell reply, or
+    /// IOPub output for plain execute requests.
+    fn stub_reply(request: &JupyterMessage) -> Option<Value> {
+        let mut reply = match &request.content {
+            JupyterMessageContent::ExecuteRequest(execute)
+                if execute
+                    .user_expressions
+                    .as_ref()
+                    .is_some_and(|expressions| !expressions.is_empty()) =>
+            {
+                // Like ipykernel, each result is an object
+                let mut reply = ExecuteReply {
+                    execution_count: ExecutionCount::new(1),
+                    ..Default::default()
+                }
+                .as_child_of(request);
+                reply.channel = Some(Channel::Shell);
+                let mut payload = serde_json::to_value(&reply).unwrap();
+                payload["content"]["user_expressions"] = serde_json::json!({
+                    "x": {
+                        "status": "ok",
+                        "data": {"text/plain": "2"},
+                        "metadata": {},
+                    }
+                });
+                return Some(payload);
+            }
+            JupyterMessageContent::ExecuteRequest(_) => {
+                let mut output = StreamContent::stdout("hello\n").as_child_of(request);
+                output.channel = Some(Channel::IOPub);
+                return Some(serde_json::to_value(&output).unwrap());
+            }
+            JupyterMessageContent::CompleteRequest(_) => CompleteReply {
+                matches: vec!["print".to_string()],
+                cursor_start: 0,
+                cursor_end: 2,
+                ..Default::default()
+            }
+            .as_child_of(request),
+            JupyterMessageContent::InspectRequest(_) => InspectReply {
+                found: true,
+                data: Media::new(vec![MediaType::Plain(
+                    "Signature: print(*args)".to_string(),
+                )]),
+                ..Default::default()
+            }
+            .as_child_of(request),
+            JupyterMessageContent::HistoryRequest(_) => HistoryReply {
+                history: vec![HistoryEntry::Input(1, 1, "x = 1".to_string())],
+                ..Default::default()
+            }
+            .as_child_of(request),
+            JupyterMessageContent::IsCompleteRequest(_) => {
+                // Like ipykernel, leave out `indent` for complete code
+                let mut reply = IsCompleteReply {
+                    status: IsCompleteReplyStatus::Complete,
+                    indent: String::new(),
+                }
+                .as_child_of(request);
+                reply.channel = Some(Channel::Shell);
+                let mut payload = serde_json::to_value(&reply).unwrap();
+                payload["content"].as_object_mut().unwrap().remove("indent");
+                return Some(payload);
+            }
+            _ => return None,
+        };
+        reply.channel = Some(Channel::Shell);
+        Some(serde_json::to_value(&reply).unwrap())
+    }
+
+    /// A minimal Jupyter Server: starts one kernel, answers kernel messages
+    /// with `stub_reply`, and records every request line with its
+    /// Authorization header.
+    async fn spawn_stub_server() -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let requests = log.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let requests = requests.clone();
+                tokio::spawn(async move {
+                    let mut stream = BufReader::new(stream);
+                    let mut request_line = String::new();
+                    stream.read_line(&mut request_line).await.unwrap();
+                    let mut auth = String::new();
+                    let mut ws_key = String::new();
+                    let mut content_length = 0;
+                    loop {
+                        let mut line = String::new();
+                        stream.read_line(&mut line).await.unwrap();
+                        let line = line.trim_end();
+                        if line.is_empty() {
+                            break;
+                        }
+                        let (name, value) = line.split_once(':').unwrap();
+                        match name.to_ascii_lowercase().as_str() {
+                            "authorization" => auth = value.trim().to_string(),
+                            "sec-websocket-key" => ws_key = value.trim().to_string(),
+                            "content-length" => content_length = value.trim().parse().unwrap(),
+                            _ => {}
+                        }
+                    }
+                    let mut body = vec![0; content_length];
+                    tokio::io::AsyncReadExt::read_exact(&mut stream, &mut body)
+                        .await
+                        .unwrap();
+                    let request_line = request_line.trim_end().to_string();
+                    requests
+                        .lock()
+                        .unwrap()
+                        .push(format!("{} [{}]", request_line, auth));
+
+                    if request_line.starts_with("POST /api/kernels ") {
+                        let body = r#"{"id": "k1", "name": "python3"}"#;
+                        let response = format!(
+                            "HTTP/1.1 201 Created\r\nConnection: close\r\n\
+                             Content-Length: {}\r\n\r\n{}",
+                            body.len(),
+                            body
+                        );
+                        stream.write_all(response.as_bytes()).await.unwrap();
+                    } else if request_line.starts_with("GET /api/kernels/k1/channels") {
+                        let response = format!(
+                            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+                             Connection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                            derive_accept_key(ws_key.as_bytes())
+                        );
+                        stream.write_all(response.as_bytes()).await.unwrap();
+                        let mut socket =
+                            WebSocketStream::from_raw_socket(stream, Role::Server, None).await;
+                        while let Some(Ok(Message::Text(text))) = socket.next().await {
+                            let request: JupyterMessage = serde_json::from_str(&text).unwrap();
+                            assert!(matches!(request.channel, Some(Channel::Shell)));
+                            if let Some(reply) = stub_reply(&request) {
+                                let reply = Message::Text(reply.to_string().into());
+                                socket.send(reply).await.unwrap();
+                            }
+                        }
+                    } else {
+                        stream
+                            .write_all(
+                                b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\
+                                  Content-Length: 0\r\n\r\n",
+                            )
+                            .await
+                            .unwrap();
+                    }
+                });
+            }
+        });
+
+        (url, log)
+    }
+
+    #[test]
+    fn test_server_url() {
+        let api = ServerApi::new("https://hub.example.org/user/alice/", None).unwrap();
+        assert_eq!(
+            api.url("/api/kernels"),
+            "https://hub.example.org/user/alice/api/kernels"
+        );
+        assert_eq!(api.location(), "hub.example.org:443");
+
+        assert!(ServerApi::new("http://localhost:8888", None).is_ok());
+        assert!(ServerApi::new("localhost:8888", None).is_err());
+        assert!(ServerApi::new("ftp://example.org", None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_server_kernel_lifecycle_and_execute() {
+        let (url, log) = spawn_stub_server().await;
+
+        let mut kernel = ServerKernel::start(&url, Some("secret".to_string()), None)
+            .await
+            .expect("Failed to start server kernel");
+        assert_eq!(kernel.kernel_id, "k1");
+        assert!(kernel.connection_info().is_none());
+
+        let (mut client, mut rx) = kernel.connect().await.expect("Failed to connect");
+        let msg_id = client.execute("print('hello')").await.unwrap();
+
+        let msg = tokio::time::timeout(std::time::Duration::from_secs(5), rx.recv())
+            .await
+            .expect("Timed out waiting for output")
+            .expect("Channel closed");
+        let KernelMessage::IoPub(msg) = msg else {
+            panic!("Expected an IOPub message, got {:?}", msg);
+        };
+        assert_eq!(msg.parent_header.unwrap().msg_id, msg_id);
+        let JupyterMessageContent::StreamContent(stream) = msg.content else {
+            panic!("Expected stream output");
+        };
+        assert_eq!(stream.text, "hello\n");
+
+        let pending = client.complete("pr", 2).await.unwrap();
+        let reply = pending.await.unwrap();
+        assert_eq!(reply.matches, ["print"]);
+        assert_eq!((reply.cursor_start, reply.cursor_end), (0, 2));
+
+        let reply = client.inspect("print", 5, 0).await.unwrap().await.unwrap();
+        assert!(reply.found);
+        assert!(matches!(
+            &reply.data.content[..],
+            [MediaType::Plain(text)] if text == "Signature: print(*args)"
+        ));
+
+        let reply = client.history(100).await.unwrap().await.unwrap();
+        assert!(matches!(
+            &reply.history[..],
+            [HistoryEntry::Input(1, 1, source)] if source == "x = 1"
+        ));
+
+        let reply = client.is_complete("x = 1").await.unwrap().await.unwrap();
+        assert_eq!(reply.status, IsCompleteReplyStatus::Complete);
+        assert_eq!(reply.indent, "");
+
+        let expressions = HashMap::from([("x".to_string(), "1 + 1".to_string())]);
+        let results = client.evaluate(expressions).await.unwrap().await.unwrap();
+        let result: Value = serde_json::from_str(&results["x"]).unwrap();
+        assert_eq!(result["status"], "ok");
+        assert_eq!(result["data"]["text/plain"], "2");
+
+        kernel.shutdown().await.expect("Failed to shut down");
+
+        let log = log.lock().unwrap();
+        assert_eq!(log[0], "POST /api/kernels HTTP/1.1 [token secret]");
+        assert!(log[1].starts_with("GET /api/kernels/k1/channels?session_id="));
+        assert!(log[1].ends_with("[token secret]"));
+        assert_eq!(log[2], "DELETE /api/kernels/k1 HTTP/1.1 [token secret]");
+    }
+}
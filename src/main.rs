@@ -1,23 +1,123 @@
 mod app;
+mod console;
 mod event;
 mod input;
+mod json_tree;
 mod kernel;
 mod notebook;
 mod ui;
+mod variables;
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use crossterm::{
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
-use ratatui_image::picker::{Picker, ProtocolType};
+use kernel::backend::KernelSource;
+use ratatui_image::picker::Picker;
 use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
+use ui::image::ImageProtocol;
+
+/// Longest the main loop handles queued events before drawing.
+const MAX_BATCH_TIME: Duration = Duration::from_millis(16);
+
+/// Command line options.
+struct Args {
+    file_path: Option<String>,
+    /// Where to get the kernel (`--existing`, `--server`)
+    kernel_source: KernelSource,
+    /// Kernelspec to start instead of the notebook's (`--kernel`)
+    kernel: Option<String>,
+    auto_restart: bool,
+    /// Notify when runs take at least this long (None disables, `--notify-after 0`)
+    notify_after: Option<Duration>,
+    image_protocol: ImageProtocol,
+}
+
+fn parse_args() -> Result<Args> {
+    let mut args = Args {
+        file_path: None,
+        kernel_source: KernelSource::Local,
+        kernel: None,
+        auto_restart: false,
+        notify_after: Some(app::DEFAULT_NOTIFY_AFTER),
+        image_protocol: ImageProtocol::Auto,
+    };
+    let mut existing = None;
+    let mut server = None;
+    let mut token_file = None;
+    let mut argv = std::env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "--auto-restart" => args.auto_restart = true,
+            "--existing" => {
+                let file = argv
+                    .next()
+                    .context("--existing requires a connection file")?;
+                existing = Some(PathBuf::from(file));
+            }
+            "--notify-after" => {
+                let secs: u64 = argv
+                    .next()
+                    .context("--notify-after requires a number of seconds")?
+                    .parse()
+                    .context("--notify-after requires a number of seconds")?;
+                args.notify_after = (secs > 0).then(|| Duration::from_secs(secs));
+            }
+            "--image-protocol" => {
+                args.image_protocol = argv
+                    .next()
+                    .context(
+                        "--image-protocol requires auto, kitty, sixel, iterm2, halfblocks or none",
+                    )?
+                    .parse()?;
+            }
+            "--kernel" => {
+                args.kernel = Some(argv.next().context("--kernel requires a kernelspec name")?);
+            }
+            "--server" => {
+                server = Some(argv.next().context("--server requires a URL")?);
+            }
+            "--token-file" => {
+                let file = argv.next().context("--token-file requires a path")?;
+                token_file = Some(PathBuf::from(file));
+            }
+            flag if flag.starts_with("--") => bail!("Unknown option: {}", flag),
+            path => args.file_path = Some(path.to_string()),
+        }
+    }
+
+    args.kernel_source = match (existing, server) {
+        (Some(_), Some(_)) => bail!("--existing and --server cannot be combined"),
+        (Some(_), None) if args.kernel.is_some() => {
+            bail!("--kernel cannot be combined with --existing")
+        }
+        (Some(file), None) => KernelSource::Existing(file),
+        // The token is never taken from argv, where other users can see it in `ps`
+        (None, Some(url)) => KernelSource::Server {
+            url,
+            token: match token_file {
+                Some(file) => Some(
+                    std::fs::read_to_string(&file)
+                        .with_context(|| format!("Failed to read {}", file.display()))?
+                        .trim()
+                        .to_string(),
+                ),
+                None => std::env::var("JUPYTER_TOKEN").ok(),
+            },
+        },
+        (None, None) => KernelSource::Local,
+    };
+    Ok(args)
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Parse command line args (just a file path for now)
-    let file_path = std::env::args().nth(1);
+    // Parse command line args before touching the terminal
+    let args = parse_args()?;
 
     // Initialize terminal
     enable_raw_mode().context("Failed to enable raw mode")?;
@@ -26,17 +126,15 @@ async fn main() -> Result<()> {
 
     // Query terminal for graphics protocol support and font size.
     // Must be called after EnterAlternateScreen but before reading terminal events.
-    let mut picker = Picker::from_query_stdio().unwrap_or_else(|_| {
-        let mut p = Picker::from_fontsize((8, 16));
-        p.set_protocol_type(ProtocolType::Kitty);
-        p
-    });
-    picker.set_protocol_type(ProtocolType::Kitty);
+    let picker = ui::image::query_picker(args.image_protocol);
+    if args.image_protocol != ImageProtocol::Placeholder {
+        ui::image::preload_fonts();
+    }
 
     let mut terminal = ratatui::init();
 
     // Run the application
-    let result = run(&mut terminal, file_path.as_deref(), picker).await;
+    let result = run(&mut terminal, &args, picker).await;
 
     // Restore terminal
     disable_raw_mode().context("Failed to disable raw mode")?;
@@ -50,27 +148,43 @@ async fn main() -> Result<()> {
     result
 }
 
-async fn run(
-    terminal: &mut ratatui::DefaultTerminal,
-    file_path: Option<&str>,
-    picker: Picker,
-) -> Result<()> {
+async fn run(terminal: &mut ratatui::DefaultTerminal, args: &Args, picker: Picker) -> Result<()> {
     // Set up event channel
     let (event_tx, mut event_rx) = mpsc::unbounded_channel();
 
     // Initialize app (starts kernel, connects, loads notebook)
-    let (mut app, kernel_rx) = app::App::new(file_path, event_tx.clone(), picker).await?;
+    let (mut app, kernel_rx) = app::App::new(
+        args.file_path.as_deref(),
+        &args.kernel_source,
+        args.kernel.as_deref(),
+        event_tx.clone(),
+        picker,
+    )
+    .await?;
+    app.auto_restart = args.auto_restart;
+    app.notify_after = args.notify_after;
+    app.image_placeholders = args.image_protocol == ImageProtocol::Placeholder;
 
     // Spawn event collection loop
     tokio::spawn(event::run_event_loop(event_tx, kernel_rx));
 
-    // Initial draw
+    // Initial draw (App starts with needs_redraw set)
     app.draw(terminal)?;
 
     // Main event loop
     while !app.should_quit {
         if let Some(event) = event_rx.recv().await {
             app.handle_event(event).await?;
+            // Take whatever else has arrived (a burst of output) before
+            // drawing, so it costs one frame; a kernel that keeps the
+            // channel full still gets a frame every MAX_BATCH_TIME
+            let deadline = Instant::now() + MAX_BATCH_TIME;
+            while !app.should_quit
+                && Instant::now() < deadline
+                && let Ok(event) = event_rx.try_recv()
+            {
+                app.handle_event(event).await?;
+            }
             app.draw(terminal)?;
         } else {
             break;
@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+/// A MIME bundle (`data` of display_data / execute_result outputs).
+///
+/// Values are kept as the JSON the kernel or notebook provided, so
+/// `application/json`, plotly, vega, widget views and any other vendor type
+/// survive a load/save unchanged. Text types are stored as plain strings.
+/// Entries are ordered by MIME type so rendering and saving are deterministic
+/// (the wire format is an unordered JSON object).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MimeBundle {
+    entries: BTreeMap<String, Value>,
+}
+
+impl MimeBundle {
+    /// Build a bundle from a jupyter_protocol Media, as received from the kernel
+    /// or parsed by nbformat.
+    ///
+    /// Entries are converted one at a time, so one that can't be encoded is
+    /// skipped without losing the rest.
+    pub fn from_media(media: &jupyter_protocol::Media) -> Self {
+        // Serializing with the wire format joins multiline strings and keeps
+        // JSON / unknown types as their original values.
+        let entries = media
+            .content
+            .iter()
+            .filter_map(|entry| {
+                match serde_json::to_value(jupyter_protocol::Media::new(vec![entry.clone()])) {
+                    Ok(Value::Object(map)) => map.into_iter().next(),
+                    _ => None,
+                }
+            })
+            .collect();
+        Self { entries }
+    }
+
+    /// Convert back to a jupyter_protocol Media for saving. Entries it
+    /// can't decode (e.g. a non-string `text/plain`) are skipped one by one.
+    pub fn to_media(&self) -> jupyter_protocol::Media {
+        let content = self
+            .entries
+            .iter()
+            .filter_map(|(mime, value)| {
+                let entry = serde_json::Map::from_iter([(mime.clone(), value.clone())]);
+                serde_json::from_value::<jupyter_protocol::Media>(Value::Object(entry)).ok()
+            })
+            .flat_map(|media| media.content)
+            .collect();
+        jupyter_protocol::Media::new(content)
+    }
+
+    pub fn get(&self, mime: &str) -> Option<&Value> {
+        self.entries.get(mime)
+    }
+
+    /// The value for a text-based MIME type (text/plain, image/png base64, ...).
+    pub fn text(&self, mime: &str) -> Option<&str> {
+        self.entries.get(mime).and_then(Value::as_str)
+    }
+
+    pub fn contains(&self, mime: &str) -> bool {
+        self.entries.contains_key(mime)
+    }
+
+    /// Iterate over (MIME type, value) pairs in order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.entries
+            .iter()
+            .map(|(mime, value)| (mime.as_str(), value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vendor_and_json_types_survive() {
+        let raw = serde_json::json!({
+            "text/plain": ["line one\n", "line two"],
+            "application/json": {"a": [1, 2, {"b": null}]},
+            "application/vnd.plotly.v1+json": {"data": [], "layout": {}},
+            "application/x-custom+json": {"nested": {"ok": true}},
+        });
+        let media: jupyter_protocol::Media = serde_json::from_value(raw.clone()).unwrap();
+        let bundle = MimeBundle::from_media(&media);
+
+        assert_eq!(bundle.text("text/plain"), Some("line one\nline two"));
+        assert_eq!(
+            bundle.get("application/json"),
+            Some(&raw["application/json"])
+        );
+        assert_eq!(
+            bundle.get("application/x-custom+json"),
+            Some(&raw["application/x-custom+json"])
+        );
+
+        // And back again
+        assert_eq!(MimeBundle::from_media(&bundle.to_media()), bundle);
+    }
+
+    #[test]
+    fn test_entries_are_ordered_by_mime_type() {
+        let raw =
+            serde_json::json!({"text/plain": "x", "image/png": "AAAA", "text/html": "<b>x</b>"});
+        let media: jupyter_protocol::Media = serde_json::from_value(raw).unwrap();
+        let bundle = MimeBundle::from_media(&media);
+        let mimes: Vec<&str> = bundle.iter().map(|(mime, _)| mime).collect();
+        assert_eq!(mimes, ["image/png", "text/html", "text/plain"]);
+    }
+
+    #[test]
+    fn test_bad_entry_is_skipped_alone() {
+        let mut bundle = MimeBundle::default();
+        bundle
+            .entries
+            .insert("text/plain".to_string(), serde_json::json!("ok"));
+        bundle.entries.insert(
+            "image/png".to_string(),
+            serde_json::json!({"not": "base64"}),
+        );
+
+        let roundtrip = MimeBundle::from_media(&bundle.to_media());
+        assert_eq!(roundtrip.text("text/plain"), Some("ok"));
+        assert!(!roundtrip.contains("image/png"));
+    }
+}
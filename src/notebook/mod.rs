@@ -1 +1,3 @@
+pub mod mime;
 pub mod model;
+pub mod stream;
@@ -1,7 +1,13 @@
 use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
+use super::mime::MimeBundle;
+use super::stream::StreamText;
+
 /// Runtime representation of a notebook cell.
 #[derive(Debug, Clone)]
 pub struct Cell {
@@ -11,8 +17,19 @@ pub struct Cell {
     pub outputs: Vec<CellOutput>,
     pub execution_count: Option<usize>,
     pub execution_state: ExecutionState,
+    /// When the running execution started (kernel's execute_input); drives the
+    /// live elapsed time. Not serialized -- finished runs use metadata.execution.
+    pub run_started: Option<Instant>,
+    /// How long the last run took, for when timing isn't recorded in
+    /// metadata.execution (not serialized).
+    pub last_run: Option<Duration>,
     /// Whether a markdown cell should display rendered output (not serialized to .ipynb).
     pub rendered: bool,
+    /// Cell metadata as loaded (tags, jupyter.*, papermill/nbgrader keys, ...),
+    /// written back unchanged on save.
+    pub metadata: nbformat::v4::CellMetadata,
+    /// Markdown cell attachments (`attachment:` images), kept verbatim.
+    pub attachments: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -35,24 +52,30 @@ impl std::fmt::Display for CellType {
 #[derive(Debug, Clone, PartialEq)]
 pub enum ExecutionState {
     Idle,
+    /// Waiting in the execution queue
+    Queued,
     Running,
     Done,
     Error,
+    /// Not run because an earlier queued cell failed
+    Skipped,
 }
 
 /// A single output from a cell execution.
 #[derive(Debug, Clone)]
 pub enum CellOutput {
     /// stdout/stderr stream
-    Stream { name: String, text: String },
+    Stream { name: String, text: StreamText },
     /// Rich display data (can contain text/plain, image/png, etc.)
     DisplayData {
-        data: std::collections::HashMap<String, String>,
+        data: MimeBundle,
+        metadata: serde_json::Map<String, serde_json::Value>,
     },
     /// The return value of an expression
     ExecuteResult {
         execution_count: usize,
-        data: std::collections::HashMap<String, String>,
+        data: MimeBundle,
+        metadata: serde_json::Map<String, serde_json::Value>,
     },
     /// Error traceback
     Error {
@@ -66,10 +89,9 @@ impl CellOutput {
     /// Get image data (base64-encoded) from this output, if any.
     pub fn image_data(&self) -> Option<&str> {
         match self {
-            CellOutput::DisplayData { data } | CellOutput::ExecuteResult { data, .. } => data
-                .get("image/png")
-                .or_else(|| data.get("image/jpeg"))
-                .map(|s| s.as_str()),
+            CellOutput::DisplayData { data, .. } | CellOutput::ExecuteResult { data, .. } => {
+                data.text("image/png").or_else(|| data.text("image/jpeg"))
+            }
             _ => None,
         }
     }
@@ -86,10 +108,75 @@ pub struct Notebook {
     pub dirty: bool,
 }
 
+/// Notebook-level metadata, mirroring nbformat's so nothing is lost on save.
 #[derive(Debug, Clone)]
 pub struct NotebookMetadata {
-    pub kernel_name: Option<String>,
-    pub language: Option<String>,
+    pub kernelspec: Option<nbformat::v4::KernelSpec>,
+    pub language_info: Option<nbformat::v4::LanguageInfo>,
+    pub authors: Option<Vec<nbformat::v4::Author>>,
+    /// Keys we don't model (extensions, papermill, jupytext, ...), kept verbatim.
+    pub additional: HashMap<String, serde_json::Value>,
+}
+
+impl NotebookMetadata {
+    /// The kernelspec name (e.g. "python3"), if the notebook records one.
+    pub fn kernel_name(&self) -> Option<&str> {
+        self.kernelspec.as_ref().map(|k| k.name.as_str())
+    }
+
+    /// The notebook language, from language_info or the kernelspec.
+    pub fn language(&self) -> Option<&str> {
+        self.language_info
+            .as_ref()
+            .map(|l| l.name.as_str())
+            .or_else(|| self.kernelspec.as_ref()?.language.as_deref())
+    }
+
+    /// Record a new kernelspec (after switching kernels). language_info is
+    /// reset when the language changes, since its version and codemirror
+    /// details belong to the old kernel.
+    pub fn set_kernelspec(&mut self, name: &str, display_name: &str, language: Option<&str>) {
+        let additional = self
+            .kernelspec
+            .take()
+            .filter(|k| k.name == name)
+            .map(|k| k.additional)
+            .unwrap_or_default();
+        self.kernelspec = Some(nbformat::v4::KernelSpec {
+            display_name: display_name.to_string(),
+            name: name.to_string(),
+            language: language.map(str::to_string),
+            additional,
+        });
+
+        let same_language = self.language_info.as_ref().map(|l| l.name.as_str()) == language;
+        if !same_language {
+            self.language_info = language.map(|language| nbformat::v4::LanguageInfo {
+                name: language.to_string(),
+                version: None,
+                codemirror_mode: None,
+                additional: Default::default(),
+            });
+        }
+    }
+
+    fn from_v4(metadata: nbformat::v4::Metadata) -> Self {
+        Self {
+            kernelspec: metadata.kernelspec,
+            language_info: metadata.language_info,
+            authors: metadata.authors,
+            additional: metadata.additional,
+        }
+    }
+
+    fn to_v4(&self) -> nbformat::v4::Metadata {
+        nbformat::v4::Metadata {
+            kernelspec: self.kernelspec.clone(),
+            language_info: self.language_info.clone(),
+            authors: self.authors.clone(),
+            additional: self.additional.clone(),
+        }
+    }
 }
 
 impl Cell {
@@ -101,10 +188,15 @@ impl Cell {
             outputs: Vec::new(),
             execution_count: None,
             execution_state: ExecutionState::Idle,
+            run_started: None,
+            last_run: None,
             rendered: false,
+            metadata: empty_cell_metadata(),
+            attachments: None,
         }
     }
 
+    #[allow(dead_code)]
     pub fn new_markdown(source: &str) -> Self {
         Self {
             id: Uuid::new_v4().to_string(),
@@ -113,10 +205,15 @@ impl Cell {
             outputs: Vec::new(),
             execution_count: None,
             execution_state: ExecutionState::Idle,
+            run_started: None,
+            last_run: None,
             rendered: false,
+            metadata: empty_cell_metadata(),
+            attachments: None,
         }
     }
 
+    #[allow(dead_code)]
     pub fn new_raw(source: &str) -> Self {
         Self {
             id: Uuid::new_v4().to_string(),
@@ -125,7 +222,31 @@ impl Cell {
             outputs: Vec::new(),
             execution_count: None,
             execution_state: ExecutionState::Idle,
+            run_started: None,
+            last_run: None,
             rendered: false,
+            metadata: empty_cell_metadata(),
+            attachments: None,
+        }
+    }
+
+    /// Append text to the stream output with the given name,
+    /// or start a new stream output if there isn't one yet.
+    pub fn append_stream(&mut self, stream_name: &str, new_text: &str) {
+        let appended = self.outputs.iter_mut().any(|o| {
+            if let CellOutput::Stream { name, text } = o
+                && name == stream_name
+            {
+                text.push_str(new_text);
+                return true;
+            }
+            false
+        });
+        if !appended {
+            self.outputs.push(CellOutput::Stream {
+                name: stream_name.to_string(),
+                text: StreamText::new(new_text),
+            });
         }
     }
 
@@ -133,7 +254,52 @@ impl Cell {
     pub fn clear_outputs(&mut self) {
         self.outputs.clear();
         self.execution_state = ExecutionState::Idle;
+        self.run_started = None;
+        self.last_run = None;
+    }
+
+    /// The cell's `metadata.execution` timing record, created if missing.
+    pub fn execution_timing(&mut self) -> &mut nbformat::v4::ExecutionMetadata {
+        self.metadata
+            .execution
+            .get_or_insert_with(|| nbformat::v4::ExecutionMetadata {
+                iopub_execute_input: None,
+                iopub_status_busy: None,
+                shell_execute_reply: None,
+                shell_execute_reply_started: None,
+                iopub_status_idle: None,
+                additional: Default::default(),
+            })
     }
+
+    /// How long the cell has been running, or how long its last
+    /// execution took (from `metadata.execution` when recorded, so it
+    /// survives save/load).
+    pub fn elapsed(&self) -> Option<Duration> {
+        if let Some(started) = self.run_started {
+            return Some(started.elapsed());
+        }
+        let Some(timing) = self.metadata.execution.as_ref() else {
+            return self.last_run;
+        };
+        let start = timing
+            .iopub_execute_input
+            .as_deref()
+            .or(timing.iopub_status_busy.as_deref())?;
+        let end = timing
+            .shell_execute_reply
+            .as_deref()
+            .or(timing.iopub_status_idle.as_deref())?;
+        let start = DateTime::parse_from_rfc3339(start).ok()?;
+        let end = DateTime::parse_from_rfc3339(end).ok()?;
+        (end - start).to_std().ok()
+    }
+}
+
+/// Format a message timestamp the way Jupyter records it in metadata.execution
+/// (RFC 3339, microseconds, `Z`).
+pub fn timing_timestamp(date: &DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Micros, true)
 }
 
 impl Notebook {
@@ -142,8 +308,20 @@ impl Notebook {
         Self {
             cells: vec![Cell::new_code("")],
             metadata: NotebookMetadata {
-                kernel_name: Some("python3".to_string()),
-                language: Some("python".to_string()),
+                kernelspec: Some(nbformat::v4::KernelSpec {
+                    display_name: "Python 3".to_string(),
+                    name: "python3".to_string(),
+                    language: Some("python".to_string()),
+                    additional: Default::default(),
+                }),
+                language_info: Some(nbformat::v4::LanguageInfo {
+                    name: "python".to_string(),
+                    version: None,
+                    codemirror_mode: None,
+                    additional: Default::default(),
+                }),
+                authors: None,
+                additional: Default::default(),
             },
             file_path: None,
             dirty: false,
@@ -159,22 +337,14 @@ impl Notebook {
         let (cells, metadata) = match nb {
             nbformat::Notebook::V4(v4) => {
                 let cells = v4.cells.into_iter().map(convert_v4_cell).collect();
-                let metadata = NotebookMetadata {
-                    kernel_name: v4.metadata.kernelspec.as_ref().map(|k| k.name.clone()),
-                    language: v4.metadata.language_info.as_ref().map(|l| l.name.clone()),
-                };
-                (cells, metadata)
+                (cells, NotebookMetadata::from_v4(v4.metadata))
             }
             nbformat::Notebook::Legacy(legacy) => {
                 // Upgrade legacy notebooks to v4
                 let v4 = nbformat::upgrade_legacy_notebook(legacy)
                     .context("Failed to upgrade legacy notebook")?;
                 let cells = v4.cells.into_iter().map(convert_v4_cell).collect();
-                let metadata = NotebookMetadata {
-                    kernel_name: v4.metadata.kernelspec.as_ref().map(|k| k.name.clone()),
-                    language: v4.metadata.language_info.as_ref().map(|l| l.name.clone()),
-                };
-                (cells, metadata)
+                (cells, NotebookMetadata::from_v4(v4.metadata))
             }
         };
 
@@ -212,31 +382,22 @@ impl Notebook {
 
         nbformat::v4::Notebook {
             cells,
-            metadata: nbformat::v4::Metadata {
-                kernelspec: self.metadata.kernel_name.as_ref().map(|name| {
-                    nbformat::v4::KernelSpec {
-                        display_name: name.clone(),
-                        name: name.clone(),
-                        language: self.metadata.language.clone(),
-                        additional: Default::default(),
-                    }
-                }),
-                language_info: self.metadata.language.as_ref().map(|lang| {
-                    nbformat::v4::LanguageInfo {
-                        name: lang.clone(),
-                        version: None,
-                        codemirror_mode: None,
-                        additional: Default::default(),
-                    }
-                }),
-                authors: None,
-                additional: Default::default(),
-            },
+            metadata: self.metadata.to_v4(),
             nbformat: 4,
             nbformat_minor: 5,
         }
     }
 
+    /// Current index of the cell with the given id.
+    pub fn cell_index(&self, id: &str) -> Option<usize> {
+        self.cells.iter().position(|cell| cell.id == id)
+    }
+
+    /// The cell with the given id, wherever it has moved to.
+    pub fn cell_mut(&mut self, id: &str) -> Option<&mut Cell> {
+        self.cells.iter_mut().find(|cell| cell.id == id)
+    }
+
     /// Insert a new cell after the given index.
     pub fn insert_cell_after(&mut self, index: usize, cell: Cell) {
         let insert_at = (index + 1).min(self.cells.len());
@@ -292,10 +453,10 @@ fn convert_v4_cell(cell: nbformat::v4::Cell) -> Cell {
     match cell {
         nbformat::v4::Cell::Code {
             id,
+            metadata,
             source,
             outputs,
             execution_count,
-            ..
         } => Cell {
             id: id.to_string(),
             cell_type: CellType::Code,
@@ -303,25 +464,46 @@ fn convert_v4_cell(cell: nbformat::v4::Cell) -> Cell {
             outputs: outputs.into_iter().map(convert_v4_output).collect(),
             execution_count: execution_count.map(|n| n as usize),
             execution_state: ExecutionState::Idle,
+            run_started: None,
+            last_run: None,
             rendered: false,
+            metadata,
+            attachments: None,
         },
-        nbformat::v4::Cell::Markdown { id, source, .. } => Cell {
+        nbformat::v4::Cell::Markdown {
+            id,
+            metadata,
+            source,
+            attachments,
+        } => Cell {
             id: id.to_string(),
             cell_type: CellType::Markdown,
             source: source.join(""),
             outputs: Vec::new(),
             execution_count: None,
             execution_state: ExecutionState::Idle,
+            run_started: None,
+            last_run: None,
             rendered: false,
+            metadata,
+            attachments,
         },
-        nbformat::v4::Cell::Raw { id, source, .. } => Cell {
+        nbformat::v4::Cell::Raw {
+            id,
+            metadata,
+            source,
+        } => Cell {
             id: id.to_string(),
             cell_type: CellType::Raw,
             source: source.join(""),
             outputs: Vec::new(),
             execution_count: None,
             execution_state: ExecutionState::Idle,
+            run_started: None,
+            last_run: None,
             rendered: false,
+            metadata,
+            attachments: None,
         },
     }
 }
@@ -329,13 +511,18 @@ fn convert_v4_cell(cell: nbformat::v4::Cell) -> Cell {
 /// Convert an nbformat v4 Output to our runtime CellOutput.
 fn convert_v4_output(output: nbformat::v4::Output) -> CellOutput {
     match output {
-        nbformat::v4::Output::Stream { name, text } => CellOutput::Stream { name, text: text.0 },
+        nbformat::v4::Output::Stream { name, text } => CellOutput::Stream {
+            name,
+            text: StreamText::new(&text.0),
+        },
         nbformat::v4::Output::DisplayData(dd) => CellOutput::DisplayData {
-            data: media_to_hashmap(&dd.data),
+            data: MimeBundle::from_media(&dd.data),
+            metadata: dd.metadata,
         },
         nbformat::v4::Output::ExecuteResult(er) => CellOutput::ExecuteResult {
             execution_count: er.execution_count.value(),
-            data: media_to_hashmap(&er.data),
+            data: MimeBundle::from_media(&er.data),
+            metadata: er.metadata,
         },
         nbformat::v4::Output::Error(e) => CellOutput::Error {
             ename: e.ename,
@@ -345,41 +532,6 @@ fn convert_v4_output(output: nbformat::v4::Output) -> CellOutput {
     }
 }
 
-/// Convert a jupyter_protocol Media bundle to a simple HashMap.
-fn media_to_hashmap(media: &jupyter_protocol::Media) -> std::collections::HashMap<String, String> {
-    let mut map = std::collections::HashMap::new();
-    for mt in &media.content {
-        let (mime, value) = media_type_to_pair(mt);
-        map.insert(mime, value);
-    }
-    map
-}
-
-/// Extract MIME type string and content from a MediaType (public for app.rs).
-pub fn media_type_to_pair_pub(mt: &jupyter_protocol::MediaType) -> (String, String) {
-    media_type_to_pair(mt)
-}
-
-/// Extract MIME type string and content from a MediaType.
-fn media_type_to_pair(mt: &jupyter_protocol::MediaType) -> (String, String) {
-    // MediaType has variants like Plain(String), Html(String), Png(String), etc.
-    // We use the Debug representation to extract the MIME type string
-    match mt {
-        jupyter_protocol::MediaType::Plain(s) => ("text/plain".to_string(), s.clone()),
-        jupyter_protocol::MediaType::Html(s) => ("text/html".to_string(), s.clone()),
-        jupyter_protocol::MediaType::Latex(s) => ("text/latex".to_string(), s.clone()),
-        jupyter_protocol::MediaType::Javascript(s) => {
-            ("application/javascript".to_string(), s.clone())
-        }
-        jupyter_protocol::MediaType::Markdown(s) => ("text/markdown".to_string(), s.clone()),
-        jupyter_protocol::MediaType::Svg(s) => ("image/svg+xml".to_string(), s.clone()),
-        jupyter_protocol::MediaType::Png(s) => ("image/png".to_string(), s.clone()),
-        jupyter_protocol::MediaType::Jpeg(s) => ("image/jpeg".to_string(), s.clone()),
-        jupyter_protocol::MediaType::Json(v) => ("application/json".to_string(), v.to_string()),
-        _ => ("application/octet-stream".to_string(), String::new()),
-    }
-}
-
 /// Convert our runtime Cell back to nbformat v4 Cell.
 fn convert_to_v4_cell(cell: &Cell) -> nbformat::v4::Cell {
     let source = source_to_lines(&cell.source);
@@ -389,42 +541,47 @@ fn convert_to_v4_cell(cell: &Cell) -> nbformat::v4::Cell {
         .try_into()
         .unwrap_or_else(|_| Uuid::new_v4().into());
 
-    let default_metadata = nbformat::v4::CellMetadata {
-        id: None,
-        collapsed: None,
-        scrolled: None,
-        deletable: None,
-        editable: None,
-        format: None,
-        name: None,
-        tags: None,
-        jupyter: None,
-        execution: None,
-        additional: Default::default(),
-    };
+    let metadata = cell.metadata.clone();
 
     match cell.cell_type {
         CellType::Code => nbformat::v4::Cell::Code {
             id,
-            metadata: default_metadata,
+            metadata,
             execution_count: cell.execution_count.map(|n| n as i32),
             source,
             outputs: cell.outputs.iter().map(convert_to_v4_output).collect(),
         },
         CellType::Markdown => nbformat::v4::Cell::Markdown {
             id,
-            metadata: default_metadata,
+            metadata,
             source,
-            attachments: None,
+            attachments: cell.attachments.clone(),
         },
         CellType::Raw => nbformat::v4::Cell::Raw {
             id,
-            metadata: default_metadata,
+            metadata,
             source,
         },
     }
 }
 
+/// Metadata for a freshly created cell.
+fn empty_cell_metadata() -> nbformat::v4::CellMetadata {
+    nbformat::v4::CellMetadata {
+        id: None,
+        collapsed: None,
+        scrolled: None,
+        deletable: None,
+        editable: None,
+        format: None,
+        name: None,
+        tags: None,
+        jupyter: None,
+        execution: None,
+        additional: Default::default(),
+    }
+}
+
 /// Convert a source string to the Vec<String> format nbformat expects
 /// (lines with trailing newlines).
 fn source_to_lines(source: &str) -> Vec<String> {
@@ -450,7 +607,7 @@ fn convert_to_v4_output(output: &CellOutput) -> nbformat::v4::Output {
     match output {
         CellOutput::Stream { name, text } => nbformat::v4::Output::Stream {
             name: name.clone(),
-            text: nbformat::v4::MultilineString(text.clone()),
+            text: nbformat::v4::MultilineString(text.raw().to_string()),
         },
         CellOutput::Error {
             ename,
@@ -461,41 +618,225 @@ fn convert_to_v4_output(output: &CellOutput) -> nbformat::v4::Output {
             evalue: evalue.clone(),
             traceback: traceback.clone(),
         }),
-        CellOutput::DisplayData { data } => {
+        CellOutput::DisplayData { data, metadata } => {
             nbformat::v4::Output::DisplayData(nbformat::v4::DisplayData {
-                data: hashmap_to_media(data),
-                metadata: Default::default(),
+                data: data.to_media(),
+                metadata: metadata.clone(),
             })
         }
         CellOutput::ExecuteResult {
             execution_count,
             data,
+            metadata,
         } => nbformat::v4::Output::ExecuteResult(nbformat::v4::ExecuteResult {
             execution_count: jupyter_protocol::ExecutionCount::new(*execution_count),
-            data: hashmap_to_media(data),
-            metadata: Default::default(),
+            data: data.to_media(),
+            metadata: metadata.clone(),
         }),
     }
 }
 
-/// Convert a HashMap back to jupyter_protocol Media.
-fn hashmap_to_media(data: &std::collections::HashMap<String, String>) -> jupyter_protocol::Media {
-    use jupyter_protocol::MediaType;
-
-    let mut content = Vec::new();
-    for (mime, value) in data {
-        let mt = match mime.as_str() {
-            "text/plain" => MediaType::Plain(value.clone()),
-            "text/html" => MediaType::Html(value.clone()),
-            "text/markdown" => MediaType::Markdown(value.clone()),
-            "text/latex" => MediaType::Latex(value.clone()),
-            "image/png" => MediaType::Png(value.clone()),
-            "image/jpeg" => MediaType::Jpeg(value.clone()),
-            "image/svg+xml" => MediaType::Svg(value.clone()),
-            "application/javascript" => MediaType::Javascript(value.clone()),
-            _ => MediaType::Plain(value.clone()),
-        };
-        content.push(mt);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    const CORPUS: &[&str] = &[
+        "tagged.ipynb",
+        "Mediatypes.ipynb",
+        "test4_5.ipynb",
+        "test4.ipynb",
+        "test4custom.ipynb",
+        "test4docinfo.ipynb",
+        "test4jupyter_metadata.ipynb",
+        "test4jupyter_metadata_timings.ipynb",
+        "many_tracebacks.ipynb",
+        "news_categorization.ipynb",
+    ];
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/notebooks")
+            .join(name)
+    }
+
+    /// Serialize a notebook the way `Notebook::save` does and parse it back as JSON.
+    fn saved_json(notebook: &Notebook) -> Value {
+        let nb = nbformat::Notebook::V4(notebook.to_v4());
+        let json = nbformat::serialize_notebook(&nb).expect("serialize");
+        serde_json::from_str(&json).expect("reparse")
+    }
+
+    /// The fixture as it is on disk, for comparing with what we save.
+    fn original_json(path: &Path) -> Value {
+        serde_json::from_str(&std::fs::read_to_string(path).expect("read fixture"))
+            .expect("parse fixture")
+    }
+
+    /// Put both sides of a round trip in the same form: upgrading a legacy
+    /// minor version bumps `nbformat_minor` and adds cell ids, and
+    /// multiline strings may be stored as one string or a list of lines.
+    fn normalize(value: &mut Value) {
+        value.as_object_mut().unwrap().remove("nbformat_minor");
+        strip_cell_ids(value);
+        join_multiline(value);
+    }
+
+    /// Join the lines of `source`, stream `text` and MIME data entries
+    /// (under `data`, or `attachments` entries).
+    fn join_multiline(value: &mut Value) {
+        match value {
+            Value::Array(items) => items.iter_mut().for_each(join_multiline),
+            Value::Object(map) => {
+                for (key, field) in map.iter_mut() {
+                    match (key.as_str(), field) {
+                        ("source" | "text", field) => join_lines(field),
+                        ("data", Value::Object(bundle)) => bundle.values_mut().for_each(join_lines),
+                        ("attachments", Value::Object(files)) => {
+                            for bundle in files.values_mut().filter_map(Value::as_object_mut) {
+                                bundle.values_mut().for_each(join_lines);
+                            }
+                        }
+                        (_, field) => join_multiline(field),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn join_lines(value: &mut Value) {
+        if let Value::Array(lines) = value
+            && lines.iter().all(Value::is_string)
+        {
+            *value = Value::String(lines.iter().filter_map(Value::as_str).collect());
+        }
+    }
+
+    /// Legacy notebooks get fresh random cell ids on upgrade; drop them before comparing.
+    fn strip_cell_ids(value: &mut Value) {
+        if let Some(cells) = value["cells"].as_array_mut() {
+            for cell in cells {
+                if let Some(cell) = cell.as_object_mut() {
+                    cell.remove("id");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_corpus_is_lossless() {
+        for name in CORPUS {
+            let path = fixture(name);
+            let notebook = Notebook::load(&path).expect("load fixture");
+            let mut saved = saved_json(&notebook);
+            let mut original = original_json(&path);
+            normalize(&mut saved);
+            normalize(&mut original);
+            assert_eq!(saved, original, "round trip changed {name}");
+        }
+    }
+
+    #[test]
+    fn test_round_trip_matches_original_file() {
+        // tagged.ipynb is already in canonical v4.5 form, so saving must reproduce it exactly
+        let path = fixture("tagged.ipynb");
+        let original: Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let notebook = Notebook::load(&path).expect("load fixture");
+        assert_eq!(saved_json(&notebook), original);
+    }
+
+    #[test]
+    fn test_load_exposes_tags_and_metadata() {
+        let notebook = Notebook::load(&fixture("tagged.ipynb")).expect("load fixture");
+
+        assert_eq!(notebook.metadata.kernel_name(), Some("python3"));
+        assert_eq!(notebook.metadata.language(), Some("python"));
+        assert!(notebook.metadata.additional.contains_key("papermill"));
+
+        let params = &notebook.cells[1];
+        assert_eq!(
+            params.metadata.tags.as_deref(),
+            Some(&["parameters".to_string()][..])
+        );
+        assert!(notebook.cells[0].attachments.is_some());
+
+        match &notebook.cells[2].outputs[1] {
+            CellOutput::DisplayData { metadata, .. } => {
+                assert_eq!(metadata["needs_background"], "light");
+            }
+            other => panic!("expected display_data, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_set_kernelspec_updates_language() {
+        let mut notebook = Notebook::new();
+        notebook.metadata.set_kernelspec("ir", "R", Some("R"));
+
+        let kernelspec = notebook.metadata.kernelspec.as_ref().unwrap();
+        assert_eq!(kernelspec.name, "ir");
+        assert_eq!(kernelspec.display_name, "R");
+        assert_eq!(notebook.metadata.language(), Some("R"));
+
+        // Another kernel for the same language keeps language_info
+        notebook.metadata.language_info.as_mut().unwrap().version = Some("4.3".to_string());
+        notebook
+            .metadata
+            .set_kernelspec("ir-dev", "R (dev)", Some("R"));
+        assert_eq!(
+            notebook
+                .metadata
+                .language_info
+                .as_ref()
+                .unwrap()
+                .version
+                .as_deref(),
+            Some("4.3")
+        );
+    }
+
+    #[test]
+    fn test_cell_lookup_by_id_follows_moves() {
+        let mut notebook = Notebook::new();
+        notebook.insert_cell_after(0, Cell::new_code("second"));
+        let id = notebook.cells[1].id.clone();
+
+        let moved_to = notebook.move_cell_up(1);
+        assert_eq!(notebook.cell_index(&id), Some(moved_to));
+        assert_eq!(notebook.cell_mut(&id).unwrap().source, "second");
+
+        notebook.delete_cell(moved_to);
+        assert_eq!(notebook.cell_index(&id), None);
+    }
+
+    #[test]
+    fn test_elapsed_from_execution_metadata() {
+        let mut cell = Cell::new_code("sleep(1.5)");
+        assert_eq!(cell.elapsed(), None);
+
+        let start = DateTime::parse_from_rfc3339("2024-01-10T14:19:46.000000Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let end = start + chrono::Duration::milliseconds(1500);
+        cell.execution_timing().iopub_execute_input = Some(timing_timestamp(&start));
+        cell.execution_timing().iopub_status_idle = Some(timing_timestamp(&end));
+        assert_eq!(
+            cell.metadata
+                .execution
+                .as_ref()
+                .unwrap()
+                .iopub_execute_input
+                .as_deref(),
+            Some("2024-01-10T14:19:46.000000Z")
+        );
+        assert_eq!(cell.elapsed(), Some(Duration::from_millis(1500)));
+
+        // The shell reply is preferred as the end time when present
+        cell.execution_timing().shell_execute_reply = Some(timing_timestamp(
+            &(end + chrono::Duration::milliseconds(500)),
+        ));
+        assert_eq!(cell.elapsed(), Some(Duration::from_secs(2)));
     }
-    jupyter_protocol::Media { content }
 }
@@ -0,0 +1,391 @@
+/// Stream output text with a terminal-style rendering.
+///
+/// Kernels write progress bars (tqdm, pip, ...) using `\r`, `\b` and ANSI
+/// cursor movement. Printing that verbatim produces one line per update, so
+/// we keep the raw text for saving and maintain a normalized copy where
+/// those control sequences are applied the way a terminal would.
+#[derive(Debug, Clone, Default)]
+pub struct StreamText {
+    raw: String,
+    screen: Screen,
+}
+
+impl StreamText {
+    pub fn new(raw: &str) -> Self {
+        let mut text = Self::default();
+        text.push_str(raw);
+        text
+    }
+
+    /// Append a chunk of raw stream output.
+    pub fn push_str(&mut self, chunk: &str) {
+        self.raw.push_str(chunk);
+        self.screen.feed(chunk);
+        self.screen.update_rendered();
+    }
+
+    /// The text exactly as the kernel sent it (what gets saved to .ipynb).
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    /// The text with carriage returns, backspaces and cursor movement applied.
+    /// SGR (color) escapes are kept for ansi-to-tui.
+    pub fn rendered(&self) -> &str {
+        &self.screen.rendered
+    }
+}
+
+/// One screen row: its visible text and the SGR escapes that precede
+/// some of its characters.
+#[derive(Debug, Clone, Default)]
+struct Row {
+    text: String,
+    /// Characters in `text`
+    len: usize,
+    /// (column, SGR escapes written before the character there), by column.
+    /// Overwriting a character also drops its escapes.
+    styles: Vec<(usize, String)>,
+}
+
+impl Row {
+    /// Byte offset of column `col` (at most `len`).
+    fn byte_offset(&self, col: usize) -> usize {
+        if col >= self.len {
+            return self.text.len();
+        }
+        self.text
+            .char_indices()
+            .nth(col)
+            .map_or(self.text.len(), |(i, _)| i)
+    }
+
+    /// Write `ch` at `col` with `sgr` before it, padding with spaces.
+    fn put(&mut self, col: usize, ch: char, sgr: String) {
+        while self.len < col {
+            self.text.push(' ');
+            self.len += 1;
+        }
+        if col == self.len {
+            self.text.push(ch);
+            self.len += 1;
+        } else {
+            let start = self.byte_offset(col);
+            let end = start + self.text[start..].chars().next().map_or(0, char::len_utf8);
+            self.text
+                .replace_range(start..end, ch.encode_utf8(&mut [0; 4]));
+        }
+        let at = self.styles.partition_point(|(c, _)| *c < col);
+        if self.styles.get(at).is_some_and(|(c, _)| *c == col) {
+            self.styles.remove(at);
+        }
+        if !sgr.is_empty() {
+            self.styles.insert(at, (col, sgr));
+        }
+    }
+
+    /// Delete the character at `col`, shifting the rest left.
+    fn remove(&mut self, col: usize) {
+        if col >= self.len {
+            return;
+        }
+        let start = self.byte_offset(col);
+        self.text.remove(start);
+        self.len -= 1;
+        self.styles.retain(|(c, _)| *c != col);
+        for (c, _) in &mut self.styles {
+            if *c > col {
+                *c -= 1;
+            }
+        }
+    }
+
+    /// Cut the row at `col`.
+    fn truncate(&mut self, col: usize) {
+        if col < self.len {
+            let start = self.byte_offset(col);
+            self.text.truncate(start);
+            self.len = col;
+        }
+        self.styles.retain(|(c, _)| *c < col);
+    }
+
+    /// Blank columns `0..=col`.
+    fn blank_to(&mut self, col: usize) {
+        let end = (col + 1).min(self.len);
+        let start = self.byte_offset(end);
+        self.text.replace_range(..start, &" ".repeat(end));
+        self.styles.retain(|(c, _)| *c > col);
+    }
+
+    fn render_into(&self, out: &mut String) {
+        let mut styles = self.styles.iter().peekable();
+        for (col, ch) in self.text.chars().enumerate() {
+            while let Some((_, sgr)) = styles.next_if(|(c, _)| *c == col) {
+                out.push_str(sgr);
+            }
+            out.push(ch);
+        }
+    }
+}
+
+/// Minimal terminal screen: rows of text plus a cursor, and the rendered
+/// text kept up to date from the first row that changed.
+#[derive(Debug, Clone)]
+struct Screen {
+    rows: Vec<Row>,
+    row: usize,
+    col: usize,
+    /// SGR escapes waiting for the next visible character
+    pending_sgr: String,
+    /// Incomplete escape sequence carried over from the previous chunk
+    partial_escape: String,
+    /// The rows joined by newlines, then `pending_sgr`
+    rendered: String,
+    /// Byte offset in `rendered` where each row starts
+    row_offsets: Vec<usize>,
+    /// First row changed since `rendered` was last updated
+    first_dirty: Option<usize>,
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Self {
+            rows: vec![Row::default()],
+            row: 0,
+            col: 0,
+            pending_sgr: String::new(),
+            partial_escape: String::new(),
+            rendered: String::new(),
+            row_offsets: vec![0],
+            first_dirty: None,
+        }
+    }
+}
+
+impl Screen {
+    fn feed(&mut self, chunk: &str) {
+        let input = if self.partial_escape.is_empty() {
+            chunk.to_string()
+        } else {
+            std::mem::take(&mut self.partial_escape) + chunk
+        };
+
+        let mut chars = input.char_indices().peekable();
+        while let Some((start, ch)) = chars.next() {
+            match ch {
+                '\n' => {
+                    self.row += 1;
+                    self.col = 0;
+                    self.ensure_row();
+                }
+                '\r' => self.col = 0,
+                '\x08' => {
+                    if self.col > 0 {
+                        self.col -= 1;
+                        self.mark_dirty();
+                        self.rows[self.row].remove(self.col);
+                    }
+                }
+                '\x1b' => {
+                    // Find the end of the escape sequence
+                    let rest = &input[start..];
+                    match escape_len(rest) {
+                        Some(len) => {
+                            self.apply_escape(&rest[..len]);
+                            // Skip the remaining bytes of the sequence
+                            while chars.peek().is_some_and(|&(i, _)| i < start + len) {
+                                chars.next();
+                            }
+                        }
+                        None => {
+                            self.partial_escape = rest.to_string();
+                            return;
+                        }
+                    }
+                }
+                c if c.is_control() && c != '\t' => {}
+                c => self.put(c),
+            }
+        }
+    }
+
+    /// Write a visible character at the cursor, overwriting what is there.
+    fn put(&mut self, ch: char) {
+        self.mark_dirty();
+        let sgr = std::mem::take(&mut self.pending_sgr);
+        self.rows[self.row].put(self.col, ch, sgr);
+        self.col += 1;
+    }
+
+    fn apply_escape(&mut self, seq: &str) {
+        // Only CSI sequences (ESC [ ...) are interpreted; others are dropped.
+        let Some(body) = seq.strip_prefix("\x1b[") else {
+            return;
+        };
+        let Some(final_byte) = body.chars().last() else {
+            return;
+        };
+        let params = &body[..body.len() - final_byte.len_utf8()];
+        let n = params.parse::<usize>().unwrap_or(1).max(1);
+
+        match final_byte {
+            'm' => self.pending_sgr.push_str(seq),
+            'A' => self.row = self.row.saturating_sub(n),
+            'B' => {
+                self.row += n;
+                self.ensure_row();
+            }
+            'C' => self.col += n,
+            'D' => self.col = self.col.saturating_sub(n),
+            'G' => self.col = n - 1,
+            'K' => {
+                self.mark_dirty();
+                let row = &mut self.rows[self.row];
+                match params {
+                    "" | "0" => row.truncate(self.col),
+                    "1" => row.blank_to(self.col),
+                    "2" => row.truncate(0),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ensure_row(&mut self) {
+        if self.rows.len() <= self.row {
+            self.first_dirty = Some(
+                self.first_dirty
+                    .map_or(self.rows.len(), |first| first.min(self.rows.len())),
+            );
+            self.rows.resize_with(self.row + 1, Row::default);
+        }
+    }
+
+    /// Note that the cursor's row is about to change.
+    fn mark_dirty(&mut self) {
+        self.first_dirty = Some(
+            self.first_dirty
+                .map_or(self.row, |first| first.min(self.row)),
+        );
+    }
+
+    /// Re-render the rows from the first changed one, and the trailing
+    /// SGR escapes. Rows above it are left as they are.
+    fn update_rendered(&mut self) {
+        let first = self
+            .first_dirty
+            .take()
+            .unwrap_or(self.rows.len() - 1)
+            .min(self.row_offsets.len() - 1);
+        // Drop the newline before the first row too; the loop puts it back
+        self.rendered
+            .truncate(self.row_offsets[first] - usize::from(first > 0));
+        self.row_offsets.truncate(first);
+        for (i, row) in self.rows.iter().enumerate().skip(first) {
+            if i > 0 {
+                self.rendered.push('\n');
+            }
+            self.row_offsets.push(self.rendered.len());
+            row.render_into(&mut self.rendered);
+        }
+        self.rendered.push_str(&self.pending_sgr);
+    }
+}
+
+/// Length in bytes of the escape sequence at the start of `s`,
+/// or `None` if the sequence is incomplete.
+fn escape_len(s: &str) -> Option<usize> {
+    let bytes = s.as_bytes();
+    match bytes.get(1) {
+        None => None,
+        Some(b'[') => bytes[2..]
+            .iter()
+            .position(|b| (0x40..=0x7e).contains(b))
+            .map(|pos| pos + 3),
+        // OSC (e.g. OSC 8 hyperlinks), DCS, APC and PM carry a string up to
+        // BEL (OSC only) or ST (ESC \)
+        Some(&kind @ (b']' | b'P' | b'_' | b'^')) => {
+            bytes[2..].iter().enumerate().find_map(|(i, &b)| match b {
+                0x07 if kind == b']' => Some(i + 3),
+                0x1b if bytes.get(i + 3) == Some(&b'\\') => Some(i + 4),
+                _ => None,
+            })
+        }
+        // Two-byte escape (ESC + one char)
+        Some(_) => Some(1 + s[1..].chars().next().map_or(1, char::len_utf8)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_carriage_return_overwrites_line() {
+        let text = StreamText::new("10%\r50%\r100%\ndone\n");
+        assert_eq!(text.rendered(), "100%\ndone\n");
+        assert_eq!(text.raw(), "10%\r50%\r100%\ndone\n");
+    }
+
+    #[test]
+    fn test_carriage_return_across_chunks() {
+        let mut text = StreamText::default();
+        for chunk in ["  0%|     |\r", " 50%|##   |\r", "100%|#####|"] {
+            text.push_str(chunk);
+        }
+        assert_eq!(text.rendered(), "100%|#####|");
+    }
+
+    #[test]
+    fn test_backspace_erases() {
+        assert_eq!(StreamText::new("abc\x08\x08d").rendered(), "ad");
+    }
+
+    #[test]
+    fn test_cursor_up_rewrites_previous_line() {
+        // Two stacked progress bars, redrawn in place
+        let mut text = StreamText::new("a 0%\nb 0%");
+        text.push_str("\r\x1b[A\x1b[2Ka 50%\n\r\x1b[2Kb 50%");
+        assert_eq!(text.rendered(), "a 50%\nb 50%");
+    }
+
+    #[test]
+    fn test_sgr_kept_and_split_escape() {
+        let mut text = StreamText::new("\x1b[3");
+        text.push_str("1mred\x1b[0m\n");
+        assert_eq!(text.rendered(), "\x1b[31mred\n\x1b[0m");
+    }
+
+    #[test]
+    fn test_overwrite_drops_styling() {
+        let mut text = StreamText::new("\x1b[31mab");
+        text.push_str("\r\x1b[32mc");
+        assert_eq!(text.rendered(), "\x1b[32mcb");
+    }
+
+    #[test]
+    fn test_osc_payload_is_skipped() {
+        // OSC 8 hyperlink, terminated by ST, then by BEL split across chunks
+        let mut text = StreamText::new("\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\ ");
+        text.push_str("\x1b]0;ti");
+        text.push_str("tle\x07é\n");
+        assert_eq!(text.rendered(), "link é\n");
+    }
+
+    #[test]
+    fn test_rows_above_the_cursor_are_kept() {
+        let mut text = StreamText::new("done\n");
+        for percent in 0..=100 {
+            text.push_str(&format!("\r{}%", percent));
+        }
+        assert_eq!(text.rendered(), "done\n100%");
+        text.push_str("\nnext\n");
+        assert_eq!(text.rendered(), "done\n100%\nnext\n");
+        text.push_str("\x1b[3A\rDONE");
+        assert_eq!(text.rendered(), "DONE\n100%\nnext\n");
+        text.push_str("\x1b[1K\x1b[B\x1b[2K");
+        assert_eq!(text.rendered(), "    \n\nnext\n");
+    }
+}
@@ -1,193 +1,79 @@
 use crate::app::App;
+use crate::json_tree::JsonView;
 use crate::notebook::model::{CellType, ExecutionState};
 use crate::ui::highlight::Highlighter;
-use ansi_to_tui::IntoText;
-use image::DynamicImage;
+use crate::ui::html::{HtmlTable, truncate};
+use crate::ui::image::decode_base64_image;
+use crate::ui::json_tree;
+use crate::ui::render_cache::{LayoutBlock, OutputImage};
 use ratatui::Frame;
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span, Text};
-use ratatui::widgets::{Block, Borders, Paragraph, Wrap};
+use ratatui::widgets::{Block, Borders, Cell as TableCell, Paragraph, Row, Table, Wrap};
 use ratatui_image::StatefulImage;
 
-/// Convert a `ratatui_core::text::Text` (from tui-markdown) to `ratatui::text::Text`.
-/// Both have the same structure but are from different crate versions.
-fn convert_core_text(core_text: ratatui_core::text::Text<'_>) -> Text<'static> {
-    let lines: Vec<Line<'static>> = core_text
-        .lines
-        .into_iter()
-        .map(|line| {
-            let spans: Vec<Span<'static>> = line
-                .spans
-                .into_iter()
-                .map(|span| {
-                    let content: String = span.content.to_string();
-                    let style = convert_core_style(span.style);
-                    Span::styled(content, style)
-                })
-                .collect();
-            Line::from(spans)
-        })
-        .collect();
-    Text::from(lines)
-}
-
-/// Convert a `ratatui_core::style::Style` to `ratatui::style::Style`.
-fn convert_core_style(s: ratatui_core::style::Style) -> Style {
-    let mut style = Style::default();
-    if let Some(fg) = s.fg {
-        style = style.fg(convert_core_color(fg));
-    }
-    if let Some(bg) = s.bg {
-        style = style.bg(convert_core_color(bg));
-    }
-    // Convert modifiers
-    let m = s.add_modifier;
-    if m.contains(ratatui_core::style::Modifier::BOLD) {
-        style = style.add_modifier(Modifier::BOLD);
-    }
-    if m.contains(ratatui_core::style::Modifier::ITALIC) {
-        style = style.add_modifier(Modifier::ITALIC);
-    }
-    if m.contains(ratatui_core::style::Modifier::UNDERLINED) {
-        style = style.add_modifier(Modifier::UNDERLINED);
-    }
-    if m.contains(ratatui_core::style::Modifier::CROSSED_OUT) {
-        style = style.add_modifier(Modifier::CROSSED_OUT);
-    }
-    if m.contains(ratatui_core::style::Modifier::DIM) {
-        style = style.add_modifier(Modifier::DIM);
-    }
-    if m.contains(ratatui_core::style::Modifier::REVERSED) {
-        style = style.add_modifier(Modifier::REVERSED);
-    }
-    style
-}
-
-/// Convert a `ratatui_core::style::Color` to `ratatui::style::Color`.
-fn convert_core_color(c: ratatui_core::style::Color) -> Color {
-    match c {
-        ratatui_core::style::Color::Reset => Color::Reset,
-        ratatui_core::style::Color::Black => Color::Black,
-        ratatui_core::style::Color::Red => Color::Red,
-        ratatui_core::style::Color::Green => Color::Green,
-        ratatui_core::style::Color::Yellow => Color::Yellow,
-        ratatui_core::style::Color::Blue => Color::Blue,
-        ratatui_core::style::Color::Magenta => Color::Magenta,
-        ratatui_core::style::Color::Cyan => Color::Cyan,
-        ratatui_core::style::Color::Gray => Color::Gray,
-        ratatui_core::style::Color::DarkGray => Color::DarkGray,
-        ratatui_core::style::Color::LightRed => Color::LightRed,
-        ratatui_core::style::Color::LightGreen => Color::LightGreen,
-        ratatui_core::style::Color::LightYellow => Color::LightYellow,
-        ratatui_core::style::Color::LightBlue => Color::LightBlue,
-        ratatui_core::style::Color::LightMagenta => Color::LightMagenta,
-        ratatui_core::style::Color::LightCyan => Color::LightCyan,
-        ratatui_core::style::Color::White => Color::White,
-        ratatui_core::style::Color::Rgb(r, g, b) => Color::Rgb(r, g, b),
-        ratatui_core::style::Color::Indexed(i) => Color::Indexed(i),
-    }
-}
-
 /// Width of the relative line number gutter (digits + padding).
 const LINE_NUMBER_WIDTH: u16 = 4;
 
-/// Maximum height (in terminal rows) for rendered images.
-const MAX_IMAGE_HEIGHT: u16 = 20;
-
-/// Decode a base64-encoded image string into a DynamicImage.
-fn decode_base64_image(b64: &str) -> Option<DynamicImage> {
-    use image::ImageReader;
-    use std::io::Cursor;
-
-    // Strip whitespace/newlines that Jupyter sometimes includes
-    let cleaned: String = b64.chars().filter(|c| !c.is_whitespace()).collect();
-    let bytes = base64_decode(&cleaned)?;
-    ImageReader::new(Cursor::new(bytes))
-        .with_guessed_format()
-        .ok()?
-        .decode()
-        .ok()
-}
+/// Build the widget for an HTML table: header rows in bold, the index
+/// columns pinned on the left and the data columns scrolled by `scroll`,
+/// with `…` columns marking any that are out of view.
+fn html_table_widget(table: &HtmlTable, width: u16, scroll: usize) -> Table<'static> {
+    let widths = table.column_widths();
+    let view = table.view(width as usize, scroll);
+    let (index, data) = view
+        .columns
+        .split_at(table.index_columns.min(view.columns.len()));
+
+    // None stands for a `…` marker column
+    let mut columns: Vec<Option<usize>> = index.iter().copied().map(Some).collect();
+    if view.hidden_left {
+        columns.push(None);
+    }
+    columns.extend(data.iter().copied().map(Some));
+    if view.hidden_right {
+        columns.push(None);
+    }
 
-/// Simple base64 decoder (standard alphabet, with padding).
-fn base64_decode(input: &str) -> Option<Vec<u8>> {
-    const TABLE: [u8; 128] = {
-        let mut t = [255u8; 128];
-        let mut i = 0u8;
-        while i < 26 {
-            t[(b'A' + i) as usize] = i;
-            t[(b'a' + i) as usize] = i + 26;
-            i += 1;
-        }
-        let mut d = 0u8;
-        while d < 10 {
-            t[(b'0' + d) as usize] = d + 52;
-            d += 1;
-        }
-        t[b'+' as usize] = 62;
-        t[b'/' as usize] = 63;
-        t
+    let marker_style = Style::default().fg(Color::DarkGray);
+    let index_style = Style::default().add_modifier(Modifier::BOLD);
+    let cell_text = |row: &[String], column: usize| {
+        truncate(row.get(column).map_or("", String::as_str), widths[column])
     };
 
-    let input = input.as_bytes();
-    let len = input.len();
-    if len % 4 != 0 {
-        return None;
-    }
-
-    let mut out = Vec::with_capacity(len / 4 * 3);
-    let mut i = 0;
-    while i < len {
-        let a = *TABLE.get(*input.get(i)? as usize)?;
-        let b = *TABLE.get(*input.get(i + 1)? as usize)?;
-        if a == 255 || b == 255 {
-            return None;
-        }
-        out.push((a << 2) | (b >> 4));
-        if input[i + 2] != b'=' {
-            let c = *TABLE.get(input[i + 2] as usize)?;
-            if c == 255 {
-                return None;
-            }
-            out.push((b << 4) | (c >> 2));
-            if input[i + 3] != b'=' {
-                let d = *TABLE.get(input[i + 3] as usize)?;
-                if d == 255 {
-                    return None;
-                }
-                out.push((c << 6) | d);
+    let header_cells = columns.iter().map(|&column| match column {
+        Some(column) => TableCell::from(Text::from(
+            table
+                .header
+                .iter()
+                .map(|row| Line::from(cell_text(row, column)))
+                .collect::<Vec<_>>(),
+        )),
+        None => TableCell::from(Span::styled("…", marker_style)),
+    });
+    let header = Row::new(header_cells)
+        .height(table.header.len() as u16)
+        .style(Style::default().add_modifier(Modifier::BOLD));
+
+    let rows = table.rows.iter().map(|row| {
+        Row::new(columns.iter().map(|&column| match column {
+            Some(column) if column < table.index_columns => {
+                TableCell::from(Span::styled(cell_text(row, column), index_style))
             }
-        }
-        i += 4;
-    }
-    Some(out)
-}
-
-/// Calculate the height in terminal rows that an image would occupy,
-/// given the picker's font size and available width.
-fn image_height_rows(img: &DynamicImage, font_size: (u16, u16), available_width: u16) -> u16 {
-    if font_size.0 == 0 || font_size.1 == 0 || available_width == 0 {
-        return 1;
+            Some(column) => TableCell::from(cell_text(row, column)),
+            None => TableCell::from(Span::styled("…", marker_style)),
+        }))
+    });
+
+    let constraints = columns
+        .iter()
+        .map(|&column| Constraint::Length(column.map_or(1, |c| widths[c]) as u16));
+    let mut widget = Table::new(rows, constraints).column_spacing(1);
+    if !table.header.is_empty() {
+        widget = widget.header(header);
     }
-    let avail_px_w = (available_width as u32) * (font_size.0 as u32);
-    let scale = (avail_px_w as f64) / (img.width() as f64).max(1.0);
-    let scale = scale.min(1.0); // don't upscale
-    let h_px = (img.height() as f64 * scale).ceil() as u32;
-    let rows = (h_px as f64 / font_size.1 as f64).ceil() as u16;
-    rows.min(MAX_IMAGE_HEIGHT).max(1)
-}
-
-/// Check if an output's data map contains an image we can render.
-fn has_renderable_image(data: &std::collections::HashMap<String, String>) -> bool {
-    data.contains_key("image/png") || data.contains_key("image/jpeg")
-}
-
-/// Get the base64 image data from an output's data map.
-fn get_image_data(data: &std::collections::HashMap<String, String>) -> Option<&str> {
-    data.get("image/png")
-        .or_else(|| data.get("image/jpeg"))
-        .map(|s| s.as_str())
+    widget
 }
 
 /// Render the scrollable list of cells.
@@ -202,6 +88,7 @@ pub fn render_cell_list(frame: &mut Frame, app: &mut App, area: Rect) {
     }
 
     // Render cells from scroll_offset until we run out of vertical space
+    let inner_width = area.width.saturating_sub(2);
     let mut y = area.y;
     let mut cells_rendered = 0;
 
@@ -228,15 +115,10 @@ pub fn render_cell_list(frame: &mut Frame, app: &mut App, area: Rect) {
                 .unwrap_or(1)
                 .max(1)
         } else if cell.cell_type == CellType::Markdown && cell.rendered {
-            // Rendered markdown: compute height from tui_markdown output
-            let md_source = if cell.source.is_empty() {
-                " ".to_string()
-            } else {
-                cell.source.clone()
-            };
-            let core_text = tui_markdown::from_str(&md_source);
-            let text = convert_core_text(core_text);
-            text.lines.len().max(1)
+            // Rendered markdown, wrapped to the cell's width
+            app.render_cache
+                .markdown(&cell.id, &cell.source, inner_width)
+                .height
         } else {
             cell.source.lines().count().max(1)
         };
@@ -244,59 +126,23 @@ pub fn render_cell_list(frame: &mut Frame, app: &mut App, area: Rect) {
         let output_lines: usize = cell
             .outputs
             .iter()
-            .map(|o| match o {
-                crate::notebook::model::CellOutput::Stream { text, .. } => {
-                    text.lines().count().max(1)
-                }
-                crate::notebook::model::CellOutput::ExecuteResult { data, .. } => {
-                    let text_lines = data
-                        .get("text/plain")
-                        .map(|t| t.lines().count())
-                        .unwrap_or(0);
-                    let img_rows = if has_renderable_image(data) {
-                        get_image_data(data)
-                            .and_then(decode_base64_image)
-                            .map(|img| {
-                                image_height_rows(
-                                    &img,
-                                    app.picker.font_size(),
-                                    area.width.saturating_sub(2),
-                                ) as usize
-                            })
-                            .unwrap_or(1)
-                    } else {
-                        0
-                    };
-                    text_lines + img_rows
-                }
-                crate::notebook::model::CellOutput::Error { traceback, .. } => {
-                    traceback.len().max(1)
-                }
-                crate::notebook::model::CellOutput::DisplayData { data } => {
-                    let text_lines = data
-                        .get("text/plain")
-                        .map(|t| t.lines().count())
-                        .unwrap_or(0);
-                    let img_rows = if has_renderable_image(data) {
-                        get_image_data(data)
-                            .and_then(decode_base64_image)
-                            .map(|img| {
-                                image_height_rows(
-                                    &img,
-                                    app.picker.font_size(),
-                                    area.width.saturating_sub(2),
-                                ) as usize
-                            })
-                            .unwrap_or(1)
-                    } else {
-                        0
-                    };
-                    (text_lines + img_rows).max(if text_lines == 0 && img_rows == 0 {
-                        1
-                    } else {
-                        0
-                    })
-                }
+            .enumerate()
+            .map(|(output_idx, output)| {
+                let key = (cell.id.clone(), output_idx);
+                let layout = app.render_cache.output(
+                    &key,
+                    output,
+                    inner_width,
+                    &app.picker,
+                    app.image_placeholders,
+                );
+                let json_height = layout.json_root(output).map_or(0, |root| {
+                    app.json_views.get(&key).map_or_else(
+                        || JsonView::default().height(root),
+                        |view| view.height(root),
+                    )
+                });
+                layout.height(json_height)
             })
             .sum();
 
@@ -332,6 +178,18 @@ pub fn render_cell_list(frame: &mut Frame, app: &mut App, area: Rect) {
     }
 }
 
+/// Format an execution time for the cell border: "0.4s", "2m 05s", "1h 03m".
+pub fn format_elapsed(elapsed: std::time::Duration) -> String {
+    let secs = elapsed.as_secs();
+    if secs < 60 {
+        format!("{:.1}s", elapsed.as_secs_f64())
+    } else if secs < 3600 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h {:02}m", secs / 3600, (secs % 3600) / 60)
+    }
+}
+
 /// Render a single cell.
 fn render_cell(
     frame: &mut Frame,
@@ -348,7 +206,7 @@ fn render_cell(
     let type_indicator = match cell.cell_type {
         CellType::Code => {
             let exec_count = match (&cell.execution_state, cell.execution_count) {
-                (ExecutionState::Running, _) => "*".to_string(),
+                (ExecutionState::Running | ExecutionState::Queued, _) => "*".to_string(),
                 (_, Some(n)) => n.to_string(),
                 (_, None) => " ".to_string(),
             };
@@ -371,10 +229,16 @@ fn render_cell(
         Style::default().fg(Color::DarkGray)
     };
 
-    let exec_state_indicator = match cell.execution_state {
-        ExecutionState::Running => " [running]",
-        ExecutionState::Error => " [error]",
-        _ => "",
+    let elapsed = cell.elapsed().map(format_elapsed);
+    let exec_state_indicator = match (&cell.execution_state, elapsed) {
+        (ExecutionState::Queued, _) => " [queued]".to_string(),
+        (ExecutionState::Running, Some(t)) => format!(" [running {}]", t),
+        (ExecutionState::Running, None) => " [running]".to_string(),
+        (ExecutionState::Error, Some(t)) => format!(" [error {}]", t),
+        (ExecutionState::Error, None) => " [error]".to_string(),
+        (ExecutionState::Skipped, _) => " [skipped]".to_string(),
+        (_, Some(t)) if cell.cell_type == CellType::Code => format!(" [{}]", t),
+        _ => String::new(),
     };
 
     let has_output = !cell.outputs.is_empty();
@@ -389,22 +253,7 @@ fn render_cell(
         cell.source.lines().count().max(1) as u16
     };
 
-    // Collect output data we need before dropping the borrow on cell
-    let cell_type = cell.cell_type.clone();
-    let cell_source = cell.source.clone();
-    let cell_rendered = cell.rendered;
-    let outputs_clone = if has_output {
-        Some(cell.outputs.clone())
-    } else {
-        None
-    };
-
-    let language = app
-        .notebook
-        .metadata
-        .language
-        .as_deref()
-        .unwrap_or("python");
+    let language = app.notebook.metadata.language().unwrap_or("python");
 
     let block = Block::default()
         .borders(Borders::ALL)
@@ -418,6 +267,17 @@ fn render_cell(
         return;
     }
 
+    // Rendered markdown comes from the cache
+    let markdown = if cell.cell_type == CellType::Markdown && cell.rendered {
+        Some(
+            &app.render_cache
+                .markdown(&cell.id, &cell.source, inner.width)
+                .text,
+        )
+    } else {
+        None
+    };
+
     if has_output {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
@@ -434,11 +294,11 @@ fn render_cell(
         } else {
             render_source_direct(
                 frame,
-                &cell_source,
-                &cell_type,
+                &cell.source,
+                &cell.cell_type,
                 &app.highlighter,
                 language,
-                cell_rendered,
+                markdown,
                 chunks[0],
             );
             // Overlay search highlights on non-editing cells
@@ -451,19 +311,17 @@ fn render_cell(
         frame.render_widget(sep, chunks[1]);
 
         // Render output
-        if let Some(outputs) = &outputs_clone {
-            render_outputs(frame, app, cell_idx, outputs, chunks[2]);
-        }
+        render_outputs(frame, app, cell_idx, chunks[2]);
     } else if is_editing {
         render_editor_with_line_numbers(frame, app, inner);
     } else {
         render_source_direct(
             frame,
-            &cell_source,
-            &cell_type,
+            &cell.source,
+            &cell.cell_type,
             &app.highlighter,
             language,
-            cell_rendered,
+            markdown,
             inner,
         );
         // Overlay search highlights on non-editing cells
@@ -525,11 +383,7 @@ fn render_editor_with_line_numbers(frame: &mut Frame, app: &App, area: Rect) {
                         .add_modifier(Modifier::BOLD),
                 )));
             } else {
-                let distance = if line_idx > cursor_row {
-                    line_idx - cursor_row
-                } else {
-                    cursor_row - line_idx
-                };
+                let distance = line_idx.abs_diff(cursor_row);
                 gutter_lines.push(Line::from(Span::styled(
                     format!(
                         "{:>width$}",
@@ -545,12 +399,7 @@ fn render_editor_with_line_numbers(frame: &mut Frame, app: &App, area: Rect) {
         frame.render_widget(gutter, chunks[0]);
 
         // --- Post-process buffer for syntax highlighting ---
-        let language = app
-            .notebook
-            .metadata
-            .language
-            .as_deref()
-            .unwrap_or("python");
+        let language = app.notebook.metadata.language().unwrap_or("python");
 
         let cell = &app.notebook.cells[app.selected_cell];
         if cell.cell_type == CellType::Code {
@@ -642,13 +491,14 @@ fn detect_scroll_offset(
 }
 
 /// Render the source code of a cell (non-editing mode) from pre-extracted data.
+/// `markdown` is the rendered text of a rendered markdown cell.
 fn render_source_direct(
     frame: &mut Frame,
     source: &str,
     cell_type: &CellType,
     highlighter: &Highlighter,
     language: &str,
-    rendered: bool,
+    markdown: Option<&Text<'static>>,
     area: Rect,
 ) {
     let source = if source.is_empty() {
@@ -676,11 +526,9 @@ fn render_source_direct(
             frame.render_widget(paragraph, area);
         }
         CellType::Markdown => {
-            if rendered {
-                // Use tui-markdown to render formatted markdown
-                let core_text = tui_markdown::from_str(&source);
-                let text = convert_core_text(core_text);
-                let paragraph = Paragraph::new(text).wrap(Wrap { trim: false });
+            if let Some(text) = markdown {
+                // Formatted by tui-markdown
+                let paragraph = Paragraph::new(text.clone()).wrap(Wrap { trim: false });
                 frame.render_widget(paragraph, area);
             } else {
                 // Raw markdown source (not yet "executed")
@@ -730,213 +578,101 @@ fn apply_search_highlights(frame: &mut Frame, app: &App, cell_idx: usize, area:
     }
 }
 
-/// Render the output(s) of a cell, including inline images via Kitty graphics protocol.
-fn render_outputs(
-    frame: &mut Frame,
-    app: &mut App,
-    cell_idx: usize,
-    outputs: &[crate::notebook::model::CellOutput],
-    area: Rect,
-) {
+/// Render the output(s) of a cell from their cached layouts, including
+/// inline images via the terminal's graphics protocol.
+fn render_outputs(frame: &mut Frame, app: &mut App, cell_idx: usize, area: Rect) {
     if area.height == 0 || area.width == 0 {
         return;
     }
 
-    // We need to lay out text and images sequentially in the output area.
-    // Build a list of output segments: either text lines or an image.
-    enum OutputSegment {
-        Text(Vec<Line<'static>>),
-        Image { output_idx: usize, height: u16 },
-    }
+    let cell = &app.notebook.cells[cell_idx];
+    let table_scroll = app.table_scroll.get(&cell.id).copied().unwrap_or(0);
 
-    let mut segments: Vec<OutputSegment> = Vec::new();
-
-    for (output_idx, output) in outputs.iter().enumerate() {
-        match output {
-            crate::notebook::model::CellOutput::Stream { name, text } => {
-                let mut lines = Vec::new();
-                if name == "stderr" {
-                    match text.into_text() {
-                        Ok(parsed) => {
-                            for mut line in parsed.lines {
-                                for span in &mut line.spans {
-                                    if span.style.fg.is_none() {
-                                        span.style.fg = Some(Color::Red);
-                                    }
-                                }
-                                lines.push(line);
-                            }
-                        }
-                        Err(_) => {
-                            for line in text.lines() {
-                                lines.push(Line::from(Span::styled(
-                                    line.to_string(),
-                                    Style::default().fg(Color::Red),
-                                )));
-                            }
-                        }
-                    }
-                } else {
-                    match text.into_text() {
-                        Ok(parsed) => lines.extend(parsed.lines),
-                        Err(_) => {
-                            for line in text.lines() {
-                                lines.push(Line::from(Span::raw(line.to_string())));
-                            }
-                        }
-                    }
-                }
-                if !lines.is_empty() {
-                    segments.push(OutputSegment::Text(lines));
-                }
-            }
-            crate::notebook::model::CellOutput::ExecuteResult { data, .. } => {
-                // Render text/plain first
-                if let Some(text) = data.get("text/plain") {
-                    let mut lines = Vec::new();
-                    match text.into_text() {
-                        Ok(parsed) => {
-                            for mut line in parsed.lines {
-                                for span in &mut line.spans {
-                                    if span.style.fg.is_none() {
-                                        span.style.fg = Some(Color::Green);
-                                    }
-                                }
-                                lines.push(line);
-                            }
-                        }
-                        Err(_) => {
-                            for line in text.lines() {
-                                lines.push(Line::from(Span::styled(
-                                    line.to_string(),
-                                    Style::default().fg(Color::Green),
-                                )));
-                            }
-                        }
-                    }
-                    if !lines.is_empty() {
-                        segments.push(OutputSegment::Text(lines));
-                    }
-                }
-                // Then render image if present
-                if has_renderable_image(data) {
-                    let img_height = get_image_data(data)
-                        .and_then(decode_base64_image)
-                        .map(|img| {
-                            image_height_rows(&img, app.picker.font_size(), area.width) as u16
-                        })
-                        .unwrap_or(1);
-                    segments.push(OutputSegment::Image {
-                        output_idx,
-                        height: img_height,
-                    });
-                }
+    // Lay out text, tables, JSON trees and images sequentially in the output area
+    let mut y = area.y;
+    for (output_idx, output) in cell.outputs.iter().enumerate() {
+        let key = (cell.id.clone(), output_idx);
+        let layout = app.render_cache.output(
+            &key,
+            output,
+            area.width,
+            &app.picker,
+            app.image_placeholders,
+        );
+
+        for block in &layout.blocks {
+            let remaining = area.y + area.height - y;
+            if remaining == 0 {
+                return;
             }
-            crate::notebook::model::CellOutput::Error { traceback, .. } => {
-                let mut lines = Vec::new();
-                for tb_line in traceback {
-                    match tb_line.into_text() {
-                        Ok(parsed) => lines.extend(parsed.lines),
-                        Err(_) => {
-                            lines.push(Line::from(Span::styled(
-                                tb_line.clone(),
-                                Style::default().fg(Color::Red),
-                            )));
-                        }
-                    }
+            let h = block.height().min(remaining);
+            let seg_area = Rect::new(area.x, y, area.width, h);
+            match block {
+                LayoutBlock::Text { lines, .. } => {
+                    let paragraph =
+                        Paragraph::new(Text::from(lines.clone())).wrap(Wrap { trim: false });
+                    frame.render_widget(paragraph, seg_area);
                 }
-                if !lines.is_empty() {
-                    segments.push(OutputSegment::Text(lines));
+                LayoutBlock::Table(table) => {
+                    frame.render_widget(
+                        html_table_widget(table, area.width, table_scroll),
+                        seg_area,
+                    );
                 }
             }
-            crate::notebook::model::CellOutput::DisplayData { data } => {
-                // Render text/plain if no image, or render both if both exist
-                let has_img = has_renderable_image(data);
-
-                if let Some(text) = data.get("text/plain") {
-                    // Only show text/plain if there's no image (image is the preferred repr)
-                    if !has_img {
-                        let mut lines = Vec::new();
-                        match text.into_text() {
-                            Ok(parsed) => {
-                                for mut line in parsed.lines {
-                                    for span in &mut line.spans {
-                                        if span.style.fg.is_none() {
-                                            span.style.fg = Some(Color::Magenta);
-                                        }
-                                    }
-                                    lines.push(line);
-                                }
-                            }
-                            Err(_) => {
-                                for line in text.lines() {
-                                    lines.push(Line::from(Span::styled(
-                                        line.to_string(),
-                                        Style::default().fg(Color::Magenta),
-                                    )));
-                                }
-                            }
-                        }
-                        if !lines.is_empty() {
-                            segments.push(OutputSegment::Text(lines));
-                        }
-                    }
-                }
+            y += h;
+        }
 
-                if has_img {
-                    let img_height = get_image_data(data)
-                        .and_then(decode_base64_image)
-                        .map(|img| {
-                            image_height_rows(&img, app.picker.font_size(), area.width) as u16
-                        })
-                        .unwrap_or(1);
-                    segments.push(OutputSegment::Image {
-                        output_idx,
-                        height: img_height,
-                    });
-                }
-            }
+        if let Some(root) = layout.json_root(output) {
+            // Drawn as they are, without wrapping
+            let default_view = JsonView::default();
+            let view = app.json_views.get(&key).unwrap_or(&default_view);
+            let focused = app.mode == crate::app::Mode::JsonTree && app.json_focus == Some(key);
+            let lines = json_tree::tree_lines(root, view, focused);
+            let h = (lines.len() as u16).min(area.y + area.height - y);
+            frame.render_widget(
+                Paragraph::new(Text::from(lines)),
+                Rect::new(area.x, y, area.width, h),
+            );
+            y += h;
         }
-    }
 
-    // Now render each segment sequentially into the area
-    let mut y = area.y;
-    for segment in &segments {
         let remaining = area.y + area.height - y;
         if remaining == 0 {
-            break;
+            return;
         }
-
-        match segment {
-            OutputSegment::Text(lines) => {
-                let h = (lines.len() as u16).min(remaining);
-                let seg_area = Rect::new(area.x, y, area.width, h);
-                let paragraph =
-                    Paragraph::new(Text::from(lines.clone())).wrap(Wrap { trim: false });
-                frame.render_widget(paragraph, seg_area);
-                y += h;
+        match &mut layout.image {
+            Some(OutputImage::Placeholder(text)) => {
+                let line = Line::from(Span::styled(
+                    text.clone(),
+                    Style::default().fg(Color::DarkGray),
+                ));
+                frame.render_widget(Paragraph::new(line), Rect::new(area.x, y, area.width, 1));
+                y += 1;
             }
-            OutputSegment::Image { output_idx, height } => {
-                let h = (*height).min(remaining);
+            Some(OutputImage::Graphics {
+                rows,
+                svg,
+                protocol,
+            }) => {
+                let h = (*rows).min(remaining);
                 let seg_area = Rect::new(area.x, y, area.width, h);
 
-                // Get or create the StatefulProtocol for this image
-                let key = (cell_idx, *output_idx);
-                if !app.image_states.contains_key(&key) {
-                    // Decode the image and create a new protocol state
-                    let img_data = outputs[*output_idx].image_data();
-                    if let Some(dyn_img) = img_data.and_then(|d| decode_base64_image(d)) {
-                        let protocol = app.picker.new_resize_protocol(dyn_img);
-                        app.image_states.insert(key, protocol);
-                    }
+                // Decode the image (or take the rasterized SVG) and create
+                // its protocol state the first time it is drawn
+                if protocol.is_none() {
+                    let dyn_img = output
+                        .image_data()
+                        .and_then(decode_base64_image)
+                        .or_else(|| svg.clone());
+                    *protocol = dyn_img.map(|img| app.picker.new_resize_protocol(img));
                 }
-
-                if let Some(protocol) = app.image_states.get_mut(&key) {
-                    let image_widget = StatefulImage::default();
-                    frame.render_stateful_widget(image_widget, seg_area, protocol);
+                if let Some(protocol) = protocol {
+                    frame.render_stateful_widget(StatefulImage::default(), seg_area, protocol);
                 }
                 y += h;
             }
+            None => {}
         }
     }
 }
@@ -0,0 +1,36 @@
+use crate::ui::notify::tmux_passthrough;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use std::io::Write;
+
+/// Put `text` on the system clipboard with OSC 52, which most terminals
+/// (kitty, WezTerm, iTerm2, foot, Windows Terminal, ghostty) accept, also
+/// over SSH. Inside tmux the sequence is wrapped for passthrough like
+/// desktop notifications.
+pub fn copy(text: &str) {
+    let mut sequence = osc52_sequence(text);
+    if std::env::var_os("TMUX").is_some() {
+        sequence = tmux_passthrough(&sequence);
+    }
+
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(sequence.as_bytes());
+    let _ = stdout.flush();
+}
+
+fn osc52_sequence(text: &str) -> String {
+    format!("\x1b]52;c;{}\x1b\\", BASE64.encode(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_osc52_sequence() {
+        assert_eq!(
+            osc52_sequence(r#"["a"][0]"#),
+            "\x1b]52;c;WyJhIl1bMF0=\x1b\\"
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use crate::app::{App, Mode};
+use crate::console::ConsoleEntry;
+use crate::notebook::mime::MimeBundle;
+use crate::notebook::model::CellOutput;
+use ansi_to_tui::IntoText;
+use ratatui::Frame;
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph};
+
+/// The prompt shown before the console's input.
+const PROMPT: &str = "In [ ]: ";
+
+/// Render the scratch console: scrollback above, input prompt below.
+/// The scrollback stays pinned to its last line unless scrolled up.
+pub fn render(frame: &mut Frame, app: &mut App, area: Rect) {
+    let focused = app.mode == Mode::Console;
+    let console = &mut app.console;
+
+    let border_style = if focused {
+        Style::default().fg(Color::LightGreen)
+    } else {
+        Style::default().fg(Color::DarkGray)
+    };
+    let title = if console.running.is_some() {
+        " Console [running] "
+    } else {
+        " Console "
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(border_style)
+        .title(title);
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    if inner.height == 0 || inner.width <= PROMPT.len() as u16 {
+        return;
+    }
+
+    let input_height = (console.input.lines().len() as u16).clamp(1, inner.height.div_ceil(2));
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(input_height)])
+        .split(inner);
+
+    let lines = scrollback_lines(&console.entries);
+    let max_scroll = lines.len().saturating_sub(chunks[0].height as usize);
+    console.scroll = console.scroll.min(max_scroll);
+    let top = (max_scroll - console.scroll) as u16;
+    frame.render_widget(Paragraph::new(lines).scroll((top, 0)), chunks[0]);
+
+    let prompt_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(PROMPT.len() as u16), Constraint::Min(1)])
+        .split(chunks[1]);
+    let prompt_style = Style::default()
+        .fg(Color::Green)
+        .add_modifier(Modifier::BOLD);
+    let gutter: Vec<Line> = (0..console.input.lines().len())
+        .map(|i| {
+            let prompt = if i == 0 {
+                PROMPT.to_string()
+            } else {
+                continuation(PROMPT.len())
+            };
+            Line::from(Span::styled(prompt, prompt_style))
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(gutter), prompt_chunks[0]);
+    frame.render_widget(&console.input, prompt_chunks[1]);
+}
+
+/// The `...: ` prompt for continuation lines, right-aligned to `width`.
+fn continuation(width: usize) -> String {
+    format!("{:>width$}", "...: ", width = width)
+}
+
+/// Lay out the scrollback IPython-style: `In [n]:` inputs followed by their
+/// outputs as text. Rich outputs without text/plain show their MIME type.
+fn scrollback_lines(entries: &[ConsoleEntry]) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+    for entry in entries {
+        match entry {
+            ConsoleEntry::Input {
+                code,
+                execution_count,
+            } => {
+                if !lines.is_empty() {
+                    lines.push(Line::default());
+                }
+                let prompt = match execution_count {
+                    Some(n) => format!("In [{}]: ", n),
+                    None => "In [*]: ".to_string(),
+                };
+                let prompt_style = Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD);
+                for (i, line) in code.lines().enumerate() {
+                    let gutter = if i == 0 {
+                        prompt.clone()
+                    } else {
+                        continuation(prompt.len())
+                    };
+                    lines.push(Line::from(vec![
+                        Span::styled(gutter, prompt_style),
+                        Span::raw(line.to_string()),
+                    ]));
+                }
+            }
+            ConsoleEntry::Output(CellOutput::Stream { name, text }) => {
+                let color = (name == "stderr").then_some(Color::Red);
+                lines.extend(ansi_lines(text.rendered(), color));
+            }
+            ConsoleEntry::Output(CellOutput::ExecuteResult {
+                execution_count,
+                data,
+                ..
+            }) => {
+                let prompt = format!("Out[{}]: ", execution_count);
+                let mut result = data_lines(data, Some(Color::Green));
+                if let Some(first) = result.first_mut() {
+                    first.spans.insert(
+                        0,
+                        Span::styled(
+                            prompt,
+                            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                        ),
+                    );
+                }
+                lines.extend(result);
+            }
+            ConsoleEntry::Output(CellOutput::DisplayData { data, .. }) => {
+                lines.extend(data_lines(data, Some(Color::Magenta)));
+            }
+            ConsoleEntry::Output(CellOutput::Error { traceback, .. }) => {
+                for line in traceback {
+                    lines.extend(ansi_lines(line, Some(Color::Red)));
+                }
+            }
+        }
+    }
+    lines
+}
+
+/// text/plain of a MIME bundle, or a `[mime/type]` placeholder.
+fn data_lines(data: &MimeBundle, color: Option<Color>) -> Vec<Line<'static>> {
+    match data.text("text/plain") {
+        Some(text) => ansi_lines(text, color),
+        None => {
+            let mime = data.iter().next().map_or("no data", |(mime, _)| mime);
+            vec![Line::from(Span::styled(
+                format!("[{}]", mime),
+                Style::default().fg(Color::DarkGray),
+            ))]
+        }
+    }
+}
+
+/// Parse ANSI escapes in `text`, coloring unstyled spans with `color`.
+fn ansi_lines(text: &str, color: Option<Color>) -> Vec<Line<'static>> {
+    let mut lines = match text.into_text() {
+        Ok(parsed) => parsed.lines,
+        Err(_) => text.lines().map(|l| Line::from(l.to_string())).collect(),
+    };
+    if let Some(color) = color {
+        for span in lines.iter_mut().flat_map(|l| l.spans.iter_mut()) {
+            if span.style.fg.is_none() {
+                span.style.fg = Some(color);
+            }
+        }
+    }
+    lines
+}
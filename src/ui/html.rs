@@ -0,0 +1,875 @@
+use crate::notebook::mime::MimeBundle;
+use std::collections::BTreeMap;
+
+/// Widest a table column is drawn; longer cells are cut with `…`.
+pub const MAX_COLUMN_WIDTH: usize = 24;
+
+/// A piece of a `text/html` output.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HtmlBlock {
+    /// Everything outside tables, flattened to lines of text
+    Text(Vec<String>),
+    Table(HtmlTable),
+}
+
+/// A `<table>`, with spans expanded so every row lines up with the columns.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HtmlTable {
+    /// `<thead>` rows, or leading rows made only of `<th>` cells
+    pub header: Vec<Vec<String>>,
+    pub rows: Vec<Vec<String>>,
+    /// Leading `<th>` columns of the body (a DataFrame's index), which stay
+    /// in view while scrolling
+    pub index_columns: usize,
+}
+
+/// Which columns of a table fit in the available width.
+#[derive(Debug, PartialEq)]
+pub struct TableView {
+    /// The index columns followed by the data columns in view
+    pub columns: Vec<usize>,
+    /// Data columns are scrolled out of view on the left / right
+    pub hidden_left: bool,
+    pub hidden_right: bool,
+}
+
+impl HtmlTable {
+    /// Rows the table takes when drawn: one per header and body row.
+    pub fn height(&self) -> usize {
+        self.header.len() + self.rows.len()
+    }
+
+    pub fn column_count(&self) -> usize {
+        self.header
+            .iter()
+            .chain(&self.rows)
+            .map(Vec::len)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Columns after the index, which scroll horizontally.
+    pub fn data_columns(&self) -> usize {
+        self.column_count().saturating_sub(self.index_columns)
+    }
+
+    /// Width of each column: its widest cell, up to `MAX_COLUMN_WIDTH`.
+    pub fn column_widths(&self) -> Vec<usize> {
+        let mut widths = vec![0; self.column_count()];
+        for row in self.header.iter().chain(&self.rows) {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count().min(MAX_COLUMN_WIDTH));
+            }
+        }
+        widths
+    }
+
+    /// Lay out the table in `width` terminal columns with the data columns
+    /// scrolled left by `scroll`. Columns are one space apart and a hidden
+    /// side is marked by a one-character `…` column. At least one data
+    /// column is always shown.
+    pub fn view(&self, width: usize, scroll: usize) -> TableView {
+        let widths = self.column_widths();
+        let first_data = self.index_columns.min(widths.len());
+        let scroll = scroll.min(self.data_columns().saturating_sub(1));
+        let hidden_left = scroll > 0;
+
+        let mut columns: Vec<usize> = (0..first_data).collect();
+        let mut used: usize = widths[..first_data].iter().map(|w| w + 1).sum();
+        if hidden_left {
+            used += 2;
+        }
+        for column in first_data + scroll..widths.len() {
+            let marker = if column + 1 < widths.len() { 2 } else { 0 };
+            if columns.len() > first_data && used + widths[column] + 1 + marker > width + 1 {
+                break;
+            }
+            columns.push(column);
+            used += widths[column] + 1;
+        }
+        let hidden_right = columns.last().is_some_and(|&last| last + 1 < widths.len());
+        TableView {
+            columns,
+            hidden_left,
+            hidden_right,
+        }
+    }
+}
+
+/// Cut `text` to `width` characters, ending with `…` when shortened.
+pub fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut cut: String = text.chars().take(width.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+/// The blocks to show for an output's `text/html` instead of its
+/// `text/plain`: whenever the HTML holds a table (pandas DataFrames send a
+/// cramped text/plain alongside), or when text/plain is missing or only the
+/// default `<... object>` repr.
+pub fn displayed_blocks(data: &MimeBundle) -> Option<Vec<HtmlBlock>> {
+    let blocks = parse(data.text("text/html")?);
+    let has_table = blocks.iter().any(|b| matches!(b, HtmlBlock::Table(_)));
+    let plain_is_placeholder = data.text("text/plain").is_none_or(is_placeholder_repr);
+    (has_table || plain_is_placeholder).then_some(blocks)
+}
+
+/// Whether a text/plain is only the default repr of a display object
+/// (`<IPython.core.display.HTML object>`, `<VegaLite 4 object>` followed by
+/// a hint), which says nothing about the content.
+pub fn is_placeholder_repr(text: &str) -> bool {
+    let first_line = text.lines().next().unwrap_or_default().trim_end();
+    first_line.starts_with('<') && first_line.ends_with("object>")
+}
+
+/// Split HTML into tables and text. Paragraphs, headings and list items go
+/// on their own lines, links show their target after the text, and
+/// `<style>` / `<script>` contents are dropped. Malformed markup is read as
+/// best it can be rather than rejected.
+pub fn parse(html: &str) -> Vec<HtmlBlock> {
+    let mut parser = Parser::default();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        parser.text(&rest[..start]);
+        rest = &rest[start..];
+        rest = match read_tag(rest) {
+            Some((tag, after)) => {
+                if let Some(tag) = tag {
+                    parser.tag(tag);
+                }
+                after
+            }
+            None => {
+                parser.text("<");
+                &rest[1..]
+            }
+        };
+    }
+    parser.text(rest);
+    parser.finish()
+}
+
+struct Tag<'a> {
+    /// Lowercased element name
+    name: String,
+    closing: bool,
+    attributes: &'a str,
+}
+
+/// Read the tag at the start of `input` (which begins with `<`). Returns
+/// None if it isn't markup, and a None tag for comments, doctypes and
+/// processing instructions.
+fn read_tag(input: &str) -> Option<(Option<Tag<'_>>, &str)> {
+    if let Some(comment) = input.strip_prefix("<!--") {
+        let end = comment.find("-->").map_or(comment.len(), |i| i + 3);
+        return Some((None, &comment[end..]));
+    }
+    if input.starts_with("<!") || input.starts_with("<?") {
+        let end = input.find('>').map_or(input.len(), |i| i + 1);
+        return Some((None, &input[end..]));
+    }
+
+    let body = &input[1..];
+    let (closing, body) = match body.strip_prefix('/') {
+        Some(body) => (true, body),
+        None => (false, body),
+    };
+    if !body.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    // Find the closing `>`, skipping any inside quoted attribute values
+    let mut quote = None;
+    let mut end = body.len();
+    for (i, c) in body.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => {
+                end = i;
+                break;
+            }
+            _ => {}
+        }
+    }
+    let inner = &body[..end];
+    let rest = body.get(end + 1..).unwrap_or("");
+    let name_end = inner
+        .find(|c: char| c.is_whitespace() || c == '/')
+        .unwrap_or(inner.len());
+    let tag = Tag {
+        name: inner[..name_end].to_ascii_lowercase(),
+        closing,
+        attributes: &inner[name_end..],
+    };
+    Some((Some(tag), rest))
+}
+
+/// The value of attribute `name`, with entities decoded.
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let mut rest = attributes;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        if rest.is_empty() {
+            return None;
+        }
+        let key_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '/')
+            .unwrap_or(rest.len());
+        let key = &rest[..key_end];
+        rest = rest[key_end..].trim_start();
+        let mut value = "";
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            let (raw, remaining) = match after.chars().next() {
+                Some(q @ ('"' | '\'')) => {
+                    let inner = &after[1..];
+                    let end = inner.find(q).unwrap_or(inner.len());
+                    (&inner[..end], inner.get(end + 1..).unwrap_or(""))
+                }
+                _ => {
+                    let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                    (&after[..end], &after[end..])
+                }
+            };
+            value = raw;
+            rest = remaining;
+        }
+        if key.eq_ignore_ascii_case(name) {
+            return Some(decode_entities(value));
+        }
+        if key.is_empty() {
+            // Stray `=`; skip a character so the loop always advances
+            rest = rest.get(1..).unwrap_or("");
+        }
+    }
+}
+
+/// Replace character references (`&amp;`, `&#39;`, `&#x27;`, ...).
+/// Unknown names are left as written.
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| Some((entity_char(&rest[1..end + 1])?, end + 2)));
+        match entity {
+            Some((c, len)) => {
+                decoded.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn entity_char(name: &str) -> Option<char> {
+    if let Some(number) = name.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "times" => '×',
+        "hellip" => '…',
+        "mdash" => '—',
+        "ndash" => '–',
+        "middot" => '·',
+        "bull" => '•',
+        "copy" => '©',
+        "deg" => '°',
+        _ => return None,
+    })
+}
+
+/// A table being read.
+#[derive(Default)]
+struct TableBuilder {
+    table: HtmlTable,
+    in_head: bool,
+    seen_head: bool,
+    row: Option<Row>,
+    /// Open `<td>` / `<th>`
+    cell: Option<CellBuilder>,
+    /// Cells spanning down into later rows: column -> (rows left, is `<th>`)
+    row_spans: BTreeMap<usize, (usize, bool)>,
+    /// Depth of tables nested inside a cell, whose structure is ignored
+    nested: usize,
+}
+
+#[derive(Default)]
+struct Row {
+    cells: Vec<String>,
+    /// How many cells from the start are `<th>`
+    leading_th: usize,
+    all_th: bool,
+}
+
+struct CellBuilder {
+    text: String,
+    th: bool,
+    colspan: usize,
+    rowspan: usize,
+}
+
+impl Row {
+    fn push(&mut self, text: String, th: bool) {
+        if th && self.leading_th == self.cells.len() {
+            self.leading_th += 1;
+        }
+        self.all_th &= th;
+        self.cells.push(text);
+    }
+}
+
+impl TableBuilder {
+    /// Fill the columns covered by cells spanning down from earlier rows,
+    /// up to the next free column (or through the last span at row end).
+    fn fill_spans(&mut self, row_end: bool) {
+        let Some(row) = self.row.as_mut() else {
+            return;
+        };
+        loop {
+            let column = row.cells.len();
+            if let Some((left, th)) = self.row_spans.get_mut(&column) {
+                let th = *th;
+                *left -= 1;
+                if *left == 0 {
+                    self.row_spans.remove(&column);
+                }
+                row.push(String::new(), th);
+            } else if row_end && self.row_spans.keys().any(|&c| c > column) {
+                row.push(String::new(), false);
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn start_row(&mut self) {
+        self.end_row();
+        self.row = Some(Row {
+            all_th: true,
+            ..Row::default()
+        });
+    }
+
+    fn start_cell(&mut self, th: bool, attributes: &str) {
+        self.end_cell();
+        if self.row.is_none() {
+            self.start_row();
+        }
+        self.fill_spans(false);
+        let span = |name| {
+            attribute(attributes, name)
+                .and_then(|v| v.trim().parse::<usize>().ok())
+                .unwrap_or(1)
+                .clamp(1, 1000)
+        };
+        self.cell = Some(CellBuilder {
+            text: String::new(),
+            th,
+            colspan: span("colspan"),
+            rowspan: span("rowspan"),
+        });
+    }
+
+    fn end_cell(&mut self) {
+        let (Some(cell), Some(row)) = (self.cell.take(), self.row.as_mut()) else {
+            return;
+        };
+        let first_column = row.cells.len();
+        row.push(cell.text.trim().to_string(), cell.th);
+        for _ in 1..cell.colspan {
+            row.push(String::new(), cell.th);
+        }
+        if cell.rowspan > 1 {
+            for column in first_column..first_column + cell.colspan {
+                self.row_spans.insert(column, (cell.rowspan - 1, cell.th));
+            }
+        }
+    }
+
+    fn end_row(&mut self) {
+        self.end_cell();
+        self.fill_spans(true);
+        let Some(row) = self.row.take() else {
+            return;
+        };
+        if row.cells.is_empty() {
+            return;
+        }
+        let is_header =
+            self.in_head || (!self.seen_head && row.all_th && self.table.rows.is_empty());
+        if is_header {
+            self.table.header.push(row.cells);
+        } else {
+            if self.table.rows.is_empty() {
+                self.table.index_columns = row.leading_th;
+            }
+            self.table.rows.push(row.cells);
+        }
+    }
+
+    fn finish(mut self) -> HtmlTable {
+        self.end_row();
+        self.table
+    }
+}
+
+#[derive(Default)]
+struct Parser {
+    blocks: Vec<HtmlBlock>,
+    /// Finished lines of the current text block
+    lines: Vec<String>,
+    line: String,
+    /// Whitespace was seen since the last character written
+    pending_space: bool,
+    /// Depth inside `<pre>`, where whitespace is kept
+    pre: usize,
+    /// Depth inside `<style>` / `<script>`, whose contents are dropped
+    skip: usize,
+    /// Open lists: None for `<ul>`, the next number for `<ol>`
+    lists: Vec<Option<usize>>,
+    /// Open links: target and the text written so far
+    links: Vec<(String, String)>,
+    table: Option<TableBuilder>,
+}
+
+impl Parser {
+    fn text(&mut self, raw: &str) {
+        if self.skip > 0 || raw.is_empty() {
+            return;
+        }
+        let text = decode_entities(raw);
+        let in_cell = self.table.as_ref().is_some_and(|t| t.cell.is_some());
+        if self.table.is_some() && !in_cell {
+            // Whitespace between rows and cells, or a caption
+            return;
+        }
+        if self.pre > 0 && !in_cell {
+            for (i, part) in text.split('\n').enumerate() {
+                if i > 0 {
+                    self.new_line();
+                }
+                self.line.push_str(part);
+            }
+            return;
+        }
+        for c in text.chars() {
+            if c.is_whitespace() && c != '\u{a0}' {
+                self.pending_space = true;
+            } else {
+                self.push_char(if c == '\u{a0}' { ' ' } else { c });
+            }
+        }
+    }
+
+    fn push_char(&mut self, c: char) {
+        let pending_space = std::mem::take(&mut self.pending_space);
+        let target = match self.table.as_mut().and_then(|t| t.cell.as_mut()) {
+            Some(cell) => &mut cell.text,
+            None => &mut self.line,
+        };
+        let at_start = target.trim_start().is_empty() || target.ends_with(' ');
+        if pending_space && !at_start {
+            target.push(' ');
+        }
+        target.push(c);
+        for (_, text) in &mut self.links {
+            if pending_space && !text.is_empty() {
+                text.push(' ');
+            }
+            text.push(c);
+        }
+    }
+
+    /// Write a separator in the current cell or line.
+    fn push_str(&mut self, text: &str) {
+        self.pending_space = false;
+        match self.table.as_mut().and_then(|t| t.cell.as_mut()) {
+            Some(cell) => cell.text.push_str(text),
+            None => self.line.push_str(text),
+        }
+    }
+
+    /// Finish the current line, even if empty (`<br>`).
+    fn new_line(&mut self) {
+        let line = std::mem::take(&mut self.line);
+        self.lines.push(line.trim_end().to_string());
+        self.pending_space = false;
+    }
+
+    /// Finish the current line if anything is on it.
+    fn end_line(&mut self) {
+        if !self.line.trim().is_empty() {
+            self.new_line();
+        }
+        self.line.clear();
+        self.pending_space = false;
+    }
+
+    /// Separate what follows from the text before by a blank line.
+    fn paragraph_break(&mut self) {
+        self.end_line();
+        if self.lines.last().is_some_and(|l| !l.is_empty()) {
+            self.lines.push(String::new());
+        }
+    }
+
+    /// Move the text read so far into its own block.
+    fn flush_text(&mut self) {
+        self.end_line();
+        let mut lines = std::mem::take(&mut self.lines);
+        while lines.last().is_some_and(String::is_empty) {
+            lines.pop();
+        }
+        let start = lines
+            .iter()
+            .position(|l| !l.is_empty())
+            .unwrap_or(lines.len());
+        lines.drain(..start);
+        if !lines.is_empty() {
+            self.blocks.push(HtmlBlock::Text(lines));
+        }
+    }
+
+    fn tag(&mut self, tag: Tag) {
+        let name = tag.name.as_str();
+        if matches!(name, "style" | "script") {
+            if tag.closing {
+                self.skip = self.skip.saturating_sub(1);
+            } else if !tag.attributes.trim_end().ends_with('/') {
+                self.skip += 1;
+            }
+            return;
+        }
+        if self.skip > 0 {
+            return;
+        }
+        if self.table.is_some() {
+            self.table_tag(&tag);
+            return;
+        }
+
+        match (name, tag.closing) {
+            ("table", false) => {
+                self.flush_text();
+                self.table = Some(TableBuilder::default());
+            }
+            ("br", _) => self.new_line(),
+            ("p" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "hr" | "blockquote", _) => {
+                self.paragraph_break()
+            }
+            ("pre", false) => {
+                self.paragraph_break();
+                self.pre += 1;
+            }
+            ("pre", true) => {
+                self.pre = self.pre.saturating_sub(1);
+                self.paragraph_break();
+            }
+            ("ul", false) => {
+                self.end_line();
+                self.lists.push(None);
+            }
+            ("ol", false) => {
+                self.end_line();
+                let start = attribute(tag.attributes, "start")
+                    .and_then(|s| s.trim().parse().ok())
+                    .unwrap_or(1);
+                self.lists.push(Some(start));
+            }
+            ("ul" | "ol", true) => {
+                self.end_line();
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.paragraph_break();
+                }
+            }
+            ("li", false) => {
+                self.end_line();
+                let indent = "  ".repeat(self.lists.len().saturating_sub(1));
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    _ => "• ".to_string(),
+                };
+                self.push_str(&format!("{}{}", indent, marker));
+            }
+            ("li" | "div" | "tr" | "dt" | "dd" | "section" | "header" | "footer", _) => {
+                self.end_line()
+            }
+            ("a", false) => {
+                let href = attribute(tag.attributes, "href").unwrap_or_default();
+                self.links.push((href, String::new()));
+            }
+            ("a", true) => self.end_link(),
+            _ => {}
+        }
+    }
+
+    /// Close a link, adding its target after the text unless it says
+    /// nothing more (same text, in-page anchor, script).
+    fn end_link(&mut self) {
+        let Some((href, text)) = self.links.pop() else {
+            return;
+        };
+        let shown = href.is_empty()
+            || href.starts_with('#')
+            || href.starts_with("javascript:")
+            || text.trim() == href;
+        if !shown {
+            self.pending_space = true;
+            for c in format!("({})", href).chars() {
+                self.push_char(c);
+            }
+        }
+    }
+
+    fn table_tag(&mut self, tag: &Tag) {
+        let Some(table) = self.table.as_mut() else {
+            return;
+        };
+        let name = tag.name.as_str();
+        if table.nested > 0 || (name == "table" && !tag.closing) {
+            match (name, tag.closing) {
+                ("table", false) => table.nested += 1,
+                ("table", true) => table.nested -= 1,
+                ("br" | "tr" | "td" | "th", _) => self.pending_space = true,
+                _ => {}
+            }
+            return;
+        }
+
+        match (name, tag.closing) {
+            ("table", true) => {
+                let table = self.table.take().map(TableBuilder::finish);
+                if let Some(table) = table.filter(|t| !t.header.is_empty() || !t.rows.is_empty()) {
+                    self.blocks.push(HtmlBlock::Table(table));
+                }
+            }
+            ("thead", false) => {
+                table.end_row();
+                table.in_head = true;
+                table.seen_head = true;
+            }
+            ("thead", true) => {
+                table.end_row();
+                table.in_head = false;
+            }
+            ("tbody" | "tfoot", _) => {
+                table.end_row();
+                table.in_head = false;
+            }
+            ("tr", false) => table.start_row(),
+            ("tr", true) => table.end_row(),
+            ("td" | "th", false) => table.start_cell(name == "th", tag.attributes),
+            ("td" | "th", true) => table.end_cell(),
+            ("br" | "p" | "div" | "li", _) => self.pending_space = true,
+            ("a", false) => {
+                let href = attribute(tag.attributes, "href").unwrap_or_default();
+                self.links.push((href, String::new()));
+            }
+            ("a", true) => self.end_link(),
+            _ => {}
+        }
+        self.pending_space |= matches!(name, "td" | "th");
+    }
+
+    fn finish(mut self) -> Vec<HtmlBlock> {
+        if let Some(table) = self.table.take().map(TableBuilder::finish)
+            && (!table.header.is_empty() || !table.rows.is_empty())
+        {
+            self.blocks.push(HtmlBlock::Table(table));
+        }
+        self.flush_text();
+        self.blocks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(row: &[&str]) -> Vec<String> {
+        row.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_dataframe_table() {
+        let html = r#"<div>
+<style scoped>
+    .dataframe tbody tr th:only-of-type { vertical-align: middle; }
+</style>
+<table border="1" class="dataframe">
+  <thead>
+    <tr style="text-align: right;">
+      <th></th>
+      <th>name</th>
+      <th>score</th>
+    </tr>
+  </thead>
+  <tbody>
+    <tr>
+      <th>0</th>
+      <td>Tom &amp; Jerry</td>
+      <td>1.5</td>
+    </tr>
+    <tr>
+      <th>1</th>
+      <td>&lt;NA&gt;</td>
+      <td>...</td>
+    </tr>
+  </tbody>
+</table>
+<p>2 rows &times; 2 columns</p>
+</div>"#;
+        let blocks = parse(html);
+        assert_eq!(
+            blocks,
+            [
+                HtmlBlock::Table(HtmlTable {
+                    header: vec![cells(&["", "name", "score"])],
+                    rows: vec![
+                        cells(&["0", "Tom & Jerry", "1.5"]),
+                        cells(&["1", "<NA>", "..."])
+                    ],
+                    index_columns: 1,
+                }),
+                HtmlBlock::Text(vec!["2 rows × 2 columns".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_table_spans() {
+        // A MultiIndex DataFrame: the outer index level spans two rows and
+        // the column header spans two columns
+        let html = "<table><thead>\
+            <tr><th></th><th></th><th colspan=\"2\">x</th></tr>\
+            </thead><tbody>\
+            <tr><th rowspan=\"2\">a</th><th>1</th><td>10</td><td>11</td></tr>\
+            <tr><th>2</th><td>20</td><td>21</td></tr>\
+            </tbody></table>";
+        let [HtmlBlock::Table(table)]: [HtmlBlock; 1] = parse(html).try_into().unwrap() else {
+            panic!("Expected a single table");
+        };
+        assert_eq!(table.header, [cells(&["", "", "x", ""])]);
+        assert_eq!(
+            table.rows,
+            [
+                cells(&["a", "1", "10", "11"]),
+                cells(&["", "2", "20", "21"])
+            ]
+        );
+        assert_eq!(table.index_columns, 2);
+    }
+
+    #[test]
+    fn test_parse_text_fallback() {
+        let html = "<h3>Results</h3><p>See <a href=\"https://example.com/doc\">the docs</a> \
+            or <a href=\"https://x.org\">https://x.org</a>.</p>\
+            <ul><li>one</li><li>two<ol><li>a</li></ol></li></ul>\
+            line<br>break<!-- hidden --><script>alert(1)</script>";
+        assert_eq!(
+            parse(html),
+            [HtmlBlock::Text(
+                [
+                    "Results",
+                    "",
+                    "See the docs (https://example.com/doc) or https://x.org.",
+                    "",
+                    "• one",
+                    "• two",
+                    "  1. a",
+                    "",
+                    "line",
+                    "break",
+                ]
+                .map(String::from)
+                .to_vec()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_view_pins_index_and_marks_hidden_columns() {
+        let table = HtmlTable {
+            header: vec![cells(&["", "aaaa", "bbbb", "cccc", "dddd"])],
+            rows: vec![cells(&["0", "1", "2", "3", "4"])],
+            index_columns: 1,
+        };
+        // "0 aaaa bbbb … " fits in 14
+        assert_eq!(
+            table.view(14, 0),
+            TableView {
+                columns: vec![0, 1, 2],
+                hidden_left: false,
+                hidden_right: true,
+            }
+        );
+        assert_eq!(
+            table.view(14, 2),
+            TableView {
+                columns: vec![0, 3, 4],
+                hidden_left: true,
+                hidden_right: false,
+            }
+        );
+        // Scrolling stops at the last data column, which is always shown
+        assert_eq!(table.view(1, 10).columns, [0, 4]);
+    }
+
+    #[test]
+    fn test_displayed_blocks_prefers_tables_and_placeholders() {
+        let bundle = |entries: serde_json::Value| {
+            MimeBundle::from_media(&serde_json::from_value(entries).unwrap())
+        };
+        let table = bundle(serde_json::json!({
+            "text/html": "<table><tr><td>1</td></tr></table>",
+            "text/plain": "   0\n0  1",
+        }));
+        assert!(displayed_blocks(&table).is_some());
+
+        let styled = bundle(serde_json::json!({
+            "text/html": "<b>bold</b>",
+            "text/plain": "bold",
+        }));
+        assert_eq!(displayed_blocks(&styled), None);
+
+        let object = bundle(serde_json::json!({
+            "text/html": "<b>bold</b>",
+            "text/plain": "<IPython.core.display.HTML object>",
+        }));
+        assert_eq!(
+            displayed_blocks(&object),
+            Some(vec![HtmlBlock::Text(vec!["bold".to_string()])])
+        );
+        assert_eq!(truncate("abcdef", 4), "abc…");
+        assert_eq!(truncate("abc", 4), "abc");
+    }
+}
@@ -0,0 +1,232 @@
+use crate::notebook::mime::MimeBundle;
+use anyhow::bail;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use image::{DynamicImage, ImageReader, RgbaImage};
+use ratatui_image::picker::{Picker, ProtocolType};
+use resvg::{tiny_skia, usvg};
+use std::io::Cursor;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+
+/// How images in outputs are drawn (`--image-protocol`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageProtocol {
+    /// Whatever the terminal answered to the startup query: Kitty, Sixel or
+    /// iTerm2 graphics, or Unicode half blocks when it supports none
+    Auto,
+    /// Force a graphics protocol (or half blocks)
+    Graphics(ProtocolType),
+    /// No graphics: a one-line `[image 640x480 png]` placeholder
+    Placeholder,
+}
+
+impl FromStr for ImageProtocol {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> anyhow::Result<Self> {
+        Ok(match name {
+            "auto" => ImageProtocol::Auto,
+            "kitty" => ImageProtocol::Graphics(ProtocolType::Kitty),
+            "sixel" => ImageProtocol::Graphics(ProtocolType::Sixel),
+            "iterm2" => ImageProtocol::Graphics(ProtocolType::Iterm2),
+            "halfblocks" => ImageProtocol::Graphics(ProtocolType::Halfblocks),
+            "none" => ImageProtocol::Placeholder,
+            _ => bail!(
+                "Unknown image protocol: {} (expected auto, kitty, sixel, iterm2, halfblocks or none)",
+                name
+            ),
+        })
+    }
+}
+
+/// Query the terminal for its graphics protocol and font size, then apply
+/// `protocol` on top. Must run after entering the alternate screen and
+/// before reading terminal events.
+///
+/// Inside tmux, ratatui-image turns on `allow-passthrough` for the pane and
+/// wraps its escape sequences so they reach the outer terminal; the outer
+/// terminal is guessed from the environment (`KITTY_WINDOW_ID`, ...).
+pub fn query_picker(protocol: ImageProtocol) -> Picker {
+    // Without a usable answer, assume a font size and let ratatui-image
+    // guess the protocol from the environment (half blocks if nothing hints)
+    let mut picker = Picker::from_query_stdio().unwrap_or_else(|_| Picker::from_fontsize((8, 16)));
+    if let ImageProtocol::Graphics(protocol_type) = protocol {
+        picker.set_protocol_type(protocol_type);
+    }
+    picker
+}
+
+/// The one-line stand-in for an output's image: `[image 640x480 png]`, with
+/// the size read from the image header (or the SVG's root element). Just
+/// `[image png]` if that fails.
+pub fn placeholder(data: &MimeBundle) -> String {
+    let Some((mime, content)) = ["image/png", "image/jpeg", "image/svg+xml"]
+        .into_iter()
+        .find_map(|mime| Some((mime, data.text(mime)?)))
+    else {
+        return "[image]".to_string();
+    };
+    let (format, size) = match mime {
+        "image/svg+xml" => ("svg", svg_dimensions(content)),
+        _ => (mime.trim_start_matches("image/"), image_dimensions(content)),
+    };
+    match size {
+        Some((width, height)) => format!("[image {}x{} {}]", width, height, format),
+        None => format!("[image {}]", format),
+    }
+}
+
+/// Check if an output's data map contains an image we can render.
+pub fn has_renderable_image(data: &MimeBundle) -> bool {
+    data.contains("image/png") || data.contains("image/jpeg") || data.contains("image/svg+xml")
+}
+
+/// Get the base64 PNG or JPEG data from an output's data map.
+pub fn get_image_data(data: &MimeBundle) -> Option<&str> {
+    data.text("image/png").or_else(|| data.text("image/jpeg"))
+}
+
+/// Width and height of a base64-encoded image, without decoding the pixels.
+pub fn image_dimensions(b64: &str) -> Option<(u32, u32)> {
+    let bytes = base64_decode(b64)?;
+    ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()
+}
+
+/// Size of an SVG document in pixels, from its `width`/`height`/`viewBox`.
+fn svg_dimensions(svg: &str) -> Option<(u32, u32)> {
+    let size = usvg::Tree::from_str(svg, &usvg::Options::default())
+        .ok()?
+        .size();
+    Some((size.width().round() as u32, size.height().round() as u32))
+}
+
+/// Start loading the system fonts on a background thread, so that the
+/// first SVG with text doesn't hold up a frame while they are scanned.
+pub fn preload_fonts() {
+    std::thread::spawn(system_fonts);
+}
+
+/// The system fonts, loaded once (usually by `preload_fonts`).
+fn system_fonts() -> Arc<usvg::fontdb::Database> {
+    static FONTS: OnceLock<Arc<usvg::fontdb::Database>> = OnceLock::new();
+    FONTS
+        .get_or_init(|| {
+            let mut fonts = usvg::fontdb::Database::new();
+            fonts.load_system_fonts();
+            Arc::new(fonts)
+        })
+        .clone()
+}
+
+/// Rasterize an SVG document at its own size, or scaled down to fit in
+/// `max_size` (width, height) pixels. The background is white, as in
+/// Jupyter, so that dark strokes on a transparent canvas stay visible.
+pub fn rasterize_svg(svg: &str, max_size: (u32, u32)) -> Option<DynamicImage> {
+    let options = usvg::Options {
+        fontdb: system_fonts(),
+        ..Default::default()
+    };
+    let tree = usvg::Tree::from_str(svg, &options).ok()?;
+    let size = tree.size();
+    let scale = (max_size.0 as f32 / size.width())
+        .min(max_size.1 as f32 / size.height())
+        .min(1.0);
+    let width = (size.width() * scale).ceil() as u32;
+    let height = (size.height() * scale).ceil() as u32;
+
+    let mut pixmap = tiny_skia::Pixmap::new(width, height)?;
+    pixmap.fill(tiny_skia::Color::WHITE);
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+    // Opaque, so the premultiplied pixels are plain RGBA
+    RgbaImage::from_raw(width, height, pixmap.take()).map(DynamicImage::ImageRgba8)
+}
+
+/// Decode a base64-encoded image string into a DynamicImage.
+pub fn decode_base64_image(b64: &str) -> Option<DynamicImage> {
+    let bytes = base64_decode(b64)?;
+    ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .ok()?
+        .decode()
+        .ok()
+}
+
+/// Decode base64 image data, ignoring the line breaks Jupyter sometimes
+/// includes.
+fn base64_decode(b64: &str) -> Option<Vec<u8>> {
+    let cleaned: String = b64.chars().filter(|c| !c.is_whitespace()).collect();
+    BASE64.decode(cleaned).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 1x1 PNG
+    const PIXEL_PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+
+    /// A red square on the left half of a 40x20 canvas
+    const SQUARE_SVG: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" width="40" height="20"><rect width="20" height="20" fill="red"/></svg>"#;
+
+    #[test]
+    fn test_parse_image_protocol() {
+        assert_eq!(
+            "auto".parse::<ImageProtocol>().unwrap(),
+            ImageProtocol::Auto
+        );
+        assert_eq!(
+            "sixel".parse::<ImageProtocol>().unwrap(),
+            ImageProtocol::Graphics(ProtocolType::Sixel)
+        );
+        assert_eq!(
+            "none".parse::<ImageProtocol>().unwrap(),
+            ImageProtocol::Placeholder
+        );
+        assert!("png".parse::<ImageProtocol>().is_err());
+    }
+
+    #[test]
+    fn test_placeholder() {
+        let bundle = |entries: serde_json::Value| {
+            MimeBundle::from_media(&serde_json::from_value(entries).unwrap())
+        };
+        let png = bundle(serde_json::json!({
+            "image/png": format!("{}\n", PIXEL_PNG),
+            "text/plain": "<Figure size 640x480 with 1 Axes>",
+        }));
+        assert_eq!(placeholder(&png), "[image 1x1 png]");
+
+        let broken = bundle(serde_json::json!({"image/jpeg": "AAAA"}));
+        assert_eq!(placeholder(&broken), "[image jpeg]");
+        assert_eq!(
+            decode_base64_image(PIXEL_PNG).map(|img| img.width()),
+            Some(1)
+        );
+
+        let svg = bundle(serde_json::json!({"image/svg+xml": SQUARE_SVG}));
+        assert_eq!(placeholder(&svg), "[image 40x20 svg]");
+    }
+
+    #[test]
+    fn test_rasterize_svg() {
+        let image = rasterize_svg(SQUARE_SVG, (400, 400)).unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), (40, 20));
+        assert_eq!(image.get_pixel(10, 10).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(30, 10).0, [255, 255, 255, 255]);
+
+        let image = rasterize_svg(SQUARE_SVG, (10, 400)).unwrap();
+        assert_eq!((image.width(), image.height()), (10, 5));
+        let image = rasterize_svg(SQUARE_SVG, (400, 4)).unwrap();
+        assert_eq!((image.width(), image.height()), (8, 4));
+        assert!(rasterize_svg("<svg", (400, 400)).is_none());
+    }
+}
@@ -0,0 +1,69 @@
+use crate::json_tree::{JsonView, MAX_TREE_HEIGHT, PathSegment, row_matches};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use serde_json::Value;
+
+/// Lay out the rows of a JSON tree in view: `▾`/`▸` for expanded and
+/// collapsed containers with their size, scalars colored by type. With
+/// `focused`, the cursor row is highlighted; rows matching the last search
+/// always are.
+pub fn tree_lines(root: &Value, view: &JsonView, focused: bool) -> Vec<Line<'static>> {
+    let rows = view.rows(root);
+    rows.iter()
+        .enumerate()
+        .skip(view.scroll)
+        .take(MAX_TREE_HEIGHT)
+        .map(|(i, row)| {
+            let mut spans = vec![Span::raw("  ".repeat(row.path.len()))];
+            let marker = match row.value {
+                Value::Object(_) | Value::Array(_) if row.expanded => "▾ ",
+                Value::Object(_) | Value::Array(_) => "▸ ",
+                _ => "  ",
+            };
+            spans.push(Span::styled(marker, Style::default().fg(Color::DarkGray)));
+            match row.path.last() {
+                Some(PathSegment::Key(key)) => spans.push(Span::styled(
+                    format!("{}: ", Value::String(key.clone())),
+                    Style::default().fg(Color::Cyan),
+                )),
+                Some(PathSegment::Index(index)) => spans.push(Span::styled(
+                    format!("{}: ", index),
+                    Style::default().fg(Color::DarkGray),
+                )),
+                None => {}
+            }
+            spans.push(value_span(row.value));
+
+            if focused && i == view.cursor {
+                Line::from(spans).style(Style::default().bg(Color::DarkGray))
+            } else if view
+                .query
+                .as_deref()
+                .is_some_and(|query| row_matches(&row.path, row.value, query))
+            {
+                let highlight = Style::default()
+                    .bg(Color::Yellow)
+                    .fg(Color::Black)
+                    .add_modifier(Modifier::BOLD);
+                for span in spans.iter_mut().skip(2) {
+                    span.style = span.style.patch(highlight);
+                }
+                Line::from(spans)
+            } else {
+                Line::from(spans)
+            }
+        })
+        .collect()
+}
+
+/// A scalar as JSON, or a container's size.
+fn value_span(value: &Value) -> Span<'static> {
+    let (text, color) = match value {
+        Value::Object(map) => (format!("{{{}}}", map.len()), Color::DarkGray),
+        Value::Array(items) => (format!("[{}]", items.len()), Color::DarkGray),
+        Value::String(_) => (value.to_string(), Color::Green),
+        Value::Number(_) => (value.to_string(), Color::LightBlue),
+        Value::Bool(_) | Value::Null => (value.to_string(), Color::Magenta),
+    };
+    Span::styled(text, Style::default().fg(color))
+}
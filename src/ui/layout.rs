@@ -1,61 +1,305 @@
 use crate::app::{App, Mode};
-use crate::ui::{cell, statusbar};
+use crate::ui::{cell, console, statusbar};
 use ratatui::Frame;
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
 
 /// Render the full application layout.
 pub fn render(frame: &mut Frame, app: &mut App) {
-    let has_completions = !app.completions.is_empty();
-    let completion_height = if has_completions {
-        // Show up to 8 completions + 1 for border
-        (app.completions.len().min(8) + 2) as u16
+    let completion_count = app.completion.as_ref().map_or(0, |c| c.visible.len());
+    let has_completions = completion_count > 0;
+    // Show up to 8 completions + 2 for borders
+    let completion_height = (completion_count.min(8) + 2) as u16;
+
+    let has_input_prompt = app.input_prompt.is_some();
+
+    let mut constraints = vec![Constraint::Min(1)]; // Cell area
+    if has_completions {
+        constraints.push(Constraint::Length(completion_height)); // Completion panel
+    }
+    if has_input_prompt {
+        constraints.push(Constraint::Length(1)); // Kernel input prompt
+    }
+    constraints.push(Constraint::Length(1)); // Status bar
+    constraints.push(Constraint::Length(1)); // Command line
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(constraints)
+        .split(frame.area());
+
+    // Render cells in the main area, with the console below them when shown
+    let cell_area = if app.console.visible {
+        let console_height = (chunks[0].height / 3).max(6);
+        let split = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(console_height)])
+            .split(chunks[0]);
+        console::render(frame, app, split[1]);
+        split[0]
     } else {
-        0
+        chunks[0]
+    };
+    // The variable explorer takes the right side of the cell area
+    let cell_area = if app.variables.visible {
+        let panel_width = (cell_area.width * 2 / 5)
+            .clamp(30, 80)
+            .min(cell_area.width / 2);
+        let split = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(1), Constraint::Length(panel_width)])
+            .split(cell_area);
+        render_variables_panel(frame, app, split[1]);
+        split[0]
+    } else {
+        cell_area
     };
+    cell::render_cell_list(frame, app, cell_area);
 
-    let chunks = if has_completions {
-        Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([
-                Constraint::Min(1),                    // Cell area
-                Constraint::Length(completion_height), // Completion panel
-                Constraint::Length(1),                 // Status bar
-                Constraint::Length(1),                 // Command line
+    let mut next = 1;
+    if has_completions {
+        render_completion_panel(frame, app, chunks[next]);
+        next += 1;
+    }
+    if has_input_prompt {
+        render_input_prompt(frame, app, chunks[next]);
+        next += 1;
+    }
+    statusbar::render(frame, app, chunks[next]);
+    render_command_line(frame, app, chunks[next + 1]);
+
+    if app.kernel_picker.is_some() {
+        render_kernel_picker(frame, app, cell_area);
+    }
+    if app.pager.is_some() {
+        render_pager(frame, app, cell_area);
+    }
+}
+
+/// Render the kernelspec picker as a popup centered over the cell area.
+fn render_kernel_picker(frame: &mut Frame, app: &App, area: Rect) {
+    use ratatui::style::{Color, Modifier, Style};
+    use ratatui::widgets::{Block, Borders, Cell, Clear, Row, Table, TableState};
+
+    let Some(picker) = &app.kernel_picker else {
+        return;
+    };
+
+    let current = app.notebook.metadata.kernel_name();
+    let rows: Vec<Row> = picker
+        .kernels
+        .iter()
+        .map(|k| {
+            let marker = if Some(k.name.as_str()) == current {
+                "*"
+            } else {
+                " "
+            };
+            Row::new(vec![
+                Cell::from(marker),
+                Cell::from(k.display_name.clone()),
+                Cell::from(k.name.clone()),
+                Cell::from(k.language.clone().unwrap_or_default()),
+                Cell::from(k.location.clone()),
             ])
-            .split(frame.area())
-    } else {
-        Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([
-                Constraint::Min(1),    // Cell area
-                Constraint::Length(1), // Status bar
-                Constraint::Length(1), // Command line
+        })
+        .collect();
+
+    let width = area.width.saturating_sub(4).min(100);
+    let height = (picker.kernels.len() as u16 + 3).min(area.height);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(1),
+            Constraint::Percentage(30),
+            Constraint::Percentage(15),
+            Constraint::Percentage(10),
+            Constraint::Fill(1),
+        ],
+    )
+    .header(
+        Row::new(vec!["", "Kernel", "Name", "Language", "Path"])
+            .style(Style::default().fg(Color::DarkGray)),
+    )
+    .row_highlight_style(
+        Style::default()
+            .fg(Color::Black)
+            .bg(Color::Cyan)
+            .add_modifier(Modifier::BOLD),
+    )
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Cyan))
+            .title(" Select kernel (Enter to switch, Esc to cancel) "),
+    );
+
+    let mut state = TableState::default().with_selected(Some(picker.selected));
+    frame.render_widget(Clear, popup);
+    frame.render_stateful_widget(table, popup, &mut state);
+}
+
+/// Render the variable explorer: name, type, size and the start of each repr.
+fn render_variables_panel(frame: &mut Frame, app: &App, area: Rect) {
+    use ratatui::style::{Color, Modifier, Style};
+    use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState, Wrap};
+
+    let panel = &app.variables;
+    let focused = app.mode == Mode::Variables;
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(if focused {
+            Color::LightYellow
+        } else {
+            Color::DarkGray
+        }))
+        .title(format!(" Variables ({}) ", panel.variables.len()));
+
+    if let Some(error) = &panel.error {
+        let paragraph = Paragraph::new(error.clone())
+            .style(Style::default().fg(Color::Red))
+            .wrap(Wrap { trim: true })
+            .block(block);
+        frame.render_widget(paragraph, area);
+        return;
+    }
+
+    let rows: Vec<Row> = panel
+        .variables
+        .iter()
+        .map(|v| {
+            // Multi-line reprs (DataFrames, arrays) are squeezed onto one line
+            let value = v.value.split_whitespace().collect::<Vec<_>>().join(" ");
+            Row::new(vec![
+                Cell::from(v.name.clone()),
+                Cell::from(v.type_name.clone()).style(Style::default().fg(Color::Cyan)),
+                Cell::from(v.size.clone()).style(Style::default().fg(Color::DarkGray)),
+                Cell::from(value),
             ])
-            .split(frame.area())
+        })
+        .collect();
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Percentage(25),
+            Constraint::Percentage(20),
+            Constraint::Percentage(15),
+            Constraint::Fill(1),
+        ],
+    )
+    .header(
+        Row::new(vec!["Name", "Type", "Size", "Value"]).style(Style::default().fg(Color::DarkGray)),
+    )
+    .row_highlight_style(if focused {
+        Style::default()
+            .fg(Color::Black)
+            .bg(Color::LightYellow)
+            .add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    })
+    .block(block);
+
+    let mut state = TableState::default().with_selected(Some(panel.selected));
+    frame.render_stateful_widget(table, area, &mut state);
+}
+
+/// Render the pager popup (documentation or `page` output, ANSI colored),
+/// centered over the cell area.
+fn render_pager(frame: &mut Frame, app: &App, area: Rect) {
+    use ansi_to_tui::IntoText;
+    use ratatui::style::{Color, Style};
+    use ratatui::text::Text;
+    use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+
+    let Some(panel) = &app.pager else {
+        return;
     };
 
-    // Render cells in the main area
-    cell::render_cell_list(frame, app, chunks[0]);
+    let text = panel
+        .text
+        .into_text()
+        .unwrap_or_else(|_| Text::raw(panel.text.clone()));
+    let total = text.lines.len();
 
-    if has_completions {
-        render_completion_panel(frame, app, chunks[1]);
-        statusbar::render(frame, app, chunks[2]);
-        render_command_line(frame, app, chunks[3]);
+    let width = area.width.saturating_sub(4).min(100);
+    let height = (total as u16 + 2).min(area.height.saturating_sub(2));
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let title = match &panel.inspect {
+        Some(target) if target.detail_level == 0 => " Documentation (? for source, Esc to close) ",
+        Some(_) => " Source (? for docs, Esc to close) ",
+        None => " Pager (Esc to close) ",
+    };
+    let position = format!(" {}/{} ", (panel.scroll as usize + 1).min(total), total);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .title(title)
+        .title_bottom(position);
+
+    let paragraph = Paragraph::new(text).block(block).scroll((panel.scroll, 0));
+    frame.render_widget(Clear, popup);
+    frame.render_widget(paragraph, popup);
+}
+
+/// Render the prompt line for a pending kernel input_request.
+/// Password prompts (getpass) are masked.
+fn render_input_prompt(frame: &mut Frame, app: &App, area: Rect) {
+    use ratatui::style::{Color, Modifier, Style};
+    use ratatui::text::{Line, Span};
+    use ratatui::widgets::Paragraph;
+
+    let Some(prompt) = &app.input_prompt else {
+        return;
+    };
+
+    let typed = if prompt.password {
+        "*".repeat(prompt.buffer.chars().count())
     } else {
-        statusbar::render(frame, app, chunks[1]);
-        render_command_line(frame, app, chunks[2]);
-    }
+        prompt.buffer.clone()
+    };
+
+    let line = Line::from(vec![
+        Span::styled(
+            prompt.prompt.clone(),
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        ),
+        Span::styled(typed, Style::default().fg(Color::White)),
+        // Block cursor
+        Span::styled(" ", Style::default().bg(Color::White)),
+    ]);
+
+    frame.render_widget(Paragraph::new(line), area);
 }
 
-/// Render the completion panel.
+/// Render the completion panel: type tag, match, and signature per line.
 fn render_completion_panel(frame: &mut Frame, app: &App, area: Rect) {
     use ratatui::style::{Color, Modifier, Style};
     use ratatui::text::{Line, Span};
     use ratatui::widgets::{Block, Borders, Paragraph};
 
+    let Some(completion) = &app.completion else {
+        return;
+    };
+
     let max_visible = area.height.saturating_sub(2) as usize; // -2 for borders
-    let total = app.completions.len();
-    let selected = app.completion_selected;
+    let total = completion.visible.len();
+    let selected = completion.selected;
 
     // Scrolling: ensure selected item is visible
     let scroll_offset = if selected >= max_visible {
@@ -64,18 +308,43 @@ fn render_completion_panel(frame: &mut Frame, app: &App, area: Rect) {
         0
     };
 
+    let tag_width = completion
+        .items
+        .iter()
+        .map(|item| item.kind_tag().len())
+        .max()
+        .unwrap_or(0);
+
     let mut lines: Vec<Line> = Vec::new();
     for i in scroll_offset..total.min(scroll_offset + max_visible) {
-        let item = &app.completions[i];
-        let style = if i == selected {
-            Style::default()
-                .fg(Color::Black)
-                .bg(Color::Cyan)
-                .add_modifier(Modifier::BOLD)
+        let item = &completion.items[completion.visible[i]];
+        let (text_style, detail_style) = if i == selected {
+            let style = Style::default().fg(Color::Black).bg(Color::Cyan);
+            (style.add_modifier(Modifier::BOLD), style)
         } else {
-            Style::default().fg(Color::White)
+            (
+                Style::default().fg(Color::White),
+                Style::default().fg(Color::DarkGray),
+            )
         };
-        lines.push(Line::from(Span::styled(item.clone(), style)));
+
+        let mut spans = Vec::new();
+        if tag_width > 0 {
+            let tag_style = if i == selected {
+                detail_style
+            } else {
+                Style::default().fg(kind_color(item.kind.as_deref()))
+            };
+            spans.push(Span::styled(
+                format!("{:<width$} ", item.kind_tag(), width = tag_width),
+                tag_style,
+            ));
+        }
+        spans.push(Span::styled(item.text.clone(), text_style));
+        if let Some(signature) = &item.signature {
+            spans.push(Span::styled(format!(" {}", signature), detail_style));
+        }
+        lines.push(Line::from(spans));
     }
 
     let title = format!(" Completions ({}/{}) ", selected + 1, total);
@@ -88,6 +357,19 @@ fn render_completion_panel(frame: &mut Frame, app: &App, area: Rect) {
     frame.render_widget(paragraph, area);
 }
 
+/// Color of a completion's type tag.
+fn kind_color(kind: Option<&str>) -> ratatui::style::Color {
+    use ratatui::style::Color;
+    match kind {
+        Some("function") => Color::Blue,
+        Some("class") => Color::Yellow,
+        Some("module") => Color::Magenta,
+        Some("keyword") => Color::Red,
+        Some("param") | Some("property") => Color::Cyan,
+        _ => Color::Green,
+    }
+}
+
 /// Render the bottom command/message line.
 fn render_command_line(frame: &mut Frame, app: &App, area: Rect) {
     use ratatui::style::{Color, Style};
@@ -102,21 +384,24 @@ fn render_command_line(frame: &mut Frame, app: &App, area: Rect) {
             crate::app::SearchDirection::Backward => "?",
         };
         format!("{}{}", prefix, app.search_buffer)
+    } else if let Some(query) = &app.json_search {
+        format!("/{}", query)
     } else {
         app.status_message.clone()
     };
 
-    let style = if app.mode == Mode::Command || app.mode == Mode::Search {
-        Style::default().fg(Color::White)
-    } else if app.status_message.starts_with("Error")
-        || app.status_message.starts_with("Save failed")
-    {
-        Style::default().fg(Color::Red)
-    } else if app.status_message.starts_with("Unsaved changes") {
-        Style::default().fg(Color::Yellow)
-    } else {
-        Style::default().fg(Color::DarkGray)
-    };
+    let style =
+        if app.mode == Mode::Command || app.mode == Mode::Search || app.json_search.is_some() {
+            Style::default().fg(Color::White)
+        } else if app.status_message.starts_with("Error")
+            || app.status_message.starts_with("Save failed")
+        {
+            Style::default().fg(Color::Red)
+        } else if app.status_message.starts_with("Unsaved changes") {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default().fg(Color::DarkGray)
+        };
 
     let paragraph = Paragraph::new(Span::styled(content, style));
     frame.render_widget(paragraph, area);
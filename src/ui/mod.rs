@@ -1,5 +1,12 @@
 pub mod cell;
+pub mod clipboard;
+pub mod console;
 pub mod highlight;
+pub mod html;
+pub mod image;
+pub mod json_tree;
 pub mod layout;
+pub mod notify;
 pub mod output;
+pub mod render_cache;
 pub mod statusbar;
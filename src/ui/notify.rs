@@ -0,0 +1,72 @@
+use std::io::Write;
+
+/// Ring the terminal bell and post a desktop notification.
+///
+/// VTE-based terminals (GNOME Terminal, Tilix), foot and urxvt understand
+/// OSC 777; most others (iTerm2, kitty, WezTerm, Windows Terminal, ghostty)
+/// understand OSC 9. Inside tmux the sequence is wrapped for passthrough,
+/// which needs `set -g allow-passthrough on`.
+pub fn send(title: &str, body: &str) {
+    let mut sequence = notification_sequence(title, body, uses_osc_777());
+    if std::env::var_os("TMUX").is_some() {
+        sequence = tmux_passthrough(&sequence);
+    }
+
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(b"\x07");
+    let _ = stdout.write_all(sequence.as_bytes());
+    let _ = stdout.flush();
+}
+
+fn uses_osc_777() -> bool {
+    let term = std::env::var("TERM").unwrap_or_default();
+    std::env::var_os("VTE_VERSION").is_some()
+        || term.starts_with("foot")
+        || term.starts_with("rxvt")
+}
+
+/// Build the OSC 777 (`notify;title;body`) or OSC 9 (`title: body`) sequence.
+fn notification_sequence(title: &str, body: &str, osc_777: bool) -> String {
+    let title = sanitize(title);
+    let body = sanitize(body);
+    if osc_777 {
+        format!("\x1b]777;notify;{};{}\x1b\\", title, body)
+    } else {
+        format!("\x1b]9;{}: {}\x1b\\", title, body)
+    }
+}
+
+/// Strip control characters (which would end the sequence early) and the
+/// `;` field separator.
+fn sanitize(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_control())
+        .map(|c| if c == ';' { ',' } else { c })
+        .collect()
+}
+
+/// Wrap an escape sequence in a tmux DCS passthrough.
+pub fn tmux_passthrough(sequence: &str) -> String {
+    format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notification_sequences() {
+        assert_eq!(
+            notification_sequence("jupyter_tui: a.ipynb", "Cell 3 finished; ok\x07", false),
+            "\x1b]9;jupyter_tui: a.ipynb: Cell 3 finished, ok\x1b\\"
+        );
+        assert_eq!(
+            notification_sequence("jupyter_tui", "Error in cell 2", true),
+            "\x1b]777;notify;jupyter_tui;Error in cell 2\x1b\\"
+        );
+        assert_eq!(
+            tmux_passthrough("\x1b]9;hi\x1b\\"),
+            "\x1bPtmux;\x1b\x1b]9;hi\x1b\x1b\\\x1b\\"
+        );
+    }
+}
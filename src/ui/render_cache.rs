@@ -0,0 +1,667 @@
+use crate::json_tree::json_mime;
+use crate::notebook::mime::MimeBundle;
+use crate::notebook::model::CellOutput;
+use crate::ui::html::{self, HtmlBlock, HtmlTable};
+use crate::ui::image::{
+    get_image_data, has_renderable_image, image_dimensions, placeholder, rasterize_svg,
+};
+use ansi_to_tui::IntoText;
+use image::DynamicImage;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+use ratatui::widgets::{Paragraph, Wrap};
+use ratatui_image::picker::Picker;
+use ratatui_image::protocol::StatefulProtocol;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Maximum height (in terminal rows) for rendered images.
+const MAX_IMAGE_HEIGHT: u16 = 20;
+
+/// Layouts of outputs and rendered markdown, reused between frames so that
+/// images are decoded, SVGs rasterized and text parsed and wrapped only
+/// when the content or the width changes.
+#[derive(Default)]
+pub struct RenderCache {
+    /// Keyed by (cell id, output index), so entries follow moved cells
+    outputs: HashMap<(String, usize), OutputLayout>,
+    /// Keyed by cell id
+    markdown: HashMap<String, MarkdownLayout>,
+}
+
+/// An output laid out for one width.
+pub struct OutputLayout {
+    /// (width, `content_size`) it was laid out for
+    stamp: (u16, usize),
+    /// Text blocks, drawn first
+    pub blocks: Vec<LayoutBlock>,
+    /// MIME type of the payload drawn as a JSON tree after the blocks. Its
+    /// rows follow the tree's folding, so they are not cached.
+    pub json: Option<String>,
+    /// Drawn last
+    pub image: Option<OutputImage>,
+    /// Rows taken even when there is nothing to draw
+    min_height: usize,
+}
+
+pub enum LayoutBlock {
+    /// Lines wrapped to `height` rows
+    Text {
+        lines: Vec<Line<'static>>,
+        height: u16,
+    },
+    Table(HtmlTable),
+}
+
+#[allow(clippy::large_enum_variant)]
+pub enum OutputImage {
+    /// One line standing in for the image when graphics are off
+    Placeholder(String),
+    /// The image drawn with the terminal's graphics protocol
+    Graphics {
+        rows: u16,
+        /// The rasterized image, for SVGs
+        svg: Option<DynamicImage>,
+        /// Created when the image is first drawn
+        protocol: Option<StatefulProtocol>,
+    },
+}
+
+/// A markdown cell rendered by tui-markdown and wrapped for one width.
+pub struct MarkdownLayout {
+    source: String,
+    width: u16,
+    pub text: Text<'static>,
+    pub height: usize,
+}
+
+impl RenderCache {
+    /// The layout of `output` at `width`, recomputed if the output has
+    /// changed size or was laid out for another width. Only the size is
+    /// compared (see `content_size`): code that replaces an output's data in
+    /// place must call `forget_output`, as update_display_data does.
+    pub fn output(
+        &mut self,
+        key: &(String, usize),
+        output: &CellOutput,
+        width: u16,
+        picker: &Picker,
+        image_placeholders: bool,
+    ) -> &mut OutputLayout {
+        let stamp = (width, content_size(output));
+        if self
+            .outputs
+            .get(key)
+            .is_none_or(|layout| layout.stamp != stamp)
+        {
+            let layout = OutputLayout::new(output, stamp, picker, image_placeholders);
+            self.outputs.insert(key.clone(), layout);
+        }
+        self.outputs.get_mut(key).expect("inserted above")
+    }
+
+    /// The markdown cell `cell_id` rendered from `source` at `width`.
+    pub fn markdown(&mut self, cell_id: &str, source: &str, width: u16) -> &MarkdownLayout {
+        let current = self
+            .markdown
+            .get(cell_id)
+            .is_some_and(|layout| layout.width == width && layout.source == source);
+        if !current {
+            let layout = MarkdownLayout::new(source, width);
+            self.markdown.insert(cell_id.to_string(), layout);
+        }
+        &self.markdown[cell_id]
+    }
+
+    /// Data columns of the widest HTML table among the laid-out outputs of
+    /// cell `cell_id` (None if it has none, or hasn't been drawn).
+    pub fn table_columns(&self, cell_id: &str) -> Option<usize> {
+        self.outputs
+            .iter()
+            .filter(|((id, _), _)| id == cell_id)
+            .flat_map(|(_, layout)| &layout.blocks)
+            .filter_map(|block| match block {
+                LayoutBlock::Table(table) => Some(table.data_columns()),
+                LayoutBlock::Text { .. } => None,
+            })
+            .max()
+    }
+
+    /// Drop the layout of one output (its data was replaced).
+    pub fn forget_output(&mut self, key: &(String, usize)) {
+        self.outputs.remove(key);
+    }
+
+    /// Drop everything cached for the cell with id `cell_id`.
+    pub fn forget_cell(&mut self, cell_id: &str) {
+        self.outputs.retain(|(id, _), _| id != cell_id);
+        self.markdown.remove(cell_id);
+    }
+
+    pub fn clear(&mut self) {
+        self.outputs.clear();
+        self.markdown.clear();
+    }
+}
+
+impl OutputLayout {
+    fn new(
+        output: &CellOutput,
+        stamp: (u16, usize),
+        picker: &Picker,
+        image_placeholders: bool,
+    ) -> Self {
+        let width = stamp.0;
+        let mut layout = Self {
+            stamp,
+            blocks: Vec::new(),
+            json: None,
+            image: None,
+            min_height: 0,
+        };
+        match output {
+            CellOutput::Stream { name, text } => {
+                let color = (name == "stderr").then_some(Color::Red);
+                layout.push_text(ansi_lines(text.rendered(), color), width);
+                layout.min_height = 1;
+            }
+            CellOutput::Error { traceback, .. } => {
+                let lines = traceback
+                    .iter()
+                    .flat_map(|line| match line.into_text() {
+                        Ok(parsed) => parsed.lines,
+                        Err(_) => vec![Line::from(Span::styled(
+                            line.clone(),
+                            Style::default().fg(Color::Red),
+                        ))],
+                    })
+                    .collect();
+                layout.push_text(lines, width);
+                layout.min_height = 1;
+            }
+            CellOutput::ExecuteResult { data, .. } => {
+                // Text (JSON tree, HTML tables or text/plain), then the image
+                layout.push_rich_text(data, Color::Green, width);
+                layout.image = output_image(data, width, picker, image_placeholders);
+            }
+            CellOutput::DisplayData { data, .. } => {
+                // The image when there is one, else the text
+                layout.image = output_image(data, width, picker, image_placeholders);
+                if layout.image.is_none() {
+                    layout.push_rich_text(data, Color::Magenta, width);
+                }
+                layout.min_height = 1;
+            }
+        }
+        layout
+    }
+
+    /// A JSON tree (see `json_tree::json_mime`), the HTML when it is
+    /// shown (see `html::displayed_blocks`), else text/plain.
+    fn push_rich_text(&mut self, data: &MimeBundle, color: Color, width: u16) {
+        if let Some(mime) = json_mime(data) {
+            self.json = Some(mime.to_string());
+        } else if let Some(blocks) = html::displayed_blocks(data) {
+            for block in blocks {
+                match block {
+                    HtmlBlock::Text(lines) => self.push_text(
+                        lines
+                            .into_iter()
+                            .map(|line| Line::from(Span::styled(line, Style::default().fg(color))))
+                            .collect(),
+                        width,
+                    ),
+                    HtmlBlock::Table(table) => self.blocks.push(LayoutBlock::Table(table)),
+                }
+            }
+        } else if let Some(text) = data.text("text/plain") {
+            self.push_text(ansi_lines(text, Some(color)), width);
+        }
+    }
+
+    fn push_text(&mut self, lines: Vec<Line<'static>>, width: u16) {
+        if lines.is_empty() {
+            return;
+        }
+        let text = Text::from(lines);
+        let height = Paragraph::new(text.clone())
+            .wrap(Wrap { trim: false })
+            .line_count(width);
+        self.blocks.push(LayoutBlock::Text {
+            lines: text.lines,
+            height: height as u16,
+        });
+    }
+
+    /// The payload drawn as a JSON tree, if any.
+    pub fn json_root<'a>(&self, output: &'a CellOutput) -> Option<&'a Value> {
+        let mime = self.json.as_deref()?;
+        match output {
+            CellOutput::ExecuteResult { data, .. } | CellOutput::DisplayData { data, .. } => {
+                data.get(mime)
+            }
+            _ => None,
+        }
+    }
+
+    /// Rows taken, given those of the JSON tree if there is one.
+    pub fn height(&self, json_height: usize) -> usize {
+        let blocks: usize = self.blocks.iter().map(|b| b.height() as usize).sum();
+        let image = self
+            .image
+            .as_ref()
+            .map_or(0, |image| image.height() as usize);
+        (blocks + json_height + image).max(self.min_height)
+    }
+}
+
+impl LayoutBlock {
+    pub fn height(&self) -> u16 {
+        match self {
+            LayoutBlock::Text { height, .. } => *height,
+            LayoutBlock::Table(table) => table.height() as u16,
+        }
+    }
+}
+
+impl OutputImage {
+    pub fn height(&self) -> u16 {
+        match self {
+            OutputImage::Placeholder(_) => 1,
+            OutputImage::Graphics { rows, .. } => *rows,
+        }
+    }
+}
+
+impl MarkdownLayout {
+    fn new(source: &str, width: u16) -> Self {
+        let text = convert_core_text(tui_markdown::from_str(if source.is_empty() {
+            " "
+        } else {
+            source
+        }));
+        let height = Paragraph::new(text.clone())
+            .wrap(Wrap { trim: false })
+            .line_count(width)
+            .max(1);
+        Self {
+            source: source.to_string(),
+            width,
+            text,
+            height,
+        }
+    }
+}
+
+/// A cheap stand-in for an output's content: its size. Outputs change in
+/// place only by growing (streams) or through update_display_data, which
+/// calls `forget_output`.
+fn content_size(output: &CellOutput) -> usize {
+    match output {
+        CellOutput::Stream { text, .. } => text.raw().len(),
+        CellOutput::Error { traceback, .. } => traceback.len(),
+        CellOutput::ExecuteResult { data, .. } | CellOutput::DisplayData { data, .. } => {
+            data.iter().count()
+        }
+    }
+}
+
+/// Parse ANSI escapes, giving unstyled text `color`.
+fn ansi_lines(text: &str, color: Option<Color>) -> Vec<Line<'static>> {
+    match text.into_text() {
+        Ok(parsed) => parsed
+            .lines
+            .into_iter()
+            .map(|mut line| {
+                for span in &mut line.spans {
+                    if span.style.fg.is_none() {
+                        span.style.fg = color;
+                    }
+                }
+                line
+            })
+            .collect(),
+        Err(_) => {
+            let style = color.map_or_else(Style::default, |color| Style::default().fg(color));
+            text.lines()
+                .map(|line| Line::from(Span::styled(line.to_string(), style)))
+                .collect()
+        }
+    }
+}
+
+/// The image of an execute_result or display_data: its size read from the
+/// header (or the SVG rasterized for `width`), or its placeholder line.
+fn output_image(
+    data: &MimeBundle,
+    width: u16,
+    picker: &Picker,
+    image_placeholders: bool,
+) -> Option<OutputImage> {
+    if !has_renderable_image(data) {
+        return None;
+    }
+    if image_placeholders {
+        return Some(OutputImage::Placeholder(placeholder(data)));
+    }
+    let font_size = picker.font_size();
+    let (size, svg) = match get_image_data(data) {
+        Some(b64) => (image_dimensions(b64), None),
+        None => {
+            // No bigger than the image can be drawn
+            let max_size = (
+                u32::from(width) * u32::from(font_size.0),
+                u32::from(MAX_IMAGE_HEIGHT) * u32::from(font_size.1),
+            );
+            let svg = data
+                .text("image/svg+xml")
+                .and_then(|svg| rasterize_svg(svg, max_size));
+            (svg.as_ref().map(|img| (img.width(), img.height())), svg)
+        }
+    };
+    Some(OutputImage::Graphics {
+        rows: size.map_or(1, |size| image_height_rows(size, font_size, width)),
+        svg,
+        protocol: None,
+    })
+}
+
+/// Calculate the height in terminal rows that an image of `size` pixels
+/// would occupy, given the picker's font size and available width.
+fn image_height_rows(size: (u32, u32), font_size: (u16, u16), available_width: u16) -> u16 {
+    if font_size.0 == 0 || font_size.1 == 0 || available_width == 0 {
+        return 1;
+    }
+    let avail_px_w = (available_width as u32) * (font_size.0 as u32);
+    let scale = (avail_px_w as f64) / (size.0 as f64).max(1.0);
+    let scale = scale.min(1.0); // don't upscale
+    let h_px = (size.1 as f64 * scale).ceil() as u32;
+    let rows = (h_px as f64 / font_size.1 as f64).ceil() as u16;
+    rows.clamp(1, MAX_IMAGE_HEIGHT)
+}
+
+/// Convert a `ratatui_core::text::Text` (from tui-markdown) to `ratatui::text::Text`.
+/// Both have the same structure but are from different crate versions.
+fn convert_core_text(core_text: ratatui_core::text::Text<'_>) -> Text<'static> {
+    let lines: Vec<Line<'static>> = core_text
+        .lines
+        .into_iter()
+        .map(|line| {
+            let spans: Vec<Span<'static>> = line
+                .spans
+                .into_iter()
+                .map(|span| {
+                    let content: String = span.content.to_string();
+                    let style = convert_core_style(span.style);
+                    Span::styled(content, style)
+                })
+                .collect();
+            Line::from(spans)
+        })
+        .collect();
+    Text::from(lines)
+}
+
+/// Convert a `ratatui_core::style::Style` to `ratatui::style::Style`.
+fn convert_core_style(s: ratatui_core::style::Style) -> Style {
+    let mut style = Style::default();
+    if let Some(fg) = s.fg {
+        style = style.fg(convert_core_color(fg));
+    }
+    if let Some(bg) = s.bg {
+        style = style.bg(convert_core_color(bg));
+    }
+    // Convert modifiers
+    let m = s.add_modifier;
+    if m.contains(ratatui_core::style::Modifier::BOLD) {
+        style = style.add_modifier(Modifier::BOLD);
+    }
+    if m.contains(ratatui_core::style::Modifier::ITALIC) {
+        style = style.add_modifier(Modifier::ITALIC);
+    }
+    if m.contains(ratatui_core::style::Modifier::UNDERLINED) {
+        style = style.add_modifier(Modifier::UNDERLINED);
+    }
+    if m.contains(ratatui_core::style::Modifier::CROSSED_OUT) {
+        style = style.add_modifier(Modifier::CROSSED_OUT);
+    }
+    if m.contains(ratatui_core::style::Modifier::DIM) {
+        style = style.add_modifier(Modifier::DIM);
+    }
+    if m.contains(ratatui_core::style::Modifier::REVERSED) {
+        style = style.add_modifier(Modifier::REVERSED);
+    }
+    style
+}
+
+/// Convert a `ratatui_core::style::Color` to `ratatui::style::Color`.
+fn convert_core_color(c: ratatui_core::style::Color) -> Color {
+    match c {
+        ratatui_core::style::Color::Reset => Color::Reset,
+        ratatui_core::style::Color::Black => Color::Black,
+        ratatui_core::style::Color::Red => Color::Red,
+        ratatui_core::style::Color::Green => Color::Green,
+        ratatui_core::style::Color::Yellow => Color::Yellow,
+        ratatui_core::style::Color::Blue => Color::Blue,
+        ratatui_core::style::Color::Magenta => Color::Magenta,
+        ratatui_core::style::Color::Cyan => Color::Cyan,
+        ratatui_core::style::Color::Gray => Color::Gray,
+        ratatui_core::style::Color::DarkGray => Color::DarkGray,
+        ratatui_core::style::Color::LightRed => Color::LightRed,
+        ratatui_core::style::Color::LightGreen => Color::LightGreen,
+        ratatui_core::style::Color::LightYellow => Color::LightYellow,
+        ratatui_core::style::Color::LightBlue => Color::LightBlue,
+        ratatui_core::style::Color::LightMagenta => Color::LightMagenta,
+        ratatui_core::style::Color::LightCyan => Color::LightCyan,
+        ratatui_core::style::Color::White => Color::White,
+        ratatui_core::style::Color::Rgb(r, g, b) => Color::Rgb(r, g, b),
+        ratatui_core::style::Color::Indexed(i) => Color::Indexed(i),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::App;
+    use crate::kernel::backend::NoKernel;
+    use crate::notebook::model::{Cell, Notebook};
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use criterion::Criterion;
+    use image::{ImageFormat, Rgb, RgbImage};
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+    use ratatui_image::picker::ProtocolType;
+    use std::io::Cursor;
+    use std::time::{Duration, Instant};
+
+    fn picker() -> Picker {
+        let mut picker = Picker::from_fontsize((8, 16));
+        picker.set_protocol_type(ProtocolType::Halfblocks);
+        picker
+    }
+
+    /// A matplotlib-like display_data: a PNG of `width` x `height` pixels
+    /// with its `<Figure>` repr.
+    fn plot(width: u32, height: u32) -> CellOutput {
+        let pixels =
+            RgbImage::from_fn(width, height, |x, y| Rgb([x as u8, y as u8, (x ^ y) as u8]));
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(pixels)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        let media = serde_json::json!({
+            "image/png": BASE64.encode(png),
+            "text/plain": format!("<Figure size {}x{} with 1 Axes>", width, height),
+        });
+        CellOutput::DisplayData {
+            data: MimeBundle::from_media(&serde_json::from_value(media).unwrap()),
+            metadata: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_output_layout_is_cached_until_content_or_width_changes() {
+        let mut cache = RenderCache::default();
+        let picker = picker();
+        let key = ("cell".to_string(), 0);
+        let mut cell = Cell::new_code("");
+        cell.append_stream("stdout", "0123456789abcdef\n");
+
+        let layout = cache.output(&key, &cell.outputs[0], 10, &picker, false);
+        assert_eq!(layout.height(0), 2);
+        // Marks the entry so a rebuild shows
+        layout.blocks.clear();
+        assert_eq!(
+            cache
+                .output(&key, &cell.outputs[0], 10, &picker, false)
+                .height(0),
+            1
+        );
+
+        cell.append_stream("stdout", "more\n");
+        assert_eq!(
+            cache
+                .output(&key, &cell.outputs[0], 10, &picker, false)
+                .height(0),
+            3
+        );
+        assert_eq!(
+            cache
+                .output(&key, &cell.outputs[0], 20, &picker, false)
+                .height(0),
+            2
+        );
+
+        cache.forget_cell("cell");
+        assert!(cache.outputs.is_empty());
+    }
+
+    #[test]
+    fn test_table_columns_come_from_the_layout() {
+        let mut cache = RenderCache::default();
+        let html = "<table><tr><th>a</th><th>b</th><th>c</th></tr>\
+                    <tr><td>1</td><td>2</td><td>3</td></tr></table>";
+        let output = CellOutput::DisplayData {
+            data: MimeBundle::from_media(
+                &serde_json::from_value(serde_json::json!({"text/html": html})).unwrap(),
+            ),
+            metadata: Default::default(),
+        };
+        assert_eq!(cache.table_columns("cell"), None, "not drawn yet");
+
+        cache.output(&("cell".to_string(), 0), &output, 40, &picker(), false);
+        assert_eq!(cache.table_columns("cell"), Some(3));
+        assert_eq!(cache.table_columns("other"), None);
+    }
+
+    #[test]
+    fn test_image_layout() {
+        let mut cache = RenderCache::default();
+        let picker = picker();
+        let key = ("cell".to_string(), 0);
+        let output = plot(640, 480);
+
+        // 40 columns of 8 pixels: scaled to half, 240 pixels in rows of 16
+        let layout = cache.output(&key, &output, 40, &picker, false);
+        assert!(layout.blocks.is_empty());
+        assert_eq!(layout.height(0), 15);
+        // Never upscaled, and never taller than MAX_IMAGE_HEIGHT
+        assert_eq!(
+            image_height_rows((640, 480), (8, 16), 200),
+            MAX_IMAGE_HEIGHT
+        );
+        assert_eq!(image_height_rows((64, 48), (8, 16), 200), 3);
+
+        let layout = cache.output(&key, &output, 40, &picker, true);
+        assert_eq!(layout.height(0), 15, "cached for this width");
+        cache.forget_output(&key);
+        let layout = cache.output(&key, &output, 40, &picker, true);
+        assert!(
+            matches!(&layout.image, Some(OutputImage::Placeholder(text)) if text == "[image 640x480 png]")
+        );
+        assert_eq!(layout.height(0), 1);
+    }
+
+    #[test]
+    fn test_markdown_layout() {
+        let mut cache = RenderCache::default();
+        assert_eq!(cache.markdown("md", "# Title\n\nSome text", 80).height, 3);
+        // "# Title" and "Some text" both wrap
+        assert_eq!(cache.markdown("md", "# Title\n\nSome text", 5).height, 5);
+        assert_eq!(cache.markdown("md", "", 80).height, 1);
+    }
+
+    /// Frame time of a 200-cell notebook of plots, redrawing the same view
+    /// (as the idle tick does) and scrolling through it, and the same with
+    /// the cache emptied before every frame. Run with
+    /// `cargo test --release bench_frame_time -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_frame_time() {
+        let mut notebook = Notebook::new();
+        let figure = plot(640, 480);
+        notebook.cells = (0..200)
+            .map(|i| {
+                if i % 5 == 0 {
+                    let mut cell = Cell::new_markdown(&format!(
+                        "## Experiment {}\n\nLoss curve for run **{}**, with *log* scale.",
+                        i / 5,
+                        i
+                    ));
+                    cell.rendered = true;
+                    cell
+                } else {
+                    let mut cell =
+                        Cell::new_code(&format!("plt.plot(losses[{}])\nplt.yscale('log')", i));
+                    cell.append_stream("stdout", &format!("run {}: final loss 0.0{}\n", i, i));
+                    cell.outputs.push(figure.clone());
+                    cell
+                }
+            })
+            .collect();
+
+        let (event_tx, _event_rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut app = App::with_kernel(
+            notebook,
+            Box::new(NoKernel),
+            Box::new(NoKernel),
+            event_tx,
+            picker(),
+        );
+        let mut terminal = Terminal::new(TestBackend::new(160, 48)).unwrap();
+        let mut criterion = Criterion::default()
+            .warm_up_time(Duration::from_secs(1))
+            .measurement_time(Duration::from_secs(3));
+
+        for (name, cached, scroll) in [
+            ("idle, cached", true, false),
+            ("idle, uncached", false, false),
+            ("scrolling, cached", true, true),
+            ("scrolling, uncached", false, true),
+        ] {
+            app.selected_cell = 100;
+            app.scroll_offset = 100;
+            criterion.bench_function(name, |b| {
+                b.iter_custom(|frames| {
+                    let mut total = Duration::ZERO;
+                    for _ in 0..frames {
+                        if scroll {
+                            app.selected_cell = (app.selected_cell + 1) % 200;
+                        }
+                        if !cached {
+                            app.render_cache.clear();
+                        }
+                        let start = Instant::now();
+                        terminal
+                            .draw(|frame| crate::ui::layout::render(frame, &mut app))
+                            .unwrap();
+                        total += start.elapsed();
+                    }
+                    total
+                })
+            });
+        }
+        criterion.final_summary();
+    }
+}
@@ -32,6 +32,30 @@ pub fn render(frame: &mut Frame, app: &App, area: Rect) {
             .fg(Color::Black)
             .bg(Color::Cyan)
             .add_modifier(Modifier::BOLD),
+        crate::app::Mode::Input => Style::default()
+            .fg(Color::Black)
+            .bg(Color::LightBlue)
+            .add_modifier(Modifier::BOLD),
+        crate::app::Mode::KernelPicker => Style::default()
+            .fg(Color::Black)
+            .bg(Color::LightMagenta)
+            .add_modifier(Modifier::BOLD),
+        crate::app::Mode::Pager => Style::default()
+            .fg(Color::Black)
+            .bg(Color::LightCyan)
+            .add_modifier(Modifier::BOLD),
+        crate::app::Mode::Console => Style::default()
+            .fg(Color::Black)
+            .bg(Color::LightGreen)
+            .add_modifier(Modifier::BOLD),
+        crate::app::Mode::Variables => Style::default()
+            .fg(Color::Black)
+            .bg(Color::LightYellow)
+            .add_modifier(Modifier::BOLD),
+        crate::app::Mode::JsonTree => Style::default()
+            .fg(Color::Black)
+            .bg(Color::LightRed)
+            .add_modifier(Modifier::BOLD),
     };
 
     let mode_text = format!(" {} ", app.mode);
@@ -81,6 +105,10 @@ pub fn render(frame: &mut Frame, app: &App, area: Rect) {
     let kernel_style = match app.kernel_status.as_str() {
         "busy" => Style::default().fg(Color::Black).bg(Color::Yellow),
         "idle" => Style::default().fg(Color::Black).bg(Color::Green),
+        "dead" => Style::default()
+            .fg(Color::White)
+            .bg(Color::Red)
+            .add_modifier(Modifier::BOLD),
         _ => Style::default().fg(Color::Black).bg(Color::Red),
     };
     let kernel_text = format!(" {} ", app.kernel_status);
@@ -0,0 +1,281 @@
+use crate::app::Mode;
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Python script defining `listing(ns)`, which lists a namespace as JSON.
+/// Modules, classes, functions and IPython's own names (`In`, `Out`,
+/// `_`-prefixed) are left out. Values are shown with a bounded
+/// `reprlib.Repr`, and a value whose size or repr raises becomes a
+/// placeholder row (`<ValueError>`) instead of failing the whole listing.
+const NAMESPACE_SCRIPT: &str = r#"
+import json, reprlib
+
+short = reprlib.Repr()
+short.maxlevel = 2
+short.maxstring = short.maxother = short.maxlong = 200
+short.maxlist = short.maxtuple = short.maxdict = 10
+short.maxset = short.maxfrozenset = short.maxdeque = short.maxarray = 10
+hidden_names = ('In', 'Out', 'exit', 'quit', 'get_ipython')
+hidden_types = (type, type(json), type(len), type(lambda: 0))
+
+def row(name, value):
+    type_name = type(value).__name__
+    try:
+        if hasattr(value, 'shape'):
+            size = str(value.shape)
+        elif hasattr(value, '__len__'):
+            size = str(len(value))
+        else:
+            size = ''
+        return {'name': name, 'type': type_name, 'size': size,
+                'value': short.repr(value)[:200]}
+    except Exception as e:
+        return {'name': name, 'type': type_name, 'size': '',
+                'value': '<%s>' % type(e).__name__}
+
+def listing(ns):
+    rows = []
+    for name, value in sorted(ns.items(), key=lambda item: item[0]):
+        if name.startswith('_') or name in hidden_names:
+            continue
+        try:
+            if isinstance(value, hidden_types):
+                continue
+        except Exception:
+            pass
+        rows.append(row(name, value))
+    return json.dumps(rows)
+"#;
+
+/// One entry of the kernel's user namespace.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Variable {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_name: String,
+    /// `shape` for arrays and DataFrames, `len()` for containers, else empty
+    pub size: String,
+    /// Start of the repr
+    pub value: String,
+}
+
+/// The variable explorer side panel (`V`, `:vars`).
+pub struct VariablePanel {
+    pub visible: bool,
+    pub variables: Vec<Variable>,
+    pub selected: usize,
+    /// Why the last refresh failed, shown in place of the list
+    pub error: Option<String>,
+    /// Mode to return to when the panel loses focus
+    pub return_mode: Mode,
+}
+
+impl VariablePanel {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            variables: Vec::new(),
+            selected: 0,
+            error: None,
+            return_mode: Mode::Normal,
+        }
+    }
+
+    pub fn selected_variable(&self) -> Option<&Variable> {
+        self.variables.get(self.selected)
+    }
+
+    /// Replace the list, keeping the selection on the same name if it's still there.
+    pub fn update(&mut self, variables: Vec<Variable>) {
+        let selected_name = self.selected_variable().map(|v| v.name.clone());
+        self.selected = selected_name
+            .and_then(|name| variables.iter().position(|v| v.name == name))
+            .unwrap_or(self.selected)
+            .min(variables.len().saturating_sub(1));
+        self.variables = variables;
+        self.error = None;
+    }
+}
+
+/// The user_expressions to send for a namespace listing.
+pub fn namespace_request() -> HashMap<String, String> {
+    // user_expressions are evaluated, not executed, so the script runs
+    // through exec() in a namespace of its own
+    let script = Value::String(NAMESPACE_SCRIPT.to_string()).to_string();
+    let expression = format!(
+        "(lambda ns: exec({}, ns) or ns['listing'](globals()))({{}})",
+        script
+    );
+    HashMap::from([("namespace".to_string(), expression)])
+}
+
+/// The user_expressions to send for the full view of variable `name`:
+/// `to_string()` for pandas DataFrames and Series, `repr()` otherwise.
+pub fn detail_request(name: &str) -> HashMap<String, String> {
+    // A JSON string literal is also a valid Python one
+    let name = Value::String(name.to_string()).to_string();
+    let expression = format!(
+        "__import__('json').dumps((lambda v: v.to_string(max_rows=1000) \
+         if type(v).__name__ in ('DataFrame', 'Series') else repr(v))(globals()[{}]))",
+        name
+    );
+    HashMap::from([("detail".to_string(), expression)])
+}
+
+/// Parse the reply to `namespace_request`.
+pub fn parse_namespace(results: &HashMap<String, String>) -> Result<Vec<Variable>> {
+    let json = expression_json(results, "namespace")?;
+    serde_json::from_str(&json).context("Unexpected variable listing")
+}
+
+/// Parse the reply to `detail_request`.
+pub fn parse_detail(results: &HashMap<String, String>) -> Result<String> {
+    let json = expression_json(results, "detail")?;
+    serde_json::from_str(&json).context("Unexpected variable repr")
+}
+
+/// The JSON text produced by the expression under `key`. Expressions
+/// evaluate to `json.dumps(...)`, so the result's text/plain is the Python
+/// repr of an ASCII string.
+fn expression_json(results: &HashMap<String, String>, key: &str) -> Result<String> {
+    let result: Value = serde_json::from_str(results.get(key).context("No result from kernel")?)?;
+    if result["status"] != "ok" {
+        bail!(
+            "{}: {}",
+            result["ename"].as_str().unwrap_or("Error"),
+            result["evalue"].as_str().unwrap_or_default()
+        );
+    }
+    let repr = result["data"]["text/plain"]
+        .as_str()
+        .context("Result has no text/plain")?;
+    unquote_python_str(repr).context("Result is not a string")
+}
+
+/// Undo `repr()` of an ASCII Python string: strip the quotes and unescape
+/// backslashes, quote characters and `\xNN` (JSON leaves DEL unescaped).
+fn unquote_python_str(repr: &str) -> Option<String> {
+    let quote = repr.chars().next().filter(|&c| c == '\'' || c == '"')?;
+    let inner = repr.strip_prefix(quote)?.strip_suffix(quote)?;
+    let mut text = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next()? {
+                escaped @ ('\\' | '\'' | '"') => text.push(escaped),
+                'x' => {
+                    let hex: String = chars.by_ref().take(2).collect();
+                    text.push(char::from(u8::from_str_radix(&hex, 16).ok()?));
+                }
+                _ => return None,
+            }
+        } else {
+            text.push(c);
+        }
+    }
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results(key: &str, result: Value) -> HashMap<String, String> {
+        HashMap::from([(key.to_string(), result.to_string())])
+    }
+
+    #[test]
+    fn test_unquote_python_str() {
+        assert_eq!(unquote_python_str(r#"'[1, 2]'"#).as_deref(), Some("[1, 2]"));
+        assert_eq!(
+            unquote_python_str(r#"'{"a": "it\'s \\n"}'"#).as_deref(),
+            Some(r#"{"a": "it's \n"}"#)
+        );
+        assert_eq!(
+            unquote_python_str(r#""'quoted'""#).as_deref(),
+            Some("'quoted'")
+        );
+        assert_eq!(unquote_python_str(r"'a\x7fb'").as_deref(), Some("a\x7fb"));
+        assert_eq!(unquote_python_str("42"), None);
+    }
+
+    #[test]
+    fn test_parse_namespace() {
+        let listing = r#"'[{"name": "df", "type": "DataFrame", "size": "(3, 2)", "value": "   a  b\\n0  1  2"}]'"#;
+        let reply = results(
+            "namespace",
+            serde_json::json!({"status": "ok", "data": {"text/plain": listing}, "metadata": {}}),
+        );
+        let variables = parse_namespace(&reply).unwrap();
+        assert_eq!(
+            variables,
+            [Variable {
+                name: "df".to_string(),
+                type_name: "DataFrame".to_string(),
+                size: "(3, 2)".to_string(),
+                value: "   a  b\n0  1  2".to_string(),
+            }]
+        );
+
+        let error = results(
+            "namespace",
+            serde_json::json!({"status": "error", "ename": "NameError", "evalue": "oops", "traceback": []}),
+        );
+        assert_eq!(
+            parse_namespace(&error).unwrap_err().to_string(),
+            "NameError: oops"
+        );
+    }
+
+    #[test]
+    fn test_namespace_expression_in_python() {
+        // Evaluated the way ipykernel evaluates user_expressions
+        let script = "class Bad:\n\
+                      \x20   def __len__(self): raise ValueError('no')\n\
+                      bad = Bad()\n\
+                      big = list(range(1000))\n\
+                      n = 3\n\
+                      import sys\n\
+                      print(eval(sys.argv[1]))";
+        let output = match std::process::Command::new("python3")
+            .args(["-c", script, &namespace_request()["namespace"]])
+            .output()
+        {
+            Ok(output) => output,
+            Err(_) => return, // No Python to try it with
+        };
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let variables: Vec<Variable> = serde_json::from_slice(&output.stdout).unwrap();
+        let names: Vec<&str> = variables.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, ["bad", "big", "n"]);
+        assert_eq!(variables[0].value, "<ValueError>");
+        assert_eq!(variables[1].size, "1000");
+        assert_eq!(variables[1].value, "[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, ...]");
+        assert_eq!(variables[2].value, "3");
+    }
+
+    #[test]
+    fn test_update_keeps_selection_by_name() {
+        let variable = |name: &str| Variable {
+            name: name.to_string(),
+            type_name: "int".to_string(),
+            size: String::new(),
+            value: "1".to_string(),
+        };
+        let mut panel = VariablePanel::new();
+        panel.update(vec![variable("a"), variable("b"), variable("c")]);
+        panel.selected = 1;
+        panel.update(vec![variable("a0"), variable("a"), variable("b")]);
+        assert_eq!(panel.selected_variable().unwrap().name, "b");
+        panel.update(vec![variable("z")]);
+        assert_eq!(panel.selected, 0);
+        panel.update(Vec::new());
+        assert_eq!(panel.selected_variable(), None);
+    }
+}